@@ -0,0 +1,660 @@
+use crate::logger::Logger;
+use crate::Args;
+use crate::external_tools;
+use crate::read_fasta;
+use crate::synima::CitationInfo;
+use crate::util::get_version;
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A genome whose parsed pep/cds FASTA is searched against
+pub struct Species {
+    pub name: String,
+    pub fasta: PathBuf,
+    pub db_prefix: PathBuf, // base path (no ext)
+}
+
+/// A single pairwise search: query genome FASTA vs subject genome database
+#[derive(Clone)]
+pub struct SearchJob {
+    pub q_name: String,
+    pub s_name: String,
+    pub q_fasta: PathBuf,
+    pub q_db_prefix: PathBuf, // query genome database (used by MMseqs2)
+    pub s_fasta: PathBuf,
+    pub db_prefix: PathBuf,
+    pub out_path: PathBuf,
+    pub max_target_seqs: usize,
+    pub log_path: Option<PathBuf>, // stdout/stderr of the search tools (None = discarded)
+}
+
+/// Executables resolved for an aligner (bundled bin dir first, then PATH)
+#[derive(Debug, Clone)]
+pub struct AlignerTools {
+    pub db_builder: PathBuf,
+    pub searcher: PathBuf,
+}
+
+/// An all-vs-all search backend used by the align-all step.
+///
+/// Implementations only describe how to call their tools. Job scheduling,
+/// output naming (`{q}_vs_{s}.out`) and logging are handled in `blast.rs`.
+pub trait Aligner: Sync {
+    /// Name used with --aligner
+    fn name(&self) -> &'static str;
+
+    /// Name used on the methods page and for citations
+    fn display_name(&self) -> &'static str;
+
+    /// Alignment types ("pep", "cds") this aligner can search
+    fn supported_alphabets(&self) -> &'static [&'static str];
+
+    /// Executables for (database builder, searcher)
+    fn executables(&self, alignment_type: &str) -> (&'static str, &'static str);
+
+    /// Build the database for one species
+    fn build_db(&self, tools: &AlignerTools, species: &Species, alignment_type: &str, logger: &Logger) -> Result<(), String>;
+
+    /// Command for one pairwise search writing tabular output (see `OUTPUT_COLUMNS`) to `job.out_path`
+    fn search_command(&self, tools: &AlignerTools, job: &SearchJob, args: &Args, threads: usize) -> Result<Command, String>;
+
+    /// Version string for the methods page, from the resolved search executable
+    fn version(&self, searcher: &Path) -> String;
+
+    /// Citation for the methods page
+    fn citation(&self) -> Option<CitationInfo>;
+
+    /// Whether one database of all genomes can be searched instead of one per genome (--combined_db)
+    fn supports_combined_db(&self) -> bool {
+        false
+    }
+
+    /// Aligner specific parameters for the methods page, as (name, value)
+    fn parameters(&self, _args: &Args) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// Rewrite the search output as `OUTPUT_COLUMNS` tabular if needed
+    fn normalise_output(&self, job: &SearchJob) -> Result<(), String> {
+        normalise_tabular(&job.out_path, None, None)
+    }
+
+    /// Run one pairwise search and normalise its output
+    fn search_pair(&self, tools: &AlignerTools, job: &SearchJob, args: &Args, threads: usize, logger: &Logger) -> Result<(), String> {
+        let mut cmd = self.search_command(tools, job, args, threads)?;
+        attach_log(&mut cmd, job)?;
+
+        logger.information(&format!("search_pair: Running: {}", render_cmd(&cmd)));
+
+        let status = cmd
+            .status()
+            .map_err(|e| format!("failed to run {} for {} vs {}: {}", self.display_name(), job.q_name, job.s_name, e))?;
+
+        if !status.success() {
+            return Err(format!("{} search failed for {} vs {} ({})", self.display_name(), job.q_name, job.s_name, status));
+        }
+
+        self.normalise_output(job)
+    }
+}
+
+/// Columns of every {q}_vs_{s}.out file: the 12 m8 columns plus query and subject lengths
+pub const OUTPUT_COLUMNS: [&str; 14] = [
+    "qseqid", "sseqid", "pident", "length", "mismatch", "gapopen",
+    "qstart", "qend", "sstart", "send", "evalue", "bitscore", "qlen", "slen",
+];
+
+/// All aligners accepted by --aligner
+pub const ALIGNER_NAMES: [&str; 4] = ["diamond", "mmseqs", "blastplus", "blastlegacy"];
+
+/// Look up an aligner by its --aligner name
+pub fn get_aligner(name: &str) -> Option<Box<dyn Aligner>> {
+    match name {
+        "diamond" => Some(Box::new(Diamond)),
+        "mmseqs" | "mmseqs2" => Some(Box::new(MmSeqs)),
+        "blastplus" => Some(Box::new(BlastPlus)),
+        "blastlegacy" | "legacy" => Some(Box::new(BlastLegacy)),
+        _ => None,
+    }
+}
+
+/// Look up an aligner, or exit with the list of valid names
+pub fn get_aligner_or_exit(name: &str, logger: &Logger) -> Box<dyn Aligner> {
+    get_aligner(name).unwrap_or_else(|| {
+        logger.error(&format!("get_aligner: unsupported aligner '{}', expected one of: {}", name, ALIGNER_NAMES.join(", ")));
+        std::process::exit(1);
+    })
+}
+
+pub fn resolve_aligner_tools(aligner: &dyn Aligner, alignment_type: &str, bin_dir: &Path, logger: &Logger) -> AlignerTools {
+    let (db_prog, search_prog) = aligner.executables(alignment_type);
+
+    AlignerTools {
+        db_builder: external_tools::find_executable(db_prog, bin_dir, logger),
+        searcher: external_tools::find_executable(search_prog, bin_dir, logger),
+    }
+}
+
+/// Send a search command's stdout/stderr to the job log (appending), or discard them
+pub fn attach_log(cmd: &mut Command, job: &SearchJob) -> Result<(), String> {
+    let Some(log_path) = &job.log_path else {
+        cmd.stdout(Stdio::null()).stderr(Stdio::null());
+        return Ok(());
+    };
+
+    let mut log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .map_err(|e| format!("failed to open log {}: {}", log_path.display(), e))?;
+    writeln!(log, "# {}", render_cmd(cmd)).map_err(|e| format!("write {}: {}", log_path.display(), e))?;
+
+    let log_err = log.try_clone().map_err(|e| format!("failed to open log {}: {}", log_path.display(), e))?;
+    cmd.stdout(log).stderr(log_err);
+    Ok(())
+}
+
+fn is_protein(alignment_type: &str) -> bool {
+    matches!(alignment_type, "pep" | "protein")
+}
+
+fn run_db_builder(mut cmd: Command, what: &str, species: &Species, logger: &Logger) -> Result<(), String> {
+    logger.information(&format!("build_db: Running: {}", render_cmd(&cmd)));
+
+    let status = cmd
+        .status()
+        .map_err(|e| format!("failed to run {} for {}: {}", what, species.fasta.display(), e))?;
+
+    if !status.success() {
+        return Err(format!("{} failed for {}", what, species.fasta.display()));
+    }
+    Ok(())
+}
+
+// DIAMOND
+
+pub struct Diamond;
+
+impl Aligner for Diamond {
+    fn name(&self) -> &'static str { "diamond" }
+
+    fn display_name(&self) -> &'static str { "DIAMOND" }
+
+    fn supported_alphabets(&self) -> &'static [&'static str] { &["pep"] }
+
+    fn executables(&self, _alignment_type: &str) -> (&'static str, &'static str) {
+        // diamond handles both db building and searching
+        ("diamond", "diamond")
+    }
+
+    fn build_db(&self, tools: &AlignerTools, species: &Species, _alignment_type: &str, logger: &Logger) -> Result<(), String> {
+        let mut cmd = Command::new(&tools.db_builder);
+        cmd.arg("makedb")
+            .arg("--in").arg(&species.fasta)
+            .arg("--db").arg(&species.db_prefix)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        run_db_builder(cmd, "diamond makedb", species, logger)?;
+        logger.information(&format!("Created DIAMOND DB {}", species.db_prefix.display()));
+        Ok(())
+    }
+
+    fn search_command(&self, tools: &AlignerTools, job: &SearchJob, args: &Args, threads: usize) -> Result<Command, String> {
+        // diamond blastp --db sdb -q qfasta -o out -p threads -k max_target_seqs -e evalue -f 6
+        let program = if is_protein(&args.alignment_type) { "blastp" } else { "blastn" };
+        let dmnd = job.db_prefix.with_extension("dmnd");
+
+        // Sanity check so failures are obvious
+        if !dmnd.exists() {
+            return Err(format!("missing DIAMOND DB {} (expected for {})", dmnd.display(), job.s_name));
+        }
+
+        let mut cmd = Command::new(&tools.searcher);
+        cmd.arg(program)
+            .arg("-d").arg(&job.db_prefix) // diamond takes prefix without .dmnd
+            .arg("-q").arg(&job.q_fasta)
+            .arg("-o").arg(&job.out_path)
+            .arg("-p").arg(threads.to_string())
+            .arg("-k").arg(job.max_target_seqs.to_string())
+            .arg("-e").arg(&args.evalue)
+            .arg("-f").arg("6").args(OUTPUT_COLUMNS) // tabular, standard 12 columns + qlen slen
+            .arg("--masking").arg("0");
+
+        // sensitivity: "", "fast", "sensitive", "more-sensitive", "very-sensitive", "ultra-sensitive"
+        let sensitivity = args.diamond_sensitivity.as_str();
+        if !sensitivity.is_empty() {
+            let opt = if sensitivity.starts_with("--") {
+                sensitivity.to_string()
+            } else {
+                format!("--{}", sensitivity)
+            };
+            cmd.arg(opt);
+        }
+
+        Ok(cmd)
+    }
+
+    fn version(&self, searcher: &Path) -> String {
+        let raw = get_version(searcher, &["--version"]).unwrap_or_else(|| "Unknown".into());
+        clean_diamond_version(&raw)
+    }
+
+    fn citation(&self) -> Option<CitationInfo> {
+        Some(CitationInfo {
+            tool: "DIAMOND".into(),
+            citation: "Buchfink B et al., Nat Methods (2015)".into(),
+            link: "https://pubmed.ncbi.nlm.nih.gov/25402007/".into(),
+        })
+    }
+
+    fn supports_combined_db(&self) -> bool {
+        true
+    }
+
+    fn parameters(&self, args: &Args) -> Vec<(String, String)> {
+        vec![("diamond_sensitivity".into(), args.diamond_sensitivity.clone())]
+    }
+}
+
+// MMseqs2
+
+pub struct MmSeqs;
+
+impl MmSeqs {
+    fn db_path(prefix: &Path) -> PathBuf {
+        prefix.with_extension("mmseqs")
+    }
+}
+
+impl Aligner for MmSeqs {
+    fn name(&self) -> &'static str { "mmseqs" }
+
+    fn display_name(&self) -> &'static str { "MMseqs2" }
+
+    fn supported_alphabets(&self) -> &'static [&'static str] { &["pep", "cds"] }
+
+    fn executables(&self, _alignment_type: &str) -> (&'static str, &'static str) {
+        // mmseqs handles db building, searching and conversion
+        ("mmseqs", "mmseqs")
+    }
+
+    fn build_db(&self, tools: &AlignerTools, species: &Species, alignment_type: &str, logger: &Logger) -> Result<(), String> {
+        // mmseqs createdb FASTA PREFIX.mmseqs --dbtype 1|2
+        let dbtype = if is_protein(alignment_type) { "1" } else { "2" };
+        let db = Self::db_path(&species.db_prefix);
+
+        // Only build if the database does not exist
+        if db.with_extension("mmseqs.dbtype").exists() {
+            return Ok(());
+        }
+
+        let mut cmd = Command::new(&tools.db_builder);
+        cmd.arg("createdb")
+            .arg(&species.fasta)
+            .arg(&db)
+            .args(["--dbtype", dbtype])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        run_db_builder(cmd, "mmseqs createdb", species, logger)?;
+        logger.information(&format!("Created MMseqs2 DB {}", db.display()));
+        Ok(())
+    }
+
+    fn search_command(&self, tools: &AlignerTools, job: &SearchJob, args: &Args, threads: usize) -> Result<Command, String> {
+        // mmseqs search qdb sdb resultdb tmpdir -s sensitivity -e evalue --max-seqs max_target_seqs --threads threads
+        let q_db = Self::db_path(&job.q_db_prefix);
+        let s_db = Self::db_path(&job.db_prefix);
+
+        for db in [&q_db, &s_db] {
+            if !db.with_extension("mmseqs.dbtype").exists() {
+                return Err(format!("missing MMseqs2 DB {} (expected for {} vs {})", db.display(), job.q_name, job.s_name));
+            }
+        }
+
+        let mut cmd = Command::new(&tools.searcher);
+        cmd.arg("search")
+            .arg(&q_db)
+            .arg(&s_db)
+            .arg(job.out_path.with_extension("mmseqs_result"))
+            .arg(job.out_path.with_extension("mmseqs_tmp"))
+            .arg("-s").arg(args.mmseqs_sensitivity.to_string())
+            .arg("-e").arg(&args.evalue)
+            .arg("--max-seqs").arg(job.max_target_seqs.to_string())
+            .arg("--threads").arg(threads.to_string());
+
+        // nucleotide vs nucleotide
+        if !is_protein(&args.alignment_type) {
+            cmd.args(["--search-type", "3"]);
+        }
+
+        Ok(cmd)
+    }
+
+    fn search_pair(&self, tools: &AlignerTools, job: &SearchJob, args: &Args, threads: usize, logger: &Logger) -> Result<(), String> {
+        let result_db = job.out_path.with_extension("mmseqs_result");
+        let tmp_dir = job.out_path.with_extension("mmseqs_tmp");
+
+        // Search into a result database
+        let mut cmd = self.search_command(tools, job, args, threads)?;
+        attach_log(&mut cmd, job)?;
+        logger.information(&format!("search_pair: Running: {}", render_cmd(&cmd)));
+
+        let status = cmd
+            .status()
+            .map_err(|e| format!("failed to run mmseqs search for {} vs {}: {}", job.q_name, job.s_name, e))?;
+        if !status.success() {
+            return Err(format!("mmseqs search failed for {} vs {} ({})", job.q_name, job.s_name, status));
+        }
+
+        // Convert the result database to the standard 12 BLAST columns + qlen slen
+        let mut convert = Command::new(&tools.searcher);
+        convert.arg("convertalis")
+            .arg(Self::db_path(&job.q_db_prefix))
+            .arg(Self::db_path(&job.db_prefix))
+            .arg(&result_db)
+            .arg(&job.out_path)
+            .args(["--format-output", "query,target,pident,alnlen,mismatch,gapopen,qstart,qend,tstart,tend,evalue,bits,qlen,tlen"])
+            .arg("--threads").arg(threads.to_string());
+        attach_log(&mut convert, job)?;
+
+        logger.information(&format!("search_pair: Running: {}", render_cmd(&convert)));
+
+        let status = convert
+            .status()
+            .map_err(|e| format!("failed to run mmseqs convertalis for {} vs {}: {}", job.q_name, job.s_name, e))?;
+        if !status.success() {
+            return Err(format!("mmseqs convertalis failed for {} vs {} ({})", job.q_name, job.s_name, status));
+        }
+
+        // Remove the intermediate result database (result.mmseqs_result, .index, .dbtype, ...) and tmp dir
+        remove_with_prefix(&result_db);
+        let _ = fs::remove_dir_all(&tmp_dir);
+
+        self.normalise_output(job)
+    }
+
+    fn version(&self, searcher: &Path) -> String {
+        get_version(searcher, &["version"])
+            .map(|v| v.trim().to_string())
+            .unwrap_or_else(|| "Unknown".into())
+    }
+
+    fn citation(&self) -> Option<CitationInfo> {
+        Some(CitationInfo {
+            tool: "MMseqs2".into(),
+            citation: "Steinegger M & Söding J, Nat Biotechnol (2017)".into(),
+            link: "https://pubmed.ncbi.nlm.nih.gov/29035372/".into(),
+        })
+    }
+
+    fn parameters(&self, args: &Args) -> Vec<(String, String)> {
+        vec![("mmseqs_sensitivity".into(), args.mmseqs_sensitivity.to_string())]
+    }
+}
+
+/// Remove a file and any sibling files that start with its name (MMseqs2 databases are split over several files)
+fn remove_with_prefix(path: &Path) {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|n| n.to_str())) else { return };
+
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let matches = entry.file_name().to_str().map(|f| f.starts_with(name)).unwrap_or(false);
+            if matches {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+// BLAST+
+
+pub struct BlastPlus;
+
+impl Aligner for BlastPlus {
+    fn name(&self) -> &'static str { "blastplus" }
+
+    fn display_name(&self) -> &'static str { "BLAST+" }
+
+    fn supported_alphabets(&self) -> &'static [&'static str] { &["pep", "cds"] }
+
+    fn executables(&self, alignment_type: &str) -> (&'static str, &'static str) {
+        let search = if is_protein(alignment_type) { "blastp" } else { "blastn" };
+        ("makeblastdb", search)
+    }
+
+    fn build_db(&self, tools: &AlignerTools, species: &Species, alignment_type: &str, logger: &Logger) -> Result<(), String> {
+        // makeblastdb -in FASTA -dbtype prot|nucl -out PREFIX
+        let (dbtype, index_ext) = if is_protein(alignment_type) { ("prot", "pin") } else { ("nucl", "nin") };
+
+        // Only build if the index file does not exist
+        if species.db_prefix.with_extension(index_ext).exists() {
+            return Ok(());
+        }
+
+        let mut cmd = Command::new(&tools.db_builder);
+        cmd.args(["-in"]).arg(&species.fasta)
+            .args(["-dbtype", dbtype])
+            .args(["-out"]).arg(&species.db_prefix);
+
+        run_db_builder(cmd, "makeblastdb", species, logger)
+    }
+
+    fn search_command(&self, tools: &AlignerTools, job: &SearchJob, args: &Args, threads: usize) -> Result<Command, String> {
+        // searcher is blastp or blastn
+        let mut cmd = Command::new(&tools.searcher);
+        cmd.arg("-query").arg(&job.q_fasta)
+            .arg("-db").arg(&job.db_prefix)
+            .arg("-num_threads").arg(threads.to_string())
+            .arg("-evalue").arg(&args.evalue)
+            .arg("-max_target_seqs").arg(job.max_target_seqs.to_string())
+            .arg("-outfmt").arg("6 std qlen slen")
+            .arg("-out").arg(&job.out_path);
+
+        Ok(cmd)
+    }
+
+    fn version(&self, searcher: &Path) -> String {
+        let raw = get_version(searcher, &["-version"]).unwrap_or_else(|| "Unknown".into());
+        clean_blast_version(&raw)
+    }
+
+    fn citation(&self) -> Option<CitationInfo> {
+        Some(CitationInfo {
+            tool: "BLAST+".into(),
+            citation: "Camacho C et al., BMC Bioinformatics (2009)".into(),
+            link: "https://pubmed.ncbi.nlm.nih.gov/20003500/".into(),
+        })
+    }
+}
+
+// Legacy BLAST (formatdb + blastall)
+
+pub struct BlastLegacy;
+
+impl Aligner for BlastLegacy {
+    fn name(&self) -> &'static str { "blastlegacy" }
+
+    fn display_name(&self) -> &'static str { "BLAST" }
+
+    fn supported_alphabets(&self) -> &'static [&'static str] { &["pep", "cds"] }
+
+    fn executables(&self, _alignment_type: &str) -> (&'static str, &'static str) {
+        ("formatdb", "blastall")
+    }
+
+    fn build_db(&self, tools: &AlignerTools, species: &Species, alignment_type: &str, logger: &Logger) -> Result<(), String> {
+        // formatdb -i fasta -p T|F -n prefix
+        let (pflag, index_ext) = if is_protein(alignment_type) { ("T", "pin") } else { ("F", "nin") };
+
+        // Only build if the index file does not exist
+        if species.db_prefix.with_extension(index_ext).exists() {
+            return Ok(());
+        }
+
+        let mut cmd = Command::new(&tools.db_builder);
+        cmd.args(["-i"]).arg(&species.fasta)
+            .args(["-p", pflag])
+            .args(["-n"]).arg(&species.db_prefix);
+
+        run_db_builder(cmd, "formatdb", species, logger)
+    }
+
+    fn search_command(&self, tools: &AlignerTools, job: &SearchJob, args: &Args, threads: usize) -> Result<Command, String> {
+        // blastall -p blastp|blastn -d sdb -i qfasta -o out -a threads -e evalue -m 8
+        let program = if is_protein(&args.alignment_type) { "blastp" } else { "blastn" };
+
+        let mut cmd = Command::new(&tools.searcher);
+        cmd.args(["-p", program])
+            .args(["-d"]).arg(&job.db_prefix)
+            .args(["-i"]).arg(&job.q_fasta)
+            .args(["-o"]).arg(&job.out_path)
+            .args(["-a", &threads.to_string()])
+            .args(["-e", &args.evalue])
+            .args(["-b", &job.max_target_seqs.to_string()])
+            .args(["-m", "8"]); // tabular
+
+        Ok(cmd)
+    }
+
+    // blastall cannot report sequence lengths, so they are added from the FASTA files
+    fn normalise_output(&self, job: &SearchJob) -> Result<(), String> {
+        let q_lengths = read_fasta::fasta_id_to_seq_length_hash(&job.q_fasta).map_err(|e| format!("read {}: {}", job.q_fasta.display(), e))?;
+        let s_lengths = read_fasta::fasta_id_to_seq_length_hash(&job.s_fasta).map_err(|e| format!("read {}: {}", job.s_fasta.display(), e))?;
+        normalise_tabular(&job.out_path, Some(&q_lengths), Some(&s_lengths))
+    }
+
+    fn version(&self, _searcher: &Path) -> String {
+        "Legacy BLAST (no version reporting)".into()
+    }
+
+    fn citation(&self) -> Option<CitationInfo> {
+        Some(CitationInfo {
+            tool: "BLAST".into(),
+            citation: "Altschul SF et al., Nucleic Acids Res (1997)".into(),
+            link: "https://pubmed.ncbi.nlm.nih.gov/9254694/".into(),
+        })
+    }
+}
+
+/// Rewrite search output as `OUTPUT_COLUMNS` tab-separated hit lines, dropping comment lines.
+/// 12-column (m8) lines get qlen/slen appended from the query and subject lengths when given.
+/// The file is only rewritten if it is not already in that layout.
+pub fn normalise_tabular(path: &Path, q_lengths: Option<&HashMap<String, u64>>, s_lengths: Option<&HashMap<String, u64>>) -> Result<(), String> {
+    let n_cols = OUTPUT_COLUMNS.len();
+
+    let needs_rewrite = {
+        let reader = BufReader::new(File::open(path).map_err(|e| format!("open {}: {}", path.display(), e))?);
+        let mut needs = false;
+        for line in reader.lines() {
+            let line = line.map_err(|e| format!("read {}: {}", path.display(), e))?;
+            if line.starts_with('#') || line.split('\t').count() != n_cols {
+                needs = true;
+                break;
+            }
+        }
+        needs
+    };
+
+    if !needs_rewrite {
+        return Ok(());
+    }
+
+    let tmp_path = path.with_extension("out.normalising");
+    {
+        let reader = BufReader::new(File::open(path).map_err(|e| format!("open {}: {}", path.display(), e))?);
+        let mut writer = BufWriter::new(File::create(&tmp_path).map_err(|e| format!("create {}: {}", tmp_path.display(), e))?);
+
+        for line in reader.lines() {
+            let line = line.map_err(|e| format!("read {}: {}", path.display(), e))?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            let out = if fields.len() >= n_cols {
+                fields[..n_cols].join("\t")
+            } else if let (12, Some(q_lengths), Some(s_lengths)) = (fields.len(), q_lengths, s_lengths) {
+                let qlen = q_lengths.get(fields[0]).ok_or_else(|| format!("{}: no length for query {}", path.display(), fields[0]))?;
+                let slen = s_lengths.get(fields[1]).ok_or_else(|| format!("{}: no length for subject {}", path.display(), fields[1]))?;
+                format!("{}\t{}\t{}", fields.join("\t"), qlen, slen)
+            } else {
+                return Err(format!("{}: hit line has {} columns, expected {}: {}", path.display(), fields.len(), n_cols, line));
+            };
+
+            writeln!(writer, "{}", out).map_err(|e| format!("write {}: {}", tmp_path.display(), e))?;
+        }
+        writer.flush().map_err(|e| format!("flush {}: {}", tmp_path.display(), e))?;
+    }
+
+    fs::rename(&tmp_path, path).map_err(|e| format!("rename {} -> {}: {}", tmp_path.display(), path.display(), e))
+}
+
+fn shell_escape(arg: &OsStr) -> String {
+    let s = arg.to_string_lossy();
+    if s.chars().all(|c| c.is_ascii_alphanumeric() || "_-./:".contains(c)) {
+        s.into_owned()
+    } else {
+        let esc = s.replace('\'', "'\\''");
+        format!("'{}'", esc)
+    }
+}
+
+pub fn render_cmd(cmd: &Command) -> String {
+    let mut s = String::new();
+    s.push_str(&shell_escape(cmd.get_program()));
+    for a in cmd.get_args() {
+        s.push(' ');
+        s.push_str(&shell_escape(a));
+    }
+    s
+}
+
+fn clean_blast_version(raw: &str) -> String {
+    let mut s = raw.trim().to_string();
+
+    // strip leading "blastp:", "blastn:", etc
+    if let Some(idx) = s.find(':') {
+        let after = s[(idx + 1)..].trim().to_string();
+        if after.chars().next().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+            s = after;
+        }
+    }
+
+    // remove everything after "Package:"
+    if let Some(idx) = s.to_lowercase().find("package") {
+        s = s[..idx].trim().to_string();
+    }
+
+    s
+}
+
+fn clean_diamond_version(raw: &str) -> String {
+    let s = raw.trim();
+
+    // Try: "diamond version 2.1.6"
+    if let Some(rest) = s.strip_prefix("diamond version ") {
+        return rest.trim().to_string();
+    }
+
+    // Try: "diamond v2.1.6"
+    if let Some(rest) = s.strip_prefix("diamond ") {
+        return rest.trim().to_string();
+    }
+
+    // Try: split on whitespace and look for something that starts with digits
+    for token in s.split_whitespace() {
+        if token.chars().next().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+            return token.to_string();
+        }
+    }
+
+    // Fallback: return cleaned string
+    s.to_string()
+}
@@ -1,6 +1,8 @@
 use crate::Logger;
+use crate::aligner;
+use crate::msa;
 
-use clap::{Parser, ValueEnum};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
 //use num_cpus;
 
 // setting up the command line parameters
@@ -8,6 +10,7 @@ use clap::{Parser, ValueEnum};
 #[command(name = "Synima")]
 #[command(version = "2.0")]
 #[command(about = "Synima (Synteny Imager) is an orthology prediction pipeline and synteny viewer.", long_about = None)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 
 pub struct Args {
 
@@ -30,13 +33,24 @@ pub struct Args {
 
             Where:
             <name> = Genome identifier (e.g., CNB2)
-            <type> = One of: dir, genome, gff, cds (optional), pep (optional)
+            <type> = One of: dir, genome, gff (or genetable/bed), cds (optional), pep (optional), group (optional)
             <location> = Either a full path, or a filename relative to a preceding 'dir' entry for the same genome
 
+            'genome' may be omitted when a pep/cds FASTA matching --alignment_type is given
+            (proteome-only mode). Contig lengths are then taken from the GFF.
+
+            Instead of a gff, a gene table can be given with type 'genetable'
+            (columns: gene_id contig start end strand, 1-based) or 'bed' (BED6).
+            Gene tables require the pep/cds FASTA matching --alignment_type.
+
+            Genomes can be assigned to groups for the clade-specific orthogroup report
+            with type 'group', giving comma separated labels instead of a file.
+
             E.g.,:
             CNB2    dir     /data/genomes/CNB2
             CNB2    genome  genome.fa
             CNB2    gff     annotation.gff
+            CNB2    group   VGI,clinical
 
             Cryp_gatt_IND107_V2    genome  /data/genomes/IND107/genome.fa
             Cryp_gatt_IND107_V2    gff     /data/genomes/IND107/annotation.gff
@@ -80,7 +94,7 @@ pub struct Args {
     pub match_threshold: u8,
 
     /// Aligner to use for Step 2 all-vs-all
-    /// Options: auto, diamond, blastplus, legacy
+    /// Options: diamond, mmseqs, blastplus, blastlegacy
     #[arg(long = "aligner", default_value = "diamond")]
     pub aligner: String,
 
@@ -92,10 +106,146 @@ pub struct Args {
     #[arg(long = "diamond_sensitivity", default_value = "fast")]
     pub diamond_sensitivity: String,
 
+    /// Search one database of all genomes instead of one per genome (DIAMOND only).
+    /// off: N x N pairwise searches; per-query: one search per genome; single: one search of all genes
+    #[arg(long = "combined_db", default_value = "off", value_parser = ["off", "per-query", "single"])]
+    pub combined_db: String,
+
+    /// Compress search output files ({q}_vs_{s}.out.gz or .out.zst); compressed files are read transparently by all steps
+    #[arg(long = "compress_search", default_value = "none", value_parser = ["none", "gzip", "zstd"])]
+    pub compress_search: String,
+
+    /// Precomputed all-vs-all results for the import-search step: a directory of tabular
+    /// BLAST (m8) files or a single concatenated file
+    #[arg(long = "import_search")]
+    pub import_search: Option<String>,
+
+    /// Number of times a failed search is retried
+    #[arg(long = "search_retries", default_value_t = 1)]
+    pub search_retries: usize,
+
+    /// Continue the pipeline when searches still fail after retries (failed pairs are listed in failed_searches.tsv)
+    #[arg(long = "allow_failed_searches")]
+    pub allow_failed_searches: bool,
+
+    /// MMseqs2 sensitivity (-s, 1.0 fastest to 7.5 most sensitive)
+    #[arg(long = "mmseqs_sensitivity", default_value_t = 5.7)]
+    pub mmseqs_sensitivity: f32,
+
     /// BLAST e-value cutoff (default: 1e-10)
     #[arg(short = 'e', long, default_value = "1e-10")]
     pub evalue: String,
 
+    /// Minimum percent identity for a hit to be used for orthology (0 = off)
+    #[arg(long = "min_pident", default_value_t = 0.0)]
+    pub min_pident: f64,
+
+    /// Minimum percent of the query covered by a hit (0 = off)
+    #[arg(long = "min_qcov", default_value_t = 0.0)]
+    pub min_qcov: f64,
+
+    /// Minimum percent of the subject covered by a hit (0 = off)
+    #[arg(long = "min_scov", default_value_t = 0.0)]
+    pub min_scov: f64,
+
+    /// Minimum ratio of hit bitscore to the query's self-hit bitscore, 0-1 (0 = off)
+    #[arg(long = "min_bitscore_ratio", default_value_t = 0.0)]
+    pub min_bitscore_ratio: f64,
+
+    /// RBH: hits within this fraction of the best bitscore (0-1) also count as best hits
+    #[arg(long = "rbh_tie_tolerance", default_value_t = 0.0)]
+    pub rbh_tie_tolerance: f64,
+
+    /// RBH: minimum percent of both query and subject covered by a best hit (0 = off)
+    #[arg(long = "rbh_min_coverage", default_value_t = 0.0)]
+    pub rbh_min_coverage: f64,
+
+    /// RBH: minimum bitscore for a reciprocal best hit to link two genes in a cluster (0 = off)
+    #[arg(long = "rbh_min_link_bitscore", default_value_t = 0.0)]
+    pub rbh_min_link_bitscore: f64,
+
+    /// RBH: minimum Jaccard similarity (0-1) of two linked genes' neighbours, limits chaining between clusters (0 = off)
+    #[arg(long = "rbh_min_jaccard", default_value_t = 0.0)]
+    pub rbh_min_jaccard: f64,
+
+    /// OrthoMCL: maximum e-value for hits used to pair genes
+    #[arg(long = "omcl_evalue_cutoff", default_value_t = 1e-5)]
+    pub omcl_evalue_cutoff: f64,
+
+    /// MCL inflation for the orthomcl and mcl steps (higher values give smaller, tighter clusters)
+    #[arg(long = "inflation", default_value_t = 1.5)]
+    pub inflation: f64,
+
+    /// Graph clustered by the mcl step: every hit weighted by bitscore, or reciprocal best hits only
+    #[arg(long = "mcl_graph", default_value = "bitscore", value_parser = ["bitscore", "rbh"])]
+    pub mcl_graph: String,
+
+    /// Synteny: maximum number of genes between consecutive anchors of a syntenic block
+    #[arg(long = "synteny_max_gap", default_value_t = 25)]
+    pub synteny_max_gap: usize,
+
+    /// Synteny: minimum number of collinear anchors for a syntenic block
+    #[arg(long = "synteny_min_anchors", default_value_t = 5)]
+    pub synteny_min_anchors: usize,
+
+    /// Synteny: best hits per gene in each other genome used as candidate anchors
+    #[arg(long = "synteny_top_hits", default_value_t = 5)]
+    pub synteny_top_hits: usize,
+
+    /// Run the full OrthoFinder pipeline (gene trees, rooted species tree, hierarchical orthogroups and
+    /// gene duplications) instead of stopping after orthogroups. The species tree replaces the FastTree supermatrix tree
+    #[arg(long = "orthofinder_full")]
+    pub orthofinder_full: bool,
+
+    /// Orthogroups from another tool for the import-orthogroups step (OrthoFinder Orthogroups.tsv,
+    /// Proteinortho .proteinortho.tsv, Broccoli, SonicParanoid or orthogroup<TAB>gene lines)
+    #[arg(long = "import_orthogroups")]
+    pub import_orthogroups: Option<String>,
+
+    /// Format of the --import_orthogroups file (auto: detect from the header line)
+    #[arg(long = "orthogroups_format", default_value = "auto", value_parser = ["auto", "orthofinder", "proteinortho", "broccoli", "sonicparanoid", "two-column"])]
+    pub orthogroups_format: String,
+
+    /// Minimum percentage of genomes an orthogroup must be in to be core (ortholog-summary and tree)
+    #[arg(long = "core_threshold", default_value_t = 100.0)]
+    pub core_threshold: f64,
+
+    /// Minimum percentage of genomes for soft-core (below --core_threshold). Default: --core_threshold (no soft-core)
+    #[arg(long = "soft_core_threshold")]
+    pub soft_core_threshold: Option<f64>,
+
+    /// Orthogroups in fewer than this percentage of genomes (but more than one) are cloud, not shell. Default: no cloud
+    #[arg(long = "cloud_threshold", default_value_t = 0.0)]
+    pub cloud_threshold: f64,
+
+    /// Genome group defined as the smallest clade of the tree containing the given genomes,
+    /// as NAME=GENOME1,GENOME2[,...] (repeatable; used with groups from the repo spec)
+    #[arg(long = "clade")]
+    pub clade: Vec<String>,
+
+    /// Percentage of a group's genomes an orthogroup may be missing from, and of the other
+    /// genomes it may be present in, while still counting as specific to the group
+    #[arg(long = "group_tolerance", default_value_t = 0.0)]
+    pub group_tolerance: f64,
+
+    /// Number of random genome orders for the pan/core-genome accumulation curves (ortholog-summary)
+    #[arg(long = "pangenome_iterations", default_value_t = 100)]
+    pub pangenome_iterations: usize,
+
+    /// Random seed for the pan/core-genome accumulation curves
+    #[arg(long = "pangenome_seed", default_value_t = 42)]
+    pub pangenome_seed: u64,
+
+    /// Multiple sequence aligner for the single-copy orthogroups of the tree step (famsa aligns proteins only)
+    #[arg(long = "msa", default_value = "muscle", value_parser = ["muscle", "mafft", "famsa"])]
+    pub msa: String,
+
+    /// With --alignment_type cds, align the translated single-copy orthologs (--msa) and thread
+    /// the CDS through the protein alignments (codon alignments) for the tree, instead of aligning
+    /// nucleotides directly
+    #[arg(long = "codon_alignment")]
+    pub codon_alignment: bool,
+
     /// Number of DAGchainer chains
     #[arg(long = "dagchainer_chains", default_value_t = 4)]
     pub dagchainer_chains: usize,
@@ -120,6 +270,58 @@ pub struct Args {
     #[arg(short='o', long="output_dir", default_value="synima_output")]
     pub output_dir: String,
 
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+}
+
+/// Subcommands run on the results of an earlier run instead of the pipeline
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Look up a gene, orthogroup or genomic region in an output directory
+    Query(QueryArgs),
+}
+
+/// Options of 'Synima query'. Exactly one of --gene, --orthogroup or --region is required.
+/// E.g.,:
+///   Synima query -o synima_output --gene CNB2|CNB02340
+///   Synima query -o synima_output --region CNB2:chr1:10000-50000 --format json
+#[derive(clap::Args, Debug)]
+#[command(group(ArgGroup::new("target").required(true).args(["gene", "orthogroup", "region"])))]
+pub struct QueryArgs {
+
+    /// Output directory of the run to query
+    #[arg(short = 'o', long = "output_dir", default_value = "synima_output")]
+    pub output_dir: String,
+
+    /// Gene ID (optionally as genome|gene): its orthogroup, all members with coordinates, and the synteny blocks containing it
+    #[arg(long)]
+    pub gene: Option<String>,
+
+    /// Orthogroup (cluster) ID: its members per genome
+    #[arg(long)]
+    pub orthogroup: Option<String>,
+
+    /// Region genome:contig:start-end: the genes in it and their syntenic counterparts
+    #[arg(long)]
+    pub region: Option<String>,
+
+    /// Alignment type of the ortholog summary to query
+    #[arg(short = 'a', long = "alignment_type", default_value = "pep", value_parser = ["pep", "cds"])]
+    pub alignment_type: String,
+
+    /// Orthology method of the ortholog summary to query (e.g. orthomcl, rbh, orthofinder).
+    /// Defaults to the most recently written summary.
+    #[arg(long)]
+    pub method: Option<String>,
+
+    /// Output format
+    #[arg(long, default_value = "tsv", value_parser = ["tsv", "json"])]
+    pub format: String,
+
+    /// Write results to this file instead of standard output
+    #[arg(long)]
+    pub out: Option<String>,
 }
 
 /// Steps of the Synima pipeline, in execution order.
@@ -142,6 +344,9 @@ pub enum SynimaStep {
     #[value(name = "align-all", alias = "blast-grid", help = "Run all-vs-all BLAST on parsed sequences and write tabular results")]
     BlastGrid,
 
+    #[value(name = "import-search", help = "Import precomputed all-vs-all search results (tabular BLAST) in place of align-all")]
+    ImportSearch,
+
     #[value(name = "orthofinder", alias = "blast-to-orthofinder", help = "Rewrite FASTA and BLAST to OrthoFinder format, run OrthoFinder")]
     BlastToOrthofinder,
 
@@ -152,6 +357,15 @@ pub enum SynimaStep {
     #[value(name = "rbh", alias = "blast-to-rbh", help = "Format BLAST output to use with a Reciprocal Best Hit (RBH) pipeline")]
     BlastToRbh,
 
+    #[value(name = "mcl", alias = "blast-to-mcl", help = "Cluster a graph of the BLAST output with Markov clustering (MCL)")]
+    BlastToMcl,
+
+    #[value(name = "synteny", alias = "blast-to-synteny", help = "Split homology clusters into positional orthologs using collinearity of the BLAST hits")]
+    BlastToSynteny,
+
+    #[value(name = "import-orthogroups", help = "Import orthogroups from another tool (OrthoFinder, Proteinortho, Broccoli, SonicParanoid, two-column) in place of an orthology step")]
+    ImportOrthogroups,
+
     #[value(name = "ortholog-summary", help = "Collect Orthogroups.tsv and produce orthology summaries")]
     OrthologSummary,
 
@@ -176,7 +390,7 @@ pub fn validate_step_sequence(steps: &[SynimaStep], logger: &Logger) {
     }
 
     // 1. Orthology-step mutual exclusivity (for now)
-    let orthology_steps = [BlastToOrthomcl, BlastToRbh, BlastToOrthofinder];
+    let orthology_steps = [BlastToOrthomcl, BlastToRbh, BlastToMcl, BlastToSynteny, BlastToOrthofinder, ImportOrthogroups];
 
     let selected_orthology_steps: Vec<_> = steps
         .iter()
@@ -185,20 +399,30 @@ pub fn validate_step_sequence(steps: &[SynimaStep], logger: &Logger) {
 
     if selected_orthology_steps.len() > 1 {
         logger.error(
-            "Only one of blast-to-orthomcl, blast-to-rbh, or blast-to-orthofinder \
+            "Only one of blast-to-orthomcl, blast-to-rbh, blast-to-mcl, blast-to-synteny, blast-to-orthofinder, or import-orthogroups \
              may be used at a time in a single run.",
         );
         std::process::exit(1);
     }
 
+    // align-all and import-search both write the all-vs-all results
+    if steps.contains(&BlastGrid) && steps.contains(&ImportSearch) {
+        logger.error("align-all and import-search cannot be used together: import-search replaces the align-all results.");
+        std::process::exit(1);
+    }
+
     // 2. Canonical pipeline order, including Tree
     let pipeline_order = [
         DownloadFromNcbi,
         CreateRepoDb,
         BlastGrid,
+        ImportSearch,
         BlastToOrthomcl,
         BlastToRbh,
+        BlastToMcl,
+        BlastToSynteny,
         BlastToOrthofinder,
+        ImportOrthogroups,
         OrthologSummary,
         Tree,
         Dagchainer,
@@ -222,8 +446,8 @@ pub fn validate_step_sequence(steps: &[SynimaStep], logger: &Logger) {
         if idx < last_idx {
             logger.error(&format!(
                 "Step {:?} appears out of order in --synima_step. \
-                 The allowed order is: create-repo-db -> blast-grid -> \
-                 blast-to-(orthofinder|orthomcl|rbh) -> ortholog-summary -> \
+                 The allowed order is: create-repo-db -> blast-grid|import-search -> \
+                 blast-to-(orthofinder|orthomcl|rbh|mcl|synteny)|import-orthogroups -> ortholog-summary -> \
                  tree -> dagchainer -> synima.",
                 step
             ));
@@ -247,16 +471,71 @@ pub fn validate_step_sequence(steps: &[SynimaStep], logger: &Logger) {
 /// Validate logical compatibility between aligner and alignment_type.
 /// Abort with clear error messages when combinations are unsupported.
 pub fn validate_alignment_compatibility(args: &Args, logger: &Logger) {
-    let aligner = args.aligner.as_str();
+    let aligner = aligner::get_aligner_or_exit(&args.aligner, logger);
     let alignment_type = args.alignment_type.as_str();
 
-    match (aligner, alignment_type) {
-        ("diamond", "cds") => {
-            logger.error(
-                "Invalid configuration: DIAMOND does not support CDS searches. \
-                 Use --alignment-type pep or select --aligner blastplus/blastlegacy when using CDS.",);
-            std::process::exit(1);
-        }
-        _ => {}
+    if !aligner.supported_alphabets().contains(&alignment_type) {
+        let alternatives: Vec<&str> = aligner::ALIGNER_NAMES
+            .iter()
+            .copied()
+            .filter(|name| aligner::get_aligner(name).map(|a| a.supported_alphabets().contains(&alignment_type)).unwrap_or(false))
+            .collect();
+
+        logger.error(&format!(
+            "Invalid configuration: {} does not support {} searches. \
+             Use --alignment_type {} or select --aligner {} when using {}.",
+            aligner.display_name(), alignment_type.to_uppercase(),
+            aligner.supported_alphabets().join("/"), alternatives.join("/"), alignment_type.to_uppercase()));
+        std::process::exit(1);
+    }
+
+    if args.codon_alignment && alignment_type != "cds" {
+        logger.error("Invalid configuration: --codon_alignment threads CDS through protein alignments and requires --alignment_type cds.");
+        std::process::exit(1);
     }
-}
\ No newline at end of file
+
+    // The multiple aligner sees proteins for codon alignments
+    let msa = msa::get_msa_or_exit(&args.msa, logger);
+    let msa_alphabet = if args.codon_alignment { "pep" } else { alignment_type };
+    if !msa.supported_alphabets().contains(&msa_alphabet) {
+        logger.error(&format!("Invalid configuration: {} does not align {} sequences. Use --codon_alignment or select another --msa when using CDS.",
+            msa.display_name(), msa_alphabet.to_uppercase()));
+        std::process::exit(1);
+    }
+}
+
+/// Validate the core/soft-core/cloud percentages (0 <= cloud <= soft-core <= core <= 100, core > 0)
+/// and the group tolerance (0 <= tolerance < 100)
+pub fn validate_class_thresholds(args: &Args, logger: &Logger) {
+    let (core, cloud) = (args.core_threshold, args.cloud_threshold);
+    let soft_core = args.soft_core_threshold.unwrap_or(core);
+
+    if !(core > 0.0 && core <= 100.0) {
+        logger.error(&format!("Invalid configuration: --core_threshold must be above 0 and at most 100 (got {}).", core));
+        std::process::exit(1);
+    }
+    if !(0.0..=core).contains(&soft_core) {
+        logger.error(&format!("Invalid configuration: --soft_core_threshold ({}) must be between 0 and --core_threshold ({}).", soft_core, core));
+        std::process::exit(1);
+    }
+    if !(0.0..=soft_core).contains(&cloud) {
+        logger.error(&format!("Invalid configuration: --cloud_threshold ({}) must be between 0 and the soft-core threshold ({}).", cloud, soft_core));
+        std::process::exit(1);
+    }
+    if !(0.0..100.0).contains(&args.group_tolerance) {
+        logger.error(&format!("Invalid configuration: --group_tolerance must be at least 0 and below 100 (got {}).", args.group_tolerance));
+        std::process::exit(1);
+    }
+}
+
+pub fn validate_orthology_parameters(args: &Args, logger: &Logger) {
+    if !(0.0..1.0).contains(&args.rbh_tie_tolerance) {
+        logger.error(&format!("Invalid configuration: --rbh_tie_tolerance must be at least 0 and below 1 (got {}).", args.rbh_tie_tolerance));
+        std::process::exit(1);
+    }
+    // used by the orthomcl, mcl and synteny steps
+    if args.inflation.is_nan() || args.inflation <= 1.0 {
+        logger.error(&format!("Invalid configuration: --inflation must be greater than 1 (got {}).", args.inflation));
+        std::process::exit(1);
+    }
+}
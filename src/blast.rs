@@ -1,240 +1,54 @@
 use crate::logger::Logger;
 use crate::Args;
 use crate::RepoEntry;
-use crate::external_tools;
-use crate::util::{mkdir, open_bufread, open_bufwrite}; //,open_file_read,open_file_write
+use crate::aligner::{Aligner, AlignerTools, SearchJob, Species};
+use crate::scheduler;
+use crate::search_io::{self, QueryPairWriters, SearchCompression};
+use crate::util::{mkdir, open_bufwrite}; //,open_file_read,open_file_write
 
-use std::process;
-use std::process::Command;
 use std::path::Path;
 //use std::collections::{HashSet}; //HashMap, 
 use std::path::PathBuf;
-use std::fs::{self};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Mutex;
+use std::fs;
 use std::io::{BufRead, Write};
-use std::ffi::OsStr;
-use std::process::Stdio;
-use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
-
-struct Species {
-    name: String,
-    fasta: PathBuf,
-    db_prefix: PathBuf, // base path (no ext)
-}
-
-struct BlastJob {
-    q_name: String,
-    s_name: String,
-    q_fasta: PathBuf,
-    db_prefix: PathBuf,
-    out_path: PathBuf,
-}
 
-#[derive(Debug, Clone)]
-pub struct AlignerTools {
-    pub db_builder: PathBuf,
-    pub searcher: PathBuf,
-}
-
-fn dmnd_path(prefix: &Path) -> PathBuf {
-    prefix.with_extension("dmnd")
-}
-
-pub fn resolve_aligner_tools(
-    aligner: &str,
-    alignment_type: &str,
-    bin_dir: &Path,
-    logger: &Logger,
-) -> AlignerTools {
-    let (db_prog, search_prog) = match aligner {
-        "blastplus" => {
-            let search = match alignment_type {
-                "pep" => "blastp",
-                "cds" => "blastn",
-                other => {
-                    logger.error(&format!("resolve_aligner_tools: unsupported alignment_type '{}', expected 'pep' or 'cds'", other));
-                    std::process::exit(1);
-                }
-            };
-            ("makeblastdb", search)
-        }
-
-        "blastlegacy" => {
-            // formatdb + blastall
-            ("formatdb", "blastall")
-        }
-
-        "diamond" => {
-            // diamond handles both db building and searching
-            ("diamond", "diamond")
-        }
-
-        other => {
-            logger.error(&format!("resolve_aligner_tools: unsupported aligner '{}', expected 'blastplus', 'blastlegacy', or 'diamond'", other));
-            std::process::exit(1);
-        }
-    };
-
-    let db_builder_path = external_tools::find_executable(db_prog, bin_dir, logger);
-    let searcher_path = external_tools::find_executable(search_prog, bin_dir, logger);
+fn collect_species(repo: &[RepoEntry], alignment_type: &str, db_dir: &Path) -> Vec<Species> {
+    let mut species: Vec<Species> = Vec::new();
 
-    AlignerTools {
-        db_builder: db_builder_path,
-        searcher: searcher_path,
-    }
-}
+    for entry in repo {
+        let Some(fasta) = find_fasta(entry, alignment_type) else { continue };
 
-fn shell_escape(arg: &OsStr) -> String {
-    let s = arg.to_string_lossy();
-    if s.chars().all(|c| c.is_ascii_alphanumeric() || "_-./:".contains(c)) {
-        s.into_owned()
-    } else {
-        let esc = s.replace('\'', "'\\''");
-        format!("'{}'", esc)
-    }
-}
+        // Clean the species name so DIAMOND accepts it
+        let clean_prefix = normalise_prefix(&entry.name);
 
-fn render_cmd(cmd: &Command) -> String {
-    let mut s = String::new();
-    s.push_str(&shell_escape(cmd.get_program()));
-    for a in cmd.get_args() {
-        s.push(' ');
-        s.push_str(&shell_escape(a));
+        species.push(Species {
+            name: entry.name.clone(),        // "CNB2"
+            fasta,
+            db_prefix: db_dir.join(&clean_prefix), // ".../databases/CNB2"
+        });
     }
-    s
+    species
 }
 
 pub fn create_all_dbs(
     repo: &[RepoEntry],
     alignment_type: &str,
-    db_builder: PathBuf,
+    aligner: &dyn Aligner,
+    tools: &AlignerTools,
     out_dir: &Path,
     logger: &Logger) {
 
-    // Decide which kind of builder this is from its filename
-    let builder_name = db_builder.file_name().and_then(|s| s.to_str()).unwrap_or("");
-
-    enum DbBuilderKind {
-        Diamond,
-        BlastPlus,
-        Legacy,
-    }
-
-    let builder_kind = match builder_name {
-        "diamond" => DbBuilderKind::Diamond,
-        "makeblastdb" => DbBuilderKind::BlastPlus,
-        "formatdb" => DbBuilderKind::Legacy,
-        other => {
-            logger.error(&format!("create_all_dbs: unsupported db builder executable '{}'", other));
-            std::process::exit(1);
-        }
-    };
-
     let db_dir = out_dir.join("databases");
     mkdir(&db_dir, &logger, "create_all_dbs");
 
-    let mut species: Vec<Species> = Vec::new();
-
-    for entry in repo {
-        let Some(fasta) = find_fasta(entry, alignment_type) else { continue };
-
-        //let db_prefix = db_dir.join(&entry.name);
-
-        // Clean the species name so DIAMOND accepts it
-        let clean_prefix = normalise_prefix(&entry.name);
-
-        species.push(Species {
-            name: entry.name.clone(),        // "CNB2"
-            fasta: PathBuf::from(&fasta),
-            db_prefix: db_dir.join(&clean_prefix), // db_prefix.clone(),    // ".../databases/CNB2.synima-parsed"
-        });
-    }
+    let species = collect_species(repo, alignment_type, &db_dir);
 
     for s in &species {
-        match builder_kind {
-            DbBuilderKind::Diamond => {
-                let mut cmd = Command::new(&db_builder);
-
-                cmd.arg("makedb")
-                    .arg("--in").arg(&s.fasta)
-                    .arg("--db").arg(&s.db_prefix)
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::null());
-
-                logger.information(&format!("Running: {}", render_cmd(&cmd)));
-
-                let status = match cmd.status() {
-                    Ok(st) => st,
-                    Err(e) => {
-                        logger.error(&format!("create_all_dbs: failed to run diamond makedb for {}: {}", s.name, e));
-                        std::process::exit(1);
-                    }
-                };
-
-                if !status.success() {
-                    logger.error(&format!("create_all_dbs: diamond makedb failed for {}", s.name));
-                    std::process::exit(1);
-                }
-
-                logger.information(&format!("Created DIAMOND DB {}", s.db_prefix.display()));
-            }
-
-            DbBuilderKind::BlastPlus => {
-                // makeblastdb -in FASTA -dbtype prot|nucl -out PREFIX
-                let dbtype = if matches!(alignment_type, "pep" | "protein") { "prot" } else { "nucl" };
-                //let prefix = db_dir.join(&s.name); // no extension
-                let prefix = &s.db_prefix;
-
-                // Only build if pin file does not exist
-                if !prefix.with_extension("pin").exists() {
-                    let status = Command::new(&db_builder)
-                        .args(["-in"]).arg(&s.fasta)
-                        .args(["-dbtype", dbtype])
-                        .args(["-out"]).arg(&prefix) // db_prefix
-                        .status();
-
-                    let status = match status {
-                        Ok(st) => st,
-                        Err(e) => {
-                            logger.error(&format!("create_all_dbs: failed to run makeblastdb for {}: {}", s.fasta.display(), e));
-                            std::process::exit(1);
-                        }
-                    };
-
-                    if !status.success() {
-                        logger.error(&format!("create_all_dbs: makeblastdb failed for {}", s.fasta.display()));
-                        std::process::exit(1);
-                    }
-                }
-            }
-
-            DbBuilderKind::Legacy => {
-                // formatdb -i fasta -p T|F
-                let pflag = if matches!(alignment_type, "pep" | "protein") { "T" } else { "F" };
-                //let prefix = db_dir.join(&s.name); // no extension
-                let prefix = &s.db_prefix;
-
-                // Only build if pin file does not exist
-                if !prefix.with_extension("pin").exists() {
-                    let status = Command::new(&db_builder)
-                        .args(["-i"]).arg(&s.fasta)
-                        .args(["-p", pflag])
-                        .status();
-                    
-                    let status = match status {
-                        Ok(st) => st,
-                        Err(e) => {
-                            logger.error(&format!("create_all_dbs: failed to run formatdb for {}: {}", s.fasta.display(), e));
-                            std::process::exit(1);
-                        }
-                    };
-
-                    if !status.success() {
-                        logger.error(&format!("create_all_dbs: formatdb failed for {}", s.fasta.display()));
-                        std::process::exit(1);
-                    }
-                }
-            }
+        if let Err(e) = aligner.build_db(tools, s, alignment_type, logger) {
+            logger.error(&format!("create_all_dbs: {}", e));
+            std::process::exit(1);
         }
         logger.information(&format!("create_all_dbs: {}", s.fasta.display()));
     }
@@ -262,39 +76,20 @@ fn normalise_prefix(name: &str) -> String {
 
 pub fn run_all_vs_all(
     repo: &[RepoEntry],
-    searcher: &Path,     // e.g. diamond, blastp, blastn, or blastall
-    args: &Args,         // has aligner, alignment_type, evalue, threads, max_target_seqs, diamond_sensitivity
+    aligner: &dyn Aligner,
+    tools: &AlignerTools,
+    args: &Args,         // has alignment_type, evalue, threads, max_target_seqs and aligner specific options
     out_dir: &Path,
     logger: &Logger) {
 
-    // Unpack what we need from args
-    let aligner = args.aligner.as_str();                 // "diamond" | "blastplus" | "blastlegacy"
     let alignment_type = args.alignment_type.as_str();   // "pep" | "cds" | "protein" | "nucl"
-    let evalue = args.evalue.as_str();
-    let max_target_seqs = args.max_target_seqs;
-    let diamond_sensitivity = args.diamond_sensitivity.as_str();    
-
-    let total_threads = args.threads.max(1);
 
     // Make sure output dir exists
     mkdir(&out_dir, logger, "run_all_vs_all");
 
-    // This must match create_all_dbs: databases are under out_dir/databases with prefix = FASTA stem
+    // This must match create_all_dbs: databases are under out_dir/databases
     let db_dir = out_dir.join("databases");
-    let mut species = Vec::new();
-
-    for entry in repo {
-        if let Some(fasta) = find_fasta(entry, alignment_type) {
-            // Clean the species name so DIAMOND accepts it
-            let clean_prefix = normalise_prefix(&entry.name);
-
-            species.push(Species {
-                name: entry.name.clone(),            // used in output filenames
-                fasta: PathBuf::from(&fasta),
-                db_prefix: db_dir.join(&clean_prefix), //db_dir.join(&entry.name)
-            });
-        }
-    }
+    let species = collect_species(repo, alignment_type, &db_dir);
 
     if species.is_empty() {
         logger.error("run_all_vs_all: no species sequences found for the requested alignment_type");
@@ -302,239 +97,363 @@ pub fn run_all_vs_all(
     }
 
     // Build list of jobs: all pairwise (including self)
-    let mut jobs: Vec<BlastJob> = Vec::new();
+    let compression = SearchCompression::from_arg(&args.compress_search);
+    let mut jobs: Vec<SearchJob> = Vec::new();
 
     for q in &species {
         for s in &species {
-            let out_path = out_dir.join(format!("{}_vs_{}.out", q.name, s.name));
+            let out_path = search_io::pair_file_path(out_dir, &q.name, &s.name, compression);
 
-            jobs.push(BlastJob {
+            jobs.push(SearchJob {
                 q_name: q.name.clone(),
                 s_name: s.name.clone(),
                 q_fasta: q.fasta.clone(),
+                q_db_prefix: q.db_prefix.clone(),
+                s_fasta: s.fasta.clone(),
                 db_prefix: s.db_prefix.clone(),
+                log_path: Some(out_dir.join("logs").join(format!("{}_vs_{}.log", q.name, s.name))),
                 out_path,
+                max_target_seqs: args.max_target_seqs,
             });
         }
     }
 
-    logger.information(&format!("run_all_vs_all: {} pairwise searches to run with aligner '{}'", jobs.len(), aligner));
-
-    // Copy searcher path into an owned PathBuf so it can be shared in the closure
-    let searcher_path = searcher.to_path_buf();
-
-    // Build a local rayon pool constrained to total_threads
-    let pool = ThreadPoolBuilder::new()
-        .num_threads(total_threads)
-        .build()
-        .expect("run_all_vs_all: failed to build rayon thread pool");
-
-    // Run jobs in parallel
-    // Install the parallel computation in that pool
-    pool.install(|| {
-        jobs.par_iter().for_each(|job| {
-            // Decide how many threads the external tool gets.
-            // Safe choice: 1 per job, rayon handles concurrency.
-            let job_threads = 1;
-
-            match aligner {
-                "diamond" => {
-                    // diamond blastp --db sdb -q qfasta -o out -p threads -k max_target_seqs -e evalue -f 6
-                    let program = if matches!(alignment_type, "pep" | "protein") { "blastp" } else { "blastn" };
-                    let dmnd = dmnd_path(&job.db_prefix);
-
-                    // Optional sanity check so failures are obvious
-                    if !dmnd.exists() {
-                        logger.error(&format!("run_all_vs_all: missing DIAMOND DB {} (expected for {})", dmnd.display(), job.s_name));
-                        std::process::exit(1);
-                    }
-
-                    let mut cmd = Command::new(&searcher_path);
-
-                    cmd.arg(program)
-                        .arg("-d").arg(&job.db_prefix)   // diamond takes prefix without .dmnd
-                        .arg("-q").arg(&job.q_fasta)
-                        .arg("-o").arg(&job.out_path)
-                        .arg("-p").arg(job_threads.to_string()) // threads
-                        .arg("-k").arg(max_target_seqs.to_string())
-                        .arg("-e").arg(format!("{}", evalue))
-                        .arg("-f").arg("6") // standard 12 columns
-                        .arg("--masking").arg("0")
-                        .stdout(Stdio::null())
-                        .stderr(Stdio::null());
-
-                    // sensitivity: "", "fast", "sensitive", "more-sensitive", "very-sensitive", "ultra-sensitive"
-                    if !diamond_sensitivity.is_empty() {
-                        let opt = if diamond_sensitivity.starts_with("--") {
-                            diamond_sensitivity.to_string()
-                        } else {
-                            format!("--{}", diamond_sensitivity)
-                        };
-                        cmd.arg(opt);
-                    }
-
-                    logger.information(&format!("run_all_vs_all: Running: {}", render_cmd(&cmd)));
-
-                    let status = match cmd.status() {
-                        Ok(st) => st,
-                        Err(e) => {
-                            logger.error(&format!("run_all_vs_all: failed to run diamond for {} vs {}: {}", job.q_name, job.s_name, e));
-                            std::process::exit(1);
-                        }
-                    };
-
-                    if !status.success() {
-                        logger.error(&format!("run_all_vs_all: diamond search failed for {} vs {}", job.q_name, job.s_name));
-                        std::process::exit(1);
-                    }
-                }
+    logger.information(&format!("run_all_vs_all: {} pairwise searches to run with aligner '{}'", jobs.len(), aligner.name()));
 
-                "blastplus" => {
-                    // searcher is blastp or blastn
-                    let mut cmd = std::process::Command::new(&searcher_path);
-                    cmd.arg("-query").arg(&job.q_fasta)
-                        .arg("-db").arg(&job.db_prefix)
-                        .arg("-num_threads").arg(job_threads.to_string())
-                        .arg("-evalue").arg(format!("{}", evalue))
-                        .arg("-max_target_seqs").arg(max_target_seqs.to_string())
-                        .arg("-outfmt").arg("6")
-                        .arg("-out").arg(&job.out_path);
-
-                    logger.information(&format!("run_all_vs_all: Running {}", render_cmd(&cmd)));
-
-                    let status = match cmd.status() {
-                        Ok(st) => st,
-                        Err(e) => {
-                            logger.error(&format!("run_all_vs_all: failed to run BLAST+ for {} vs {}: {}", job.q_name, job.s_name, e));
-                            std::process::exit(1);
-                        }
-                    };
-
-                    if !status.success() {
-                        logger.error(&format!("run_all_vs_all: BLAST+ search failed for {} vs {}", job.q_name, job.s_name));
-                        std::process::exit(1);
-                    }
-                }
+    let failed = run_search_jobs(&jobs, aligner, tools, args, out_dir, logger);
+    report_failed_searches(&failed, args, out_dir, logger);
+}
 
-                "blastlegacy" => {
-                    // blastall -p blastp|blastn -d sdb -i qfasta -o out -a threads -e evalue -m 8
-                    let program = if matches!(alignment_type, "pep" | "protein") { "blastp" } else { "blastn" };
-
-                    let mut cmd = Command::new(&searcher_path);
-                    cmd.args(["-p", program])
-                        .args(["-d"]).arg(&job.db_prefix)
-                        .args(["-i"]).arg(&job.q_fasta)
-                        .args(["-o"]).arg(&job.out_path)
-                        .args(["-a", &job_threads.to_string()])
-                        .args(["-e", &format!("{}", evalue)])
-                        .args(["-m", "8"]); // tabular
-
-                    logger.information(&format!("run_all_vs_all: Running {}", render_cmd(&cmd)));    
-
-                    let status = match cmd.status() {
-                        Ok(st) => st,
-                        Err(e) => {
-                            logger.error(&format!("run_all_vs_all: failed to run legacy BLAST for {} vs {}: {}", job.q_name, job.s_name, e));
-                            std::process::exit(1);
-                        }
-                    };
-
-                    if !status.success() {
-                        logger.error(&format!("run_all_vs_all: legacy BLAST search failed for {} vs {}", job.q_name, job.s_name));
-                        std::process::exit(1);
-                    }
-                }
+/// A search that still failed after all retries
+pub struct FailedSearch {
+    pub q_name: String,
+    pub s_name: String,
+    pub attempts: usize,
+    pub error: String,
+}
+
+/// Run search jobs (largest first, threads split between jobs) with retries.
+///
+/// Each job writes to `{q}_vs_{s}.out.tmp`, which is renamed (or compressed, --compress_search) to
+/// its final name only on success, so a `.out` file is never partially written. Tool output goes to the job's log in `out_dir/logs`.
+/// Returns the searches that failed every attempt.
+fn run_search_jobs(
+    jobs: &[SearchJob],
+    aligner: &dyn Aligner,
+    tools: &AlignerTools,
+    args: &Args,
+    out_dir: &Path,
+    logger: &Logger) -> Vec<FailedSearch> {
+
+    mkdir(&out_dir.join("logs"), logger, "run_search_jobs");
+
+    // Split threads between concurrent searches and aligner threads, largest searches first
+    let costs: Vec<u64> = jobs.iter().map(|job| scheduler::search_cost(&job.q_fasta, &job.s_fasta)).collect();
+    let plan = scheduler::plan_threads(args.threads, jobs.len(), args.threads.max(1));
+    scheduler::log_plan("run_search_jobs", jobs.len(), &plan, logger);
+
+    let attempts = args.search_retries + 1;
+    let failed: Mutex<Vec<FailedSearch>> = Mutex::new(Vec::new());
+
+    scheduler::run_largest_first(jobs, &costs, plan.concurrent_jobs, |job| {
+        let job_dir = job.out_path.parent().unwrap_or(out_dir);
+        let mut tmp_job = job.clone();
+        tmp_job.out_path = job_dir.join(format!("{}_vs_{}.out.tmp", job.q_name, job.s_name));
+
+        // start each job with a fresh log, and no output from an earlier run in another compression
+        if let Some(log_path) = &job.log_path {
+            let _ = fs::remove_file(log_path);
+        }
+        search_io::remove_pair_files(job_dir, &job.q_name, &job.s_name);
+
+        let mut last_error = String::new();
+        for attempt in 1..=attempts {
+            let _ = fs::remove_file(&tmp_job.out_path);
+
+            let result = aligner
+                .search_pair(tools, &tmp_job, args, plan.threads_per_job, logger)
+                .and_then(|_| search_io::store_search_output(&tmp_job.out_path, &job.out_path));
 
-                other => {
-                    logger.error(&format!("run_all_vs_all: unsupported aligner '{}', expected 'diamond', 'blastplus', or 'blastlegacy'", other));
-                    std::process::exit(1);
+            match result {
+                Ok(()) => {
+                    logger.information(&format!("run_search_jobs: wrote {}", job.out_path.display()));
+                    return;
+                }
+                Err(e) => {
+                    let log_note = job.log_path.as_ref().map(|p| format!(" (see {})", p.display())).unwrap_or_default();
+                    logger.warning(&format!("run_search_jobs: attempt {}/{} failed for {} vs {}: {}{}", attempt, attempts, job.q_name, job.s_name, e, log_note));
+                    last_error = e;
                 }
             }
-            logger.information(&format!("run_all_vs_all: wrote {}", job.out_path.display()));
+        }
+
+        // never leave partial output behind
+        let _ = fs::remove_file(&tmp_job.out_path);
+        let _ = fs::remove_file(&job.out_path);
+
+        failed.lock().unwrap_or_else(|e| e.into_inner()).push(FailedSearch {
+            q_name: job.q_name.clone(),
+            s_name: job.s_name.clone(),
+            attempts,
+            error: last_error,
         });
     });
+
+    let mut failed = failed.into_inner().unwrap_or_else(|e| e.into_inner());
+    failed.sort_by(|a, b| (&a.q_name, &a.s_name).cmp(&(&b.q_name, &b.s_name)));
+    failed
 }
 
-pub fn concatenate_unique_blast_pairs(blast_out_dir: &Path, output_file: &Path, logger: &Logger) {
-    //let mut seen_pairs = HashSet::new();
+/// Write failed_searches.tsv and stop unless --allow_failed_searches was given
+fn report_failed_searches(failed: &[FailedSearch], args: &Args, out_dir: &Path, logger: &Logger) {
+    let failed_path = out_dir.join("failed_searches.tsv");
 
-    // Create output file
-    let mut writer = open_bufwrite(&output_file, &logger, "concatenate_unique_blast_pairs");
+    if failed.is_empty() {
+        // remove a summary left by an earlier run
+        let _ = fs::remove_file(&failed_path);
+        logger.information("report_failed_searches: all searches completed");
+        return;
+    }
 
-    // Read directory
-    let read_dir = match fs::read_dir(blast_out_dir) {
-        Ok(rd) => rd,
-        Err(e) => {
-            logger.error(&format!("concatenate_unique_blast_pairs: Failed to read directory {}: {}",blast_out_dir.display(), e));
-            process::exit(1);
-        }
+    let mut writer = open_bufwrite(&failed_path, logger, "report_failed_searches");
+    let mut lines = vec!["query\tsubject\tattempts\terror".to_string()];
+    lines.extend(failed.iter().map(|f| format!("{}\t{}\t{}\t{}", f.q_name, f.s_name, f.attempts, f.error.replace(['\t', '\n'], " "))));
+    if let Err(e) = writeln!(writer, "{}", lines.join("\n")).and_then(|_| writer.flush()) {
+        logger.error(&format!("report_failed_searches: failed to write {}: {}", failed_path.display(), e));
+        std::process::exit(1);
+    }
+
+    let pairs: Vec<String> = failed.iter().map(|f| format!("{} vs {}", f.q_name, f.s_name)).collect();
+    let msg = format!("report_failed_searches: {} search(es) failed: {}. Details in {}", failed.len(), pairs.join(", "), failed_path.display());
+
+    if args.allow_failed_searches {
+        logger.warning(&format!("{} (continuing without them, --allow_failed_searches)", msg));
+    } else {
+        logger.error(&format!("{}. Re-run align-all, or use --allow_failed_searches to continue without them", msg));
+        std::process::exit(1);
+    }
+}
+
+/// Search against one database built from the combined synima_all proteome (--combined_db),
+/// then split the hits back into {q}_vs_{s}.out files.
+///
+/// "per-query" runs one search per genome, "single" runs one search of all genes. Searches use
+/// `-k max_target_seqs x genomes` and the split keeps at most `max_target_seqs` subjects per query
+/// for each subject genome, matching the per-pair searches.
+pub fn run_combined_db_search(
+    repo: &[RepoEntry],
+    aligner: &dyn Aligner,
+    tools: &AlignerTools,
+    args: &Args,
+    out_dir: &Path,
+    logger: &Logger) {
+
+    let alignment_type = args.alignment_type.as_str();
+
+    if !aligner.supports_combined_db() {
+        logger.error(&format!("run_combined_db_search: --combined_db is not supported by aligner '{}'", aligner.name()));
+        std::process::exit(1);
+    }
+
+    mkdir(out_dir, logger, "run_combined_db_search");
+    let db_dir = out_dir.join("databases");
+    mkdir(&db_dir, logger, "run_combined_db_search");
+    let combined_dir = out_dir.join("combined");
+    mkdir(&combined_dir, logger, "run_combined_db_search");
+
+    let species = collect_species(repo, alignment_type, &db_dir);
+    if species.is_empty() {
+        logger.error("run_combined_db_search: no species sequences found for the requested alignment_type");
+        std::process::exit(1);
+    }
+
+    // Combined proteome written by create-repo (synima_all)
+    let all_key = format!("{}_all", alignment_type);
+    let Some(all_fasta) = repo.iter()
+        .find(|e| e.name == "synima_all")
+        .and_then(|e| e.files.get(&all_key))
+        .map(|f| PathBuf::from(&f.path)) else {
+        logger.error(&format!("run_combined_db_search: no combined {} FASTA found (run create-repo first)", all_key));
+        std::process::exit(1);
     };
 
-    for entry in read_dir {
-        let entry = match entry {
-            Ok(e) => e,
-            Err(e) => {
-                logger.warning(&format!("concatenate_unique_blast_pairs: Skipping unreadable dir entry: {}", e));
-                continue;
-            }
-        };
+    let combined = Species {
+        name: "synima_all".to_string(),
+        fasta: all_fasta,
+        db_prefix: db_dir.join("synima_all"),
+    };
+    if let Err(e) = aligner.build_db(tools, &combined, alignment_type, logger) {
+        logger.error(&format!("run_combined_db_search: {}", e));
+        std::process::exit(1);
+    }
 
-        let path = entry.path();
-        if !path.is_file() {
-            continue;
-        }
+    // One search per query genome, or one overall
+    let compression = SearchCompression::from_arg(&args.compress_search);
+    let max_target_seqs = args.max_target_seqs.saturating_mul(species.len());
+    let queries: Vec<(String, PathBuf)> = match args.combined_db.as_str() {
+        "single" => vec![("all".to_string(), combined.fasta.clone())],
+        _ => species.iter().map(|q| (q.name.clone(), q.fasta.clone())).collect(),
+    };
+
+    let jobs: Vec<SearchJob> = queries
+        .into_iter()
+        .map(|(q_name, q_fasta)| SearchJob {
+            out_path: search_io::pair_file_path(&combined_dir, &q_name, &combined.name, compression),
+            log_path: Some(out_dir.join("logs").join(format!("{}_vs_synima_all.log", q_name))),
+            q_name,
+            s_name: combined.name.clone(),
+            q_fasta,
+            q_db_prefix: combined.db_prefix.clone(),
+            s_fasta: combined.fasta.clone(),
+            db_prefix: combined.db_prefix.clone(),
+            max_target_seqs,
+        })
+        .collect();
+
+    logger.information(&format!("run_combined_db_search: {} search(es) against the combined database with aligner '{}' (-k {})", jobs.len(), aligner.name(), max_target_seqs));
 
-        // Match files like A_vs_B.out
-        let file_name = match path.file_name().and_then(|f| f.to_str()) {
-            Some(name) => name,
-            None => continue,
-        };
+    let failed = run_search_jobs(&jobs, aligner, tools, args, out_dir, logger);
 
-        // Skip output file itself and irrelevant hidden/system files
-        if file_name == "all_vs_all.out" || file_name.starts_with('.') {
-            continue;
+    // Split back into {q}_vs_{s}.out (only for query genomes whose search succeeded)
+    let genome_names: Vec<String> = species.iter().map(|s| s.name.clone()).collect();
+    let succeeded: Vec<&SearchJob> = jobs.iter().filter(|job| !failed.iter().any(|f| f.q_name == job.q_name)).collect();
+    let query_genomes: Vec<String> = if args.combined_db == "single" {
+        if succeeded.is_empty() { Vec::new() } else { genome_names.clone() }
+    } else {
+        succeeded.iter().map(|job| job.q_name.clone()).collect()
+    };
+    let combined_outputs: Vec<PathBuf> = succeeded.iter().map(|job| job.out_path.clone()).collect();
+    split_combined_hits(&combined_outputs, &query_genomes, &genome_names, args.max_target_seqs, compression, out_dir, logger);
+
+    report_failed_searches(&failed, args, out_dir, logger);
+}
+
+/// Split combined-database hits (genome|gene IDs) into one {q}_vs_{s}.out per genome pair,
+/// keeping at most `max_target_seqs` subjects per query for each subject genome.
+/// Every pair for the searched query genomes gets a file, even when empty, as downstream steps expect the full grid.
+/// Only the outputs of the current query genome are open; hits come grouped by query genome (one
+/// search per genome, or the genome-ordered combined FASTA), and any genome that reappears later
+/// is split again in a pass of its own.
+fn split_combined_hits(combined_outputs: &[PathBuf], query_genomes: &[String], genomes: &[String], max_target_seqs: usize, compression: SearchCompression, out_dir: &Path, logger: &Logger) {
+
+    let split = CombinedSplit { genomes, max_target_seqs, compression, out_dir };
+    let mut progress = SplitProgress::default();
+    split_combined_pass(combined_outputs, None, &split, &mut progress, logger);
+
+    for q in std::mem::take(&mut progress.out_of_order) {
+        logger.information(&format!("split_combined_hits: hits of {} are not contiguous, splitting them again", q));
+        split_combined_pass(combined_outputs, Some(&q), &split, &mut progress, logger);
+    }
+
+    // Empty files for query genomes without any hits
+    for q in query_genomes {
+        if !progress.written.contains_key(q) {
+            split.open(q, logger).finish(logger, "split_combined_hits");
         }
+    }
 
-        let Some((q, r)) = file_name.strip_suffix(".out").and_then(|base| {
-            let parts: Vec<_> = base.split("_vs_").collect();
-            if parts.len() == 2 {
-                Some((parts[0], parts[1]))
-            } else {
-                None
-            }
-        }) else {
-            logger.warning(&format!("concatenate_unique_blast_pairs: Skipping unexpected file: {}", file_name));
-            continue;
-        };
+    let n_written: usize = progress.written.values().sum();
 
-        // Ensure we only process one of (A,B) or (B,A)
-        //let pair = if q <= r { (q.to_string(), r.to_string()) } else { (r.to_string(), q.to_string()) };
-        //if run_type == "orthomcl" && seen_pairs.contains(&pair) {
-        //    logger.information(&format!("concatenate_unique_blast_pairs: Skipping reciprical BLAST pair: {} vs {}", q, r));
-        //    continue;
-        //}
+    logger.information(&format!("split_combined_hits: wrote {} hits to {} genome pair files in {}", n_written, query_genomes.len() * genomes.len(), out_dir.display()));
+}
 
-        logger.information(&format!("concatenate_unique_blast_pairs: Including BLAST result: {} vs {}", q, r));
-        //seen_pairs.insert(pair);
+/// Genome pair files written by `split_combined_hits`
+struct CombinedSplit<'a> {
+    genomes: &'a [String],
+    max_target_seqs: usize,
+    compression: SearchCompression,
+    out_dir: &'a Path,
+}
 
-        // Open the BLAST file
-        let reader = open_bufread(&path, &logger, "concatenate_unique_blast_pairs");
+impl CombinedSplit<'_> {
+    fn open(&self, query: &str, logger: &Logger) -> QueryPairWriters {
+        QueryPairWriters::open(self.out_dir, query, self.genomes, self.compression, logger, "split_combined_hits")
+    }
+}
+
+/// Query genomes split so far, with their number of hits written
+#[derive(Default)]
+struct SplitProgress {
+    written: HashMap<String, usize>,
+    out_of_order: BTreeSet<String>,
+}
+
+/// One pass over the combined outputs, for every query genome or only `only_query`.
+/// Query genomes met again after they were finished are added to `progress.out_of_order`.
+fn split_combined_pass(combined_outputs: &[PathBuf], only_query: Option<&str>, split: &CombinedSplit, progress: &mut SplitProgress, logger: &Logger) {
+
+    let genome_of = |id: &str| id.split_once('|').map(|(g, _)| g.to_string());
+    let mut current: Option<QueryPairWriters> = only_query.map(|q| split.open(q, logger));
+    let mut n_written = 0usize;
+
+    for combined in combined_outputs {
+        let reader = search_io::open_search_reader(combined, logger, "split_combined_hits");
+
+        // hits are grouped by query, so subject counts are reset when the query changes
+        let mut current_query = String::new();
+        let mut subjects_per_genome: HashMap<String, HashSet<String>> = HashMap::new();
 
         for line in reader.lines() {
-            let line = match line {
-                Ok(l) => l,
-                Err(e) => {
-                    logger.warning(&format!("concatenate_unique_blast_pairs: Failed to read from {}: {}", path.display(), e));
-                    break;
-                }
+            let line = line.unwrap_or_else(|e| {
+                logger.error(&format!("split_combined_hits: failed to read {}: {}", combined.display(), e));
+                std::process::exit(1);
+            });
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split('\t');
+            let (Some(query), Some(subject)) = (fields.next(), fields.next()) else { continue };
+
+            let (Some(q_genome), Some(s_genome)) = (genome_of(query), genome_of(subject)) else {
+                logger.error(&format!("split_combined_hits: expected genome|gene IDs in {}: {}", combined.display(), line));
+                std::process::exit(1);
             };
 
-            if let Err(e) = writeln!(writer, "{}", line) {
-                logger.error(&format!("concatenate_unique_blast_pairs: Failed to write to {}: {}", output_file.display(), e));
-                process::exit(1);
+            if only_query.is_some_and(|q| q != q_genome) {
+                continue;
+            }
+
+            // Finish the previous query genome when the next one starts
+            if current.as_ref().map(|w| w.query != q_genome).unwrap_or(true) {
+                if let Some(writers) = current.take() {
+                    progress.written.insert(writers.query.clone(), n_written);
+                    writers.finish(logger, "split_combined_hits");
+                }
+                n_written = 0;
+                if progress.written.contains_key(&q_genome) {
+                    progress.out_of_order.insert(q_genome);
+                    continue;
+                }
+                current = Some(split.open(&q_genome, logger));
+            }
+
+            if query != current_query {
+                current_query = query.to_string();
+                subjects_per_genome.clear();
+            }
+
+            let seen = subjects_per_genome.entry(s_genome.clone()).or_default();
+            if !seen.contains(subject) {
+                if seen.len() >= split.max_target_seqs {
+                    continue;
+                }
+                seen.insert(subject.to_string());
+            }
+
+            let writers = current.as_mut().expect("query genome writers are open");
+            if !writers.write_line(&s_genome, &line, logger, "split_combined_hits") {
+                logger.warning(&format!("split_combined_hits: skipping hit for unknown genome pair {} vs {}", q_genome, s_genome));
+                continue;
             }
+            n_written += 1;
         }
     }
+
+    if let Some(writers) = current {
+        progress.written.insert(writers.query.clone(), n_written);
+        writers.finish(logger, "split_combined_hits");
+    }
 }
 
 pub fn ensure_blast_dir(out_dir: &Path) -> Result<PathBuf, String> {
@@ -0,0 +1,87 @@
+use crate::logger::Logger;
+use crate::Args;
+use crate::blast_rbh;
+use crate::hits::SearchHits;
+use crate::search_io;
+use crate::util::open_bufwrite;
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+/// Graph clustered by the mcl step (--mcl_graph)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MclGraph {
+    Bitscore, // every hit between two genes, weighted by bitscore
+    Rbh,      // strict reciprocal best hits between genomes, weighted by bitscore
+}
+
+impl MclGraph {
+    pub fn from_args(args: &Args) -> Self {
+        match args.mcl_graph.as_str() {
+            "rbh" => MclGraph::Rbh,
+            _ => MclGraph::Bitscore,
+        }
+    }
+}
+
+/// Bitscore graph of the all-vs-all: one edge per pair of genes (self hits excluded),
+/// keeping the best bitscore over both directions
+pub fn build_bitscore_graph(search_hits: &SearchHits, logger: &Logger) -> Vec<(String, String, f64)> {
+
+    let mut best: HashMap<(String, String), f64> = HashMap::new();
+    let mut skipped = 0usize;
+    search_io::for_each_hit_line(search_hits, logger, "build_bitscore_graph", |line| {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 12 {
+            skipped += 1;
+            return;
+        }
+        let (q, s) = (fields[0], fields[1]);
+        if q == s {
+            return;
+        }
+        let Ok(bitscore) = fields[11].trim().parse::<f64>() else {
+            skipped += 1;
+            return;
+        };
+        let key = if q < s { (q.to_string(), s.to_string()) } else { (s.to_string(), q.to_string()) };
+        let entry = best.entry(key).or_insert(0.0);
+        *entry = entry.max(bitscore);
+    });
+    if skipped > 0 {
+        logger.warning(&format!("build_bitscore_graph: skipped {} malformed hit lines", skipped));
+    }
+
+    let mut edges: Vec<(String, String, f64)> = best.into_iter().map(|((a, b), w)| (a, b, w)).collect();
+    edges.sort_by(|x, y| (&x.0, &x.1).cmp(&(&y.0, &y.1)));
+    logger.information(&format!("build_bitscore_graph: {} gene pairs", edges.len()));
+    edges
+}
+
+/// Build the graph selected by --mcl_graph (RBH pairs are also written to `rbh_pairs_path`)
+pub fn build_graph(graph: MclGraph, search_hits: &SearchHits, rbh_pairs_path: &Path, args: &Args, logger: &Logger) -> Result<Vec<(String, String, f64)>, String> {
+    match graph {
+        MclGraph::Bitscore => Ok(build_bitscore_graph(search_hits, logger)),
+        MclGraph::Rbh => {
+            let rbh_params = blast_rbh::RbhParams::from_args(args);
+            let (pairs, _) = blast_rbh::write_rbh_pairs(search_hits, rbh_pairs_path, &rbh_params, logger)?;
+            Ok(pairs)
+        }
+    }
+}
+
+/// Write the graph in mcl's label (ABC) format: gene_a, gene_b, weight
+pub fn write_abc_graph(path: &Path, edges: &[(String, String, f64)], logger: &Logger) {
+    let mut writer = open_bufwrite(path, logger, "write_abc_graph");
+    for (a, b, w) in edges {
+        if let Err(e) = writeln!(writer, "{}\t{}\t{}", a, b, w) {
+            logger.error(&format!("write_abc_graph: write error {}: {}", path.display(), e));
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = writer.flush() {
+        logger.error(&format!("write_abc_graph: write error {}: {}", path.display(), e));
+        std::process::exit(1);
+    }
+}
@@ -1,147 +1,173 @@
 use crate::logger::Logger;
+use crate::Args;
 use crate::RepoEntry;
+use crate::hits::{self, SearchHits};
 use crate::read_repo::GeneStruct;
-use crate::util::{open_bufread,open_bufwrite,open_file_read,open_file_write};
+use crate::search_io;
+use crate::util::open_bufwrite;
 
 use std::collections::HashMap;
 use std::io::{BufRead, Write};
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::process;
-use std::process::{Command, Stdio};
-
-pub fn write_blast_pairs<P: AsRef<Path>>(all_vs_all_path: P, logger: &Logger) -> Result<PathBuf, String> {
-
-    // input/output
-    let input_path = all_vs_all_path.as_ref();
-    let reader = open_bufread(&input_path, &logger, "write_blast_pairs");
-    let output_path = input_path.with_file_name(format!("{}{}", input_path.file_name().unwrap().to_string_lossy(), ".pairs"));
-    let mut writer = open_bufwrite(&output_path, &logger, "write_blast_pairs");
-
-    // copy over first 2 columns
-    for line in reader.lines() {
-        let line = line.map_err(|e| format!("Error reading line: {}", e))?;
-        let fields: Vec<&str> = line.split('\t').collect();
-        if fields.len() >= 2 {
-            writeln!(writer, "{}\t{}", fields[0], fields[1]).map_err(|e| format!("Error writing line: {}", e))?;
+use rayon::prelude::*;
+
+/// Settings for strict reciprocal best hits
+#[derive(Debug, Clone)]
+pub struct RbhParams {
+    pub tie_tolerance: f64, // hits within this fraction of the best bitscore also count as best
+    pub min_coverage: f64,  // minimum percent of both query and subject in the alignment
+}
+
+impl RbhParams {
+    pub fn from_args(args: &Args) -> Self {
+        RbhParams {
+            tie_tolerance: args.rbh_tie_tolerance,
+            min_coverage: args.rbh_min_coverage,
         }
     }
-
-    Ok(output_path)
 }
 
-pub fn run_slclust_on_pairs(slclust_path: &Path, pairs_file: &Path, logger: &Logger) -> Result<PathBuf, String> {
+/// A reciprocal best hit: (gene_a, gene_b, bitscore)
+pub type RbhPair = (String, String, f64);
 
-    logger.information(&format!("run_slclust_on_pairs: {}", pairs_file.display()));
+/// Best subjects (with their bitscore) for every query in one {q}_vs_{s} search output
+fn best_hits_per_query(path: &Path, search_hits: &SearchHits, params: &RbhParams, logger: &Logger) -> Result<HashMap<String, Vec<(String, f64)>>, String> {
 
-    // Open input and output files
-    let output_path = pairs_file.with_extension("pairs.slclust");
-    let input_file = open_file_read(&pairs_file, &logger, "run_slclust_on_pairs");
-    let output_file = open_file_write(&output_path, &logger, "run_slclust_on_pairs");
+    // best bitscore per query and subject over all HSPs passing the coverage threshold
+    let mut scores: HashMap<String, HashMap<String, f64>> = HashMap::new();
+    let reader = search_io::open_search_reader(path, logger, "best_hits_per_query");
 
-    // Pipe input and output to slclust
-    let mut child = Command::new(slclust_path)
-        .stdin(Stdio::from(input_file))
-        .stdout(Stdio::from(output_file))
-        .spawn()
-        .map_err(|e| format!("Failed to launch slclust: {}", e))?;
+    for (lnum, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        if line.trim().is_empty() || line.starts_with('#') || !search_hits.keeps(&line) {
+            continue;
+        }
+        let hit = hits::parse_hit_line(&line).map_err(|e| format!("{}:{}: {}", path.display(), lnum + 1, e))?;
 
-    let status = child.wait().map_err(|e| format!("Failed to wait for slclust: {}", e))?;
+        // self hits are not orthologs
+        if hit.query == hit.subject {
+            continue;
+        }
+        if params.min_coverage > 0.0 {
+            let qcov = hit.query_coverage().unwrap_or(100.0);
+            let scov = hit.subject_coverage().unwrap_or(100.0);
+            if qcov < params.min_coverage || scov < params.min_coverage {
+                continue;
+            }
+        }
 
-    if !status.success() {
-        return Err(format!("slclust returned non-zero exit status: {}", status));
+        let best = scores.entry(hit.query).or_default().entry(hit.subject).or_insert(0.0);
+        if hit.bitscore > *best {
+            *best = hit.bitscore;
+        }
     }
 
-    logger.information(&format!("run_slclust_on_pairs: finished successfully: {}", output_path.display()));
-    Ok(output_path)
-}
-
-pub fn parse_clusters(cluster_file: &Path, logger: &Logger) -> Result<HashMap<usize, Vec<String>>, String> {
-
-    // input
-    let reader = open_bufread(&cluster_file, &logger, "parse_clusters");
-
-    let mut clusters = HashMap::new();
-    for (index, line) in reader.lines().enumerate() {
-        let line = line.map_err(|e| format!("Error reading cluster file: {}", e))?;
-        let genes: Vec<String> = line.split_whitespace().map(|s| s.to_string()).collect();
-        clusters.insert(index + 1, genes); // cluster_id starts from 1
+    // keep the best subject(s) per query, including near ties
+    let mut best_hits = HashMap::new();
+    for (query, subjects) in scores {
+        let top = subjects.values().cloned().fold(0.0, f64::max);
+        let cutoff = top * (1.0 - params.tie_tolerance);
+        let best: Vec<(String, f64)> = subjects.into_iter().filter(|(_, score)| *score >= cutoff).collect();
+        best_hits.insert(query, best);
     }
-
-    Ok(clusters)
+    Ok(best_hits)
 }
 
-pub fn map_gene_to_cluster_id(cluster_map: &HashMap<usize, Vec<String>>) -> HashMap<String, usize> {
-    let mut gene_to_cluster = HashMap::new();
-
-    for (cluster_id, genes) in cluster_map {
-        for gene in genes {
-            gene_to_cluster.insert(gene.clone(), *cluster_id);
+/// Reciprocal best hits between two genomes
+fn reciprocal_best_hits(a_vs_b: &Path, b_vs_a: &Path, search_hits: &SearchHits, params: &RbhParams, logger: &Logger) -> Result<Vec<RbhPair>, String> {
+    let best_ab = best_hits_per_query(a_vs_b, search_hits, params, logger)?;
+    let best_ba = best_hits_per_query(b_vs_a, search_hits, params, logger)?;
+
+    let mut pairs = Vec::new();
+    for (gene_a, subjects) in &best_ab {
+        for (gene_b, score_ab) in subjects {
+            let Some(reverse) = best_ba.get(gene_b) else { continue };
+            if let Some((_, score_ba)) = reverse.iter().find(|(s, _)| s == gene_a) {
+                pairs.push((gene_a.clone(), gene_b.clone(), score_ab.max(*score_ba)));
+            }
         }
     }
-
-    gene_to_cluster
+    pairs.sort_by(|x, y| (&x.0, &x.1).cmp(&(&y.0, &y.1)));
+    Ok(pairs)
 }
 
-pub fn get_top_ortho_blast_score(
-    repo: &[RepoEntry],
-    blast_out_dir: &Path,
-    logger: &Logger
-) -> Result<HashMap<String, f64>, String> {
+/// Find strict reciprocal best hits for every pair of genomes with search output in
+/// `search_hits` and write them to `output_path` (gene_a, gene_b, bitscore).
+///
+/// Returns the pairs and the top RBH bitscore per gene, used to attach in-paralogs.
+pub fn write_rbh_pairs(search_hits: &SearchHits, output_path: &Path, params: &RbhParams, logger: &Logger) -> Result<(Vec<RbhPair>, HashMap<String, f64>), String> {
 
-    logger.information(&format!("get_top_ortho_blast_score: {}", blast_out_dir.display()));
+    logger.information(&format!("write_rbh_pairs: {} (tie tolerance {}, min coverage {})", search_hits.dir.display(), params.tie_tolerance, params.min_coverage));
 
-    let mut id_to_top_score: HashMap<String, f64> = HashMap::new();
-    let mut genomes: Vec<&String> = repo.iter().map(|r| &r.name).collect();
+    // genomes with search output
+    let mut genomes: Vec<String> = search_io::list_pair_files(&search_hits.dir, logger)
+        .into_iter()
+        .flat_map(|(q, s, _)| [q, s])
+        .collect();
     genomes.sort();
+    genomes.dedup();
 
-    for i in 0..genomes.len() {
-
-        let genome_a = genomes[i];
-        if genome_a == "synima_all" {
-            continue;
+    let mut genome_pairs: Vec<(&String, &String)> = Vec::new();
+    for (i, genome_a) in genomes.iter().enumerate() {
+        for genome_b in &genomes[i + 1..] {
+            genome_pairs.push((genome_a, genome_b));
         }
+    }
+
+    let results: Vec<Result<Vec<RbhPair>, String>> = genome_pairs
+        .par_iter()
+        .map(|(genome_a, genome_b)| {
+            let (Some(a_vs_b), Some(b_vs_a)) = (
+                search_io::find_pair_file(&search_hits.dir, genome_a, genome_b),
+                search_io::find_pair_file(&search_hits.dir, genome_b, genome_a),
+            ) else {
+                logger.warning(&format!("write_rbh_pairs: missing {} vs {} search output in one direction (failed search?), skipping", genome_a, genome_b));
+                return Ok(Vec::new());
+            };
+            reciprocal_best_hits(&a_vs_b, &b_vs_a, search_hits, params, logger)
+        })
+        .collect();
+
+    // output
+    let mut writer = open_bufwrite(output_path, logger, "write_rbh_pairs");
+    let mut id_to_top_score: HashMap<String, f64> = HashMap::new();
+    let mut all_pairs: Vec<RbhPair> = Vec::new();
 
-        for j in (i + 1)..genomes.len() {
+    for ((genome_a, genome_b), result) in genome_pairs.iter().zip(results) {
+        let pairs = result?;
+        logger.information(&format!("write_rbh_pairs: {} vs {}: {} reciprocal best hits", genome_a, genome_b, pairs.len()));
 
-            let genome_b = genomes[j];
-            if genome_b == "synima_all" {
-                continue;
+        for (gene_a, gene_b, score) in &pairs {
+            writeln!(writer, "{}\t{}\t{}", gene_a, gene_b, score).map_err(|e| format!("Error writing {}: {}", output_path.display(), e))?;
+            for gene in [gene_a, gene_b] {
+                let top = id_to_top_score.entry(gene.clone()).or_insert(*score);
+                *top = top.max(*score);
             }
+        }
+        all_pairs.extend(pairs);
+    }
+    writer.flush().map_err(|e| format!("Error writing {}: {}", output_path.display(), e))?;
 
-            // input
-            let file_name = format!("{}_vs_{}.out", genome_a, genome_b);
-            let rbh_file = blast_out_dir.join(file_name);
-            let reader = open_bufread(&rbh_file, &logger, "get_top_ortho_blast_score");
+    logger.information(&format!("write_rbh_pairs: {} reciprocal best hit pairs written to {}", all_pairs.len(), output_path.display()));
+    Ok((all_pairs, id_to_top_score))
+}
 
-            for line in reader.lines() {
-                let line = line.map_err(|e| format!("Error reading {}: {}", rbh_file.display(), e))?;
-                let fields: Vec<&str> = line.split('\t').collect();
-                if fields.len() < 12 {
-                    continue;
-                }
+pub fn map_gene_to_cluster_id(cluster_map: &HashMap<usize, Vec<String>>) -> HashMap<String, usize> {
+    let mut gene_to_cluster = HashMap::new();
 
-                let acc_a = fields[0].to_string();
-                let acc_b = fields[1].to_string();
-                let bit_score: f64 = fields[11].parse().map_err(|e| format!("Error parsing bit score in {}: {}", rbh_file.display(), e))?;
-
-                id_to_top_score
-                    .entry(acc_a.clone())
-                    .and_modify(|e| *e = e.max(bit_score))
-                    .or_insert(bit_score);
-                id_to_top_score
-                    .entry(acc_b.clone())
-                    .and_modify(|e| *e = e.max(bit_score))
-                    .or_insert(bit_score);
-            }
+    for (cluster_id, genes) in cluster_map {
+        for gene in genes {
+            gene_to_cluster.insert(gene.clone(), *cluster_id);
         }
     }
 
-    Ok(id_to_top_score)
+    gene_to_cluster
 }
 
 pub fn get_inparalogs(
     repo: &[RepoEntry],
-    blast_out_dir: &Path,
+    search_hits: &SearchHits,
     gene_to_top_ortho_blast_score: &HashMap<String, f64>,
     gene_to_cluster: &HashMap<String, usize>,
     logger: &Logger,
@@ -158,12 +184,17 @@ pub fn get_inparalogs(
         }
 
         // input
-        let self_blast_file_name = format!("{}_vs_{}.out", genome, genome);
-        let self_blast_file = blast_out_dir.join(self_blast_file_name);
-        let reader = open_bufread(&self_blast_file, &logger, "get_inparalogs");
+        let Some(self_blast_file) = search_io::find_pair_file(&search_hits.dir, genome, genome) else {
+            logger.warning(&format!("get_inparalogs: missing {}_vs_{} in {} (failed search?), skipping", genome, genome, search_hits.dir.display()));
+            continue;
+        };
+        let reader = search_io::open_search_reader(&self_blast_file, logger, "get_inparalogs");
 
         for line in reader.lines() {
             let line = line.map_err(|e| format!("Error reading line: {}", e))?;
+            if !search_hits.keeps(&line) {
+                continue;
+            }
             let fields: Vec<&str> = line.trim().split('\t').collect();
             if fields.len() < 12 {
                 continue;
@@ -294,4 +325,72 @@ pub fn write_final_rbh_clusters<P: AsRef<Path>>(
             process::exit(1);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit_line(q: &str, s: &str, bitscore: f64) -> String {
+        format!("{}\t{}\t90\t10\t0\t0\t1\t10\t1\t10\t1e-5\t{}\t10\t10", q, s, bitscore)
+    }
+
+    fn write_pair(dir: &Path, name: &str, hits: &[(&str, &str, f64)]) -> std::path::PathBuf {
+        let path = dir.join(name);
+        let lines: Vec<String> = hits.iter().map(|(q, s, b)| hit_line(q, s, *b)).collect();
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+        path
+    }
+
+    fn pairs_with_tolerance(tie_tolerance: f64) -> Vec<RbhPair> {
+        let dir = tempfile::tempdir().unwrap();
+        // a1 hits b1 (best HSP 100) and b2 (97, within 5% of 100); a2 hits b2 best
+        let a_vs_b = write_pair(dir.path(), "A_vs_B.out", &[
+            ("A|a1", "B|b1", 60.0),
+            ("A|a1", "B|b1", 100.0),
+            ("A|a1", "B|b2", 97.0),
+            ("A|a2", "B|b2", 50.0),
+            ("A|a2", "B|b1", 40.0),
+        ]);
+        // b2 hits a1 (90) and a2 (88, within 5% of 90)
+        let b_vs_a = write_pair(dir.path(), "B_vs_A.out", &[
+            ("B|b1", "A|a1", 98.0),
+            ("B|b2", "A|a1", 90.0),
+            ("B|b2", "A|a2", 88.0),
+        ]);
+        let params = RbhParams { tie_tolerance, min_coverage: 0.0 };
+        let hits = SearchHits::unfiltered(dir.path());
+        reciprocal_best_hits(&a_vs_b, &b_vs_a, &hits, &params, &Logger).unwrap()
+    }
+
+    #[test]
+    fn best_hits_keep_ties_within_tolerance() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_pair(dir.path(), "B_vs_A.out", &[
+            ("B|b2", "A|a1", 90.0),
+            ("B|b2", "A|a2", 88.0),
+            ("B|b2", "A|a3", 85.0),
+            ("B|b2", "B|b2", 200.0),
+        ]);
+        let params = RbhParams { tie_tolerance: 0.05, min_coverage: 0.0 };
+        let best = best_hits_per_query(&path, &SearchHits::unfiltered(dir.path()), &params, &Logger).unwrap();
+        let mut subjects: Vec<(String, f64)> = best["B|b2"].clone();
+        subjects.sort_by(|x, y| x.0.cmp(&y.0));
+        // cutoff 90 * 0.95 = 85.5; the self hit is ignored
+        assert_eq!(subjects, vec![("A|a1".to_string(), 90.0), ("A|a2".to_string(), 88.0)]);
+    }
+
+    #[test]
+    fn strict_rbh_without_tolerance() {
+        assert_eq!(pairs_with_tolerance(0.0), vec![("A|a1".to_string(), "B|b1".to_string(), 100.0)]);
+    }
+
+    #[test]
+    fn rbh_ties_within_tolerance() {
+        assert_eq!(pairs_with_tolerance(0.05), vec![
+            ("A|a1".to_string(), "B|b1".to_string(), 100.0),
+            ("A|a1".to_string(), "B|b2".to_string(), 97.0),
+            ("A|a2".to_string(), "B|b2".to_string(), 88.0),
+        ]);
+    }
+}
@@ -0,0 +1,444 @@
+use crate::logger::Logger;
+use crate::Args;
+use crate::hits::{self, SearchHits};
+use crate::read_gff::GffFeature;
+use crate::search_io;
+use crate::util::open_bufwrite;
+
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use std::path::Path;
+
+/// Collinearity settings for the synteny step
+#[derive(Debug, Clone)]
+pub struct SyntenyParams {
+    pub max_gap: usize,     // genes allowed between consecutive anchors of a block (in both genomes)
+    pub min_anchors: usize, // anchors needed for a syntenic block
+    pub top_hits: usize,    // best hits per gene and genome used as candidate anchors
+}
+
+impl SyntenyParams {
+    pub fn from_args(args: &Args) -> Self {
+        SyntenyParams {
+            max_gap: args.synteny_max_gap,
+            min_anchors: args.synteny_min_anchors,
+            top_hits: args.synteny_top_hits,
+        }
+    }
+}
+
+/// Position of a gene: genome, contig and rank along the contig
+#[derive(Debug, Clone)]
+pub struct GenePosition {
+    pub genome: String,
+    pub contig: String,
+    pub index: usize,
+}
+
+/// Gene order from the parsed GFF: genome|gene ID -> position
+pub fn gene_positions(all_features: &HashMap<String, Vec<GffFeature>>) -> HashMap<String, GenePosition> {
+
+    let mut positions = HashMap::new();
+    for (genome, features) in all_features {
+        let mut genes: Vec<(&str, usize, &str)> = features
+            .iter()
+            .filter_map(|f| f.attributes.get("ID").map(|id| (f.seqid.as_str(), f.start, id.as_str())))
+            .collect();
+        genes.sort();
+
+        let mut index = 0usize;
+        let mut last_contig = "";
+        for (contig, _, id) in genes {
+            if contig != last_contig {
+                index = 0;
+                last_contig = contig;
+            }
+            positions.insert(id.to_string(), GenePosition { genome: genome.clone(), contig: contig.to_string(), index });
+            index += 1;
+        }
+    }
+    positions
+}
+
+/// Candidate anchors: for each gene, its `top_hits` best hits in every other genome.
+/// Returns one entry per gene pair (gene_a < gene_b) with the best bitscore of either direction
+pub fn candidate_anchors(search_hits: &SearchHits, params: &SyntenyParams, logger: &Logger) -> HashMap<(String, String), f64> {
+
+    // query -> subject genome -> (subject, bitscore)
+    let mut best: HashMap<String, HashMap<String, Vec<(String, f64)>>> = HashMap::new();
+    let mut skipped = 0usize;
+    search_io::for_each_hit_line(search_hits, logger, "candidate_anchors", |line| {
+        let Ok(hit) = hits::parse_hit_line(line) else {
+            skipped += 1;
+            return;
+        };
+        let (Some((q_genome, _)), Some((s_genome, _))) = (hit.query.split_once('|'), hit.subject.split_once('|')) else {
+            skipped += 1;
+            return;
+        };
+        if q_genome == s_genome {
+            return;
+        }
+        best.entry(hit.query.clone())
+            .or_default()
+            .entry(s_genome.to_string())
+            .or_default()
+            .push((hit.subject, hit.bitscore));
+    });
+    if skipped > 0 {
+        logger.warning(&format!("candidate_anchors: skipped {} malformed hit lines", skipped));
+    }
+
+    let mut anchors: HashMap<(String, String), f64> = HashMap::new();
+    for (query, per_genome) in best {
+        for (_, mut subjects) in per_genome {
+            subjects.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+            subjects.dedup_by(|a, b| a.0 == b.0);
+            for (subject, bitscore) in subjects.into_iter().take(params.top_hits) {
+                let key = if query < subject { (query.clone(), subject) } else { (subject, query.clone()) };
+                let entry = anchors.entry(key).or_insert(0.0);
+                *entry = entry.max(bitscore);
+            }
+        }
+    }
+
+    logger.information(&format!("candidate_anchors: {} candidate anchor pairs", anchors.len()));
+    anchors
+}
+
+/// Anchor on one contig pair: (index on contig a, index on contig b, bitscore, anchor number)
+type Anchor = (usize, usize, f64, usize);
+
+/// Best collinear chain of the unused anchors (sorted by index a), in either orientation.
+/// Chain score is the number of anchors, with the summed bitscore breaking ties
+fn best_chain(anchors: &[Anchor], used: &[bool], max_gap: usize) -> Vec<usize> {
+
+    let mut best_chain: Vec<usize> = Vec::new();
+    let mut best_score = (0usize, 0.0f64);
+
+    for reverse in [false, true] {
+        let mut score: Vec<(usize, f64)> = vec![(0, 0.0); anchors.len()];
+        let mut prev: Vec<Option<usize>> = vec![None; anchors.len()];
+
+        for j in 0..anchors.len() {
+            if used[j] {
+                continue;
+            }
+            let (ja, jb, jw, _) = anchors[j];
+            score[j] = (1, jw);
+            for i in (0..j).rev() {
+                let (ia, ib, _, _) = anchors[i];
+                if ja - ia > max_gap + 1 {
+                    break;
+                }
+                if used[i] || ia == ja {
+                    continue;
+                }
+                let step_b = if reverse { ib.checked_sub(jb) } else { jb.checked_sub(ib) };
+                if !matches!(step_b, Some(d) if d >= 1 && d <= max_gap + 1) {
+                    continue;
+                }
+                let candidate = (score[i].0 + 1, score[i].1 + jw);
+                if candidate.0 > score[j].0 || (candidate.0 == score[j].0 && candidate.1 > score[j].1) {
+                    score[j] = candidate;
+                    prev[j] = Some(i);
+                }
+            }
+            if score[j].0 > best_score.0 || (score[j].0 == best_score.0 && score[j].1 > best_score.1) {
+                best_score = score[j];
+                let mut chain = vec![j];
+                let mut k = j;
+                while let Some(p) = prev[k] {
+                    chain.push(p);
+                    k = p;
+                }
+                best_chain = chain;
+            }
+        }
+    }
+    best_chain
+}
+
+/// Chain candidate anchors into syntenic blocks along gene order. Returns
+/// (block ID from 1, gene_a, gene_b, bitscore) for every anchor in a block of at least `min_anchors`
+pub fn find_syntenic_anchors(
+    candidates: &HashMap<(String, String), f64>,
+    positions: &HashMap<String, GenePosition>,
+    params: &SyntenyParams,
+    logger: &Logger,
+) -> Vec<(usize, String, String, f64)> {
+
+    // Group anchors by contig pair, genes ordered so that contig a <= contig b
+    let mut pairs: Vec<(&String, &String, f64)> = Vec::new();
+    let mut missing = 0usize;
+    let mut by_contigs: HashMap<(&str, &str, &str, &str), Vec<Anchor>> = HashMap::new();
+    for ((a, b), &w) in candidates {
+        let (Some(pa), Some(pb)) = (positions.get(a), positions.get(b)) else {
+            missing += 1;
+            continue;
+        };
+        let (a, b, pa, pb) = if (&pa.genome, &pa.contig) <= (&pb.genome, &pb.contig) { (a, b, pa, pb) } else { (b, a, pb, pa) };
+        by_contigs
+            .entry((pa.genome.as_str(), pa.contig.as_str(), pb.genome.as_str(), pb.contig.as_str()))
+            .or_default()
+            .push((pa.index, pb.index, w, pairs.len()));
+        pairs.push((a, b, w));
+    }
+    if missing > 0 {
+        logger.warning(&format!("find_syntenic_anchors: {} hit pairs involve genes missing from the parsed GFF", missing));
+    }
+
+    // Contig pairs are chained independently, in parallel
+    let mut groups: Vec<_> = by_contigs.into_iter().collect();
+    groups.sort_by(|x, y| x.0.cmp(&y.0));
+    let blocks: Vec<Vec<Vec<usize>>> = groups
+        .into_par_iter()
+        .map(|(_, mut anchors)| {
+            anchors.sort_by_key(|x| (x.0, x.1));
+            let mut used = vec![false; anchors.len()];
+            let mut found = Vec::new();
+            loop {
+                let chain = best_chain(&anchors, &used, params.max_gap);
+                if chain.len() < params.min_anchors.max(2) {
+                    break;
+                }
+                for &k in &chain {
+                    used[k] = true;
+                }
+                // chains are traced back from their last anchor
+                found.push(chain.iter().rev().map(|&k| anchors[k].3).collect());
+            }
+            found
+        })
+        .collect();
+
+    let mut out = Vec::new();
+    for (block_id, block) in blocks.into_iter().flatten().enumerate() {
+        for k in block {
+            let (a, b, w) = pairs[k];
+            out.push((block_id + 1, a.clone(), b.clone(), w));
+        }
+    }
+    let n_blocks = out.last().map(|x| x.0).unwrap_or(0);
+    logger.information(&format!("find_syntenic_anchors: {} syntenic anchors in {} blocks", out.len(), n_blocks));
+    out
+}
+
+/// Write syntenic anchors: block, genome_a, gene_a, genome_b, gene_b, bitscore
+pub fn write_anchors(path: &Path, anchors: &[(usize, String, String, f64)], logger: &Logger) {
+    let mut writer = open_bufwrite(path, logger, "write_anchors");
+    for (block, a, b, w) in anchors {
+        let (ga, ia) = a.split_once('|').unwrap_or(("", a));
+        let (gb, ib) = b.split_once('|').unwrap_or(("", b));
+        if let Err(e) = writeln!(writer, "{}\t{}\t{}\t{}\t{}\t{}", block, ga, ia, gb, ib, w) {
+            logger.error(&format!("write_anchors: write error {}: {}", path.display(), e));
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = writer.flush() {
+        logger.error(&format!("write_anchors: write error {}: {}", path.display(), e));
+        std::process::exit(1);
+    }
+}
+
+/// Split homology clusters into positional orthologs.
+///
+/// Within each cluster, syntenic anchors are linked strongest first, and only when the two
+/// groups share no genome, so each group holds at most one syntenic gene per genome. Other
+/// genes of the cluster join the group of their best hit (non-syntenic members), and those
+/// without a hit to a syntenic gene form one non-syntenic group; a cluster without syntenic
+/// anchors is kept whole. Returns groups of (genome|gene, syntenic).
+pub fn split_clusters(
+    clusters: &HashMap<usize, Vec<String>>,
+    anchors: &[(usize, String, String, f64)],
+    edges: &[(String, String, f64)],
+) -> Vec<Vec<(String, bool)>> {
+
+    let mut cluster_ids: Vec<&usize> = clusters.keys().collect();
+    cluster_ids.sort();
+    let gene_to_cluster: HashMap<&str, usize> = clusters
+        .iter()
+        .flat_map(|(&c, genes)| genes.iter().map(move |g| (g.as_str(), c)))
+        .collect();
+
+    // Syntenic anchors and hit edges within each cluster
+    let mut cluster_anchors: HashMap<usize, Vec<(&str, &str, f64)>> = HashMap::new();
+    for (_, a, b, w) in anchors {
+        if let (Some(ca), Some(cb)) = (gene_to_cluster.get(a.as_str()), gene_to_cluster.get(b.as_str())) {
+            if ca == cb {
+                cluster_anchors.entry(*ca).or_default().push((a, b, *w));
+            }
+        }
+    }
+    let mut cluster_edges: HashMap<usize, Vec<(&str, &str, f64)>> = HashMap::new();
+    for (a, b, w) in edges {
+        if let (Some(ca), Some(cb)) = (gene_to_cluster.get(a.as_str()), gene_to_cluster.get(b.as_str())) {
+            if ca == cb {
+                cluster_edges.entry(*ca).or_default().push((a, b, *w));
+            }
+        }
+    }
+
+    let genome_of = |g: &str| g.split_once('|').map(|(genome, _)| genome.to_string()).unwrap_or_default();
+
+    let mut out: Vec<Vec<(String, bool)>> = Vec::new();
+    for c in cluster_ids {
+        let genes = &clusters[c];
+        let index: HashMap<&str, usize> = genes.iter().enumerate().map(|(i, g)| (g.as_str(), i)).collect();
+
+        // strongest anchors first; merge groups with disjoint genomes
+        let mut parent: Vec<usize> = (0..genes.len()).collect();
+        let mut group_genomes: Vec<BTreeSet<String>> = genes.iter().map(|g| BTreeSet::from([genome_of(g)])).collect();
+        let mut syntenic = vec![false; genes.len()];
+        fn root(parent: &mut [usize], mut x: usize) -> usize {
+            while parent[x] != x {
+                parent[x] = parent[parent[x]];
+                x = parent[x];
+            }
+            x
+        }
+
+        let mut links = cluster_anchors.remove(c).unwrap_or_default();
+        links.sort_by(|x, y| y.2.total_cmp(&x.2).then((x.0, x.1).cmp(&(y.0, y.1))));
+        for (a, b, _) in links {
+            let (ia, ib) = (index[a], index[b]);
+            let (ra, rb) = (root(&mut parent, ia), root(&mut parent, ib));
+            if ra == rb {
+                syntenic[ia] = true;
+                syntenic[ib] = true;
+                continue;
+            }
+            if group_genomes[ra].is_disjoint(&group_genomes[rb]) {
+                parent[rb] = ra;
+                let moved = std::mem::take(&mut group_genomes[rb]);
+                group_genomes[ra].extend(moved);
+                syntenic[ia] = true;
+                syntenic[ib] = true;
+            }
+        }
+
+        // non-syntenic genes join the group of their best-scoring syntenic hit
+        if syntenic.iter().any(|&s| s) {
+            let mut best: Vec<Option<(f64, usize)>> = vec![None; genes.len()];
+            for (a, b, w) in cluster_edges.remove(c).unwrap_or_default() {
+                let (ia, ib) = (index[a], index[b]);
+                for (x, y) in [(ia, ib), (ib, ia)] {
+                    if !syntenic[x] && syntenic[y] && best[x].is_none_or(|(bw, _)| w > bw) {
+                        best[x] = Some((w, y));
+                    }
+                }
+            }
+            let mut leftover: Option<usize> = None;
+            for (x, b) in best.iter().enumerate() {
+                if let Some((_, y)) = b {
+                    let (rx, ry) = (root(&mut parent, x), root(&mut parent, *y));
+                    parent[rx] = ry;
+                } else if !syntenic[x] {
+                    match leftover {
+                        Some(l) => parent[x] = l,
+                        None => leftover = Some(x),
+                    }
+                }
+            }
+        } else {
+            for x in 1..genes.len() {
+                let (rx, r0) = (root(&mut parent, x), root(&mut parent, 0));
+                if rx != r0 {
+                    parent[rx] = r0;
+                }
+            }
+        }
+
+        let mut groups: HashMap<usize, Vec<(String, bool)>> = HashMap::new();
+        for (x, gene) in genes.iter().enumerate() {
+            let r = root(&mut parent, x);
+            groups.entry(r).or_default().push((gene.clone(), syntenic[x]));
+        }
+        let mut groups: Vec<Vec<(String, bool)>> = groups.into_values().collect();
+        for g in groups.iter_mut() {
+            g.sort();
+        }
+        groups.sort();
+        out.extend(groups);
+    }
+    out
+}
+
+/// Write positional ortholog groups in the .OrthoClusters format, with a fifth column
+/// marking syntenic or non-syntenic membership
+pub fn write_synteny_clusters(path: &Path, groups: &[Vec<(String, bool)>], logger: &Logger) {
+    let mut writer = open_bufwrite(path, logger, "write_synteny_clusters");
+    for (i, group) in groups.iter().enumerate() {
+        for (gene, syntenic) in group {
+            let Some((genome, gene_id)) = gene.split_once('|') else {
+                logger.error(&format!("write_synteny_clusters: Invalid gene ID format: {}", gene));
+                std::process::exit(1);
+            };
+            let membership = if *syntenic { "syntenic" } else { "non-syntenic" };
+            if let Err(e) = writeln!(writer, "{}\tOrtho\t{}\t{}\t{}", i + 1, genome, gene_id, membership) {
+                logger.error(&format!("write_synteny_clusters: write error {}: {}", path.display(), e));
+                std::process::exit(1);
+            }
+        }
+        if let Err(e) = writeln!(writer) {
+            logger.error(&format!("write_synteny_clusters: write error {}: {}", path.display(), e));
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = writer.flush() {
+        logger.error(&format!("write_synteny_clusters: write error {}: {}", path.display(), e));
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_collinear_block() {
+        // anchors sorted by index a; (2, 30) is off the diagonal
+        let anchors: Vec<Anchor> = vec![(1, 10, 5.0, 0), (2, 11, 5.0, 1), (2, 30, 9.0, 2), (3, 12, 5.0, 3)];
+        assert_eq!(best_chain(&anchors, &[false; 4], 0), vec![3, 1, 0]);
+    }
+
+    #[test]
+    fn chain_inverted_block() {
+        let anchors: Vec<Anchor> = vec![(1, 20, 5.0, 0), (2, 19, 5.0, 1), (3, 18, 5.0, 2), (4, 40, 9.0, 3)];
+        assert_eq!(best_chain(&anchors, &[false; 4], 0), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn chain_skips_used_anchors_within_max_gap() {
+        let anchors: Vec<Anchor> = vec![(1, 10, 5.0, 0), (2, 11, 5.0, 1), (3, 12, 5.0, 2)];
+        let used = [false, true, false];
+        // one gene skipped on each genome
+        assert_eq!(best_chain(&anchors, &used, 1), vec![2, 0]);
+        // without a gap the best chain is a single anchor (the first, as scores tie)
+        assert_eq!(best_chain(&anchors, &used, 0), vec![0]);
+    }
+
+    #[test]
+    fn split_keeps_non_syntenic_genes() {
+        let genes = |names: &[&str]| names.iter().map(|g| g.to_string()).collect::<Vec<String>>();
+        let clusters = HashMap::from([
+            (1, genes(&["A|a1", "A|a2", "B|b1", "B|b2", "C|c1"])),
+            (2, genes(&["A|a3", "B|b3"])),
+        ]);
+        let anchors = vec![(1, "A|a1".to_string(), "B|b1".to_string(), 100.0)];
+        let edges = vec![
+            ("A|a2".to_string(), "B|b1".to_string(), 50.0),
+            ("B|b2".to_string(), "C|c1".to_string(), 80.0),
+        ];
+        let member = |g: &str, syntenic: bool| (g.to_string(), syntenic);
+
+        assert_eq!(split_clusters(&clusters, &anchors, &edges), vec![
+            // a2 joins its best syntenic hit; b2 and c1 have none and are kept together
+            vec![member("A|a1", true), member("A|a2", false), member("B|b1", true)],
+            vec![member("B|b2", false), member("C|c1", false)],
+            // no anchors: the cluster is kept whole
+            vec![member("A|a3", false), member("B|b3", false)],
+        ]);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::logger::Logger;
+use crate::ortholog_summary;
+use crate::ortholog_summary_plot::ClassThresholds;
+use crate::tree;
+use crate::RepoEntry;
+use crate::util::open_bufwrite;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// A named set of genomes, from 'group' rows of the repo spec or a clade of the tree (--clade)
+#[derive(Debug, Clone)]
+pub struct GenomeGroup {
+    pub name: String,
+    pub source: String, // "repo_spec" or "clade"
+    pub genomes: BTreeSet<String>,
+}
+
+/// Groups given by 'group' rows of the repo spec
+pub fn groups_from_repo(repo: &[RepoEntry]) -> Vec<GenomeGroup> {
+    let mut by_name: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for entry in repo {
+        for group in &entry.groups {
+            by_name.entry(group.clone()).or_default().insert(entry.name.clone());
+        }
+    }
+    by_name
+        .into_iter()
+        .map(|(name, genomes)| GenomeGroup { name, source: "repo_spec".to_string(), genomes })
+        .collect()
+}
+
+/// Groups given by --clade NAME=GENOME1,GENOME2[,...]: the smallest clade of the tree that
+/// contains all the named genomes. The tree is treated as unrooted (FastTree trees are), so
+/// a clade is either side of any branch.
+pub fn groups_from_clades(clade_args: &[String], tree_path: &Path, logger: &Logger) -> Vec<GenomeGroup> {
+
+    if clade_args.is_empty() {
+        return Vec::new();
+    }
+    let Ok(newick) = fs::read_to_string(tree_path) else {
+        logger.warning(&format!("groups_from_clades: no tree at {} yet, --clade groups are added once the tree step has run", tree_path.display()));
+        return Vec::new();
+    };
+
+    let leaves: BTreeSet<String> = tree::extract_leaf_order_from_newick(&newick).into_iter().collect();
+    let mut sides: Vec<BTreeSet<String>> = Vec::new();
+    for clade in tree::newick_clades(&newick) {
+        sides.push(leaves.difference(&clade).cloned().collect());
+        sides.push(clade);
+    }
+
+    let mut groups: Vec<GenomeGroup> = Vec::new();
+    for arg in clade_args {
+        let Some((name, genome_list)) = arg.split_once('=') else {
+            logger.error(&format!("groups_from_clades: --clade '{}' is not in the format NAME=GENOME1,GENOME2", arg));
+            std::process::exit(1);
+        };
+        let named: BTreeSet<String> = genome_list.split(',').map(|g| g.trim().to_string()).filter(|g| !g.is_empty()).collect();
+        if let Some(missing) = named.iter().find(|g| !leaves.contains(*g)) {
+            logger.error(&format!("groups_from_clades: genome '{}' of --clade {} is not in the tree {}", missing, name, tree_path.display()));
+            std::process::exit(1);
+        }
+
+        let clade = sides
+            .iter()
+            .filter(|side| named.is_subset(side))
+            .min_by_key(|side| side.len())
+            .cloned()
+            .unwrap_or(named);
+        logger.information(&format!("groups_from_clades: clade {} has {} genomes: {}", name, clade.len(), clade.iter().cloned().collect::<Vec<_>>().join(", ")));
+        groups.push(GenomeGroup { name: name.trim().to_string(), source: "clade".to_string(), genomes: clade });
+    }
+    groups
+}
+
+fn write_lines(path: &Path, lines: &[String], logger: &Logger) {
+    let mut writer = open_bufwrite(path, logger, "write_group_reports");
+    if let Err(e) = writeln!(writer, "{}", lines.join("\n")) {
+        logger.error(&format!("write_group_reports: write error {}: {}", path.display(), e));
+        std::process::exit(1);
+    }
+}
+
+/// Intersection (UpSet) rows: for each combination of sets sharing orthogroups, a 1/0 column
+/// per set, the number of sets and the number of orthogroups, largest first
+fn upset_lines(set_names: &[String], combinations: HashMap<Vec<usize>, usize>) -> Vec<String> {
+    let mut rows: Vec<(Vec<usize>, usize)> = combinations.into_iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.len().cmp(&b.0.len())).then(a.0.cmp(&b.0)));
+
+    let mut lines = vec![format!("#{}\tdegree\torthogroups", set_names.join("\t"))];
+    for (sets, count) in rows {
+        let flags: Vec<&str> = (0..set_names.len()).map(|i| if sets.contains(&i) { "1" } else { "0" }).collect();
+        lines.push(format!("{}\t{}\t{}", flags.join("\t"), sets.len(), count));
+    }
+    lines
+}
+
+/// Write the group and intersection reports for a clusters_and_uniques file:
+///
+/// * `<prefix>.upset_genomes.tsv`: orthogroups shared by each combination of genomes
+/// * `<prefix>.upset_groups.tsv`: the same for groups (a group has an orthogroup if any member has it)
+/// * `<prefix>.groups.tsv`: the groups, their genomes and number of group-specific orthogroups
+/// * `<prefix>.group_specific.tsv`: orthogroups in all genomes of a group and none of the others,
+///   allowing `tolerance` percent of the group to lack them and of the others to have them
+pub fn write_group_reports(
+    combined_clusters_path: &Path,  // GENE_CLUSTERS_SUMMARIES.*.clusters_and_uniques
+    out_dir: &Path,
+    prefix: &str,                   // GENE_CLUSTERS_SUMMARIES.<type>.<method>
+    groups: &[GenomeGroup],
+    tolerance: f64,
+    logger: &Logger,
+) {
+
+    logger.information(&format!("write_group_reports: reading {}", combined_clusters_path.display()));
+    let members = ortholog_summary::read_cluster_members(combined_clusters_path, logger);
+
+    let genomes: Vec<String> = members.values().flatten().map(|(g, _)| g.clone()).collect::<BTreeSet<_>>().into_iter().collect();
+    let genome_index: HashMap<&str, usize> = genomes.iter().enumerate().map(|(i, g)| (g.as_str(), i)).collect();
+
+    // genomes each orthogroup is present in
+    let presence: Vec<_> = members
+        .iter()
+        .map(|(cluster_id, genes)| (cluster_id, genes.iter().map(|(g, _)| genome_index[g.as_str()]).collect::<BTreeSet<usize>>(), genes))
+        .collect();
+
+    // Intersections across genomes
+    let mut combinations: HashMap<Vec<usize>, usize> = HashMap::new();
+    for (_, present, _) in &presence {
+        *combinations.entry(present.iter().copied().collect()).or_insert(0) += 1;
+    }
+    let n_combinations = combinations.len();
+    write_lines(&out_dir.join(format!("{}.upset_genomes.tsv", prefix)), &upset_lines(&genomes, combinations), logger);
+    logger.information(&format!("write_group_reports: {} orthogroups in {} genome combinations", presence.len(), n_combinations));
+
+    // Remove reports of an earlier run that had groups
+    if groups.is_empty() {
+        for name in ["upset_groups.tsv", "groups.tsv", "group_specific.tsv"] {
+            let _ = fs::remove_file(out_dir.join(format!("{}.{}", prefix, name)));
+        }
+        return;
+    }
+
+    // Groups must name genomes in the orthogroups
+    let mut group_sets: Vec<(&GenomeGroup, BTreeSet<usize>)> = Vec::new();
+    for group in groups {
+        let set: BTreeSet<usize> = group.genomes.iter().filter_map(|g| genome_index.get(g.as_str()).copied()).collect();
+        if set.len() < group.genomes.len() {
+            logger.warning(&format!("write_group_reports: group {} lists genomes without orthogroups, ignored: {}", group.name,
+                group.genomes.iter().filter(|g| !genome_index.contains_key(g.as_str())).cloned().collect::<Vec<_>>().join(", ")));
+        }
+        if set.is_empty() {
+            continue;
+        }
+        group_sets.push((group, set));
+    }
+
+    // Intersections across groups (plus the genomes in no group)
+    let mut set_names: Vec<String> = group_sets.iter().map(|(g, _)| g.name.clone()).collect();
+    let mut sets: Vec<BTreeSet<usize>> = group_sets.iter().map(|(_, s)| s.clone()).collect();
+    let ungrouped: BTreeSet<usize> = (0..genomes.len()).filter(|i| !sets.iter().any(|s| s.contains(i))).collect();
+    if !ungrouped.is_empty() {
+        set_names.push("ungrouped".to_string());
+        sets.push(ungrouped);
+    }
+    let mut group_combinations: HashMap<Vec<usize>, usize> = HashMap::new();
+    for (_, present, _) in &presence {
+        let in_sets: Vec<usize> = sets.iter().enumerate().filter(|(_, s)| !s.is_disjoint(present)).map(|(i, _)| i).collect();
+        *group_combinations.entry(in_sets).or_insert(0) += 1;
+    }
+    write_lines(&out_dir.join(format!("{}.upset_groups.tsv", prefix)), &upset_lines(&set_names, group_combinations), logger);
+
+    // Group-specific orthogroups
+    let mut specific_lines = vec!["#group\tcluster_id\tpresent_in_group\tgroup_size\tpresent_outside\toutside_size\tgenes".to_string()];
+    let mut group_lines = vec!["#group\tsource\tgenomes\tspecific_orthogroups".to_string()];
+    for (group, set) in &group_sets {
+        let outside = genomes.len() - set.len();
+        let min_inside = ClassThresholds::min_genomes(100.0 - tolerance, set.len()).max(1);
+        let max_outside = ((tolerance * outside as f64 / 100.0) + 1e-9).floor() as usize;
+
+        let mut n_specific = 0usize;
+        for (cluster_id, present, genes) in &presence {
+            let inside = present.intersection(set).count();
+            let outside_present = present.len() - inside;
+            if inside < min_inside || outside_present > max_outside {
+                continue;
+            }
+            n_specific += 1;
+            let gene_list: Vec<String> = genes.iter().map(|(g, id)| format!("{}|{}", g, id)).collect();
+            specific_lines.push(format!("{}\t{}\t{}\t{}\t{}\t{}\t{}", group.name, cluster_id, inside, set.len(), outside_present, outside, gene_list.join(",")));
+        }
+
+        let names: Vec<&str> = set.iter().map(|&i| genomes[i].as_str()).collect();
+        group_lines.push(format!("{}\t{}\t{}\t{}", group.name, group.source, names.join(","), n_specific));
+        logger.information(&format!("write_group_reports: group {} ({} genomes): {} specific orthogroups", group.name, set.len(), n_specific));
+    }
+    write_lines(&out_dir.join(format!("{}.groups.tsv", prefix)), &group_lines, logger);
+    write_lines(&out_dir.join(format!("{}.group_specific.tsv", prefix)), &specific_lines, logger);
+}
@@ -0,0 +1,194 @@
+use crate::logger::Logger;
+use crate::util::open_bufwrite;
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+/// Limits on which edges may link two genes, to reduce chaining (0 disables a limit)
+#[derive(Debug, Clone)]
+pub struct LinkageParams {
+    pub min_edge_weight: f64, // minimum edge weight (e.g. bitscore)
+    pub min_jaccard: f64,     // minimum Jaccard similarity of the two genes' neighbourhoods (0-1)
+}
+
+/// Disjoint sets with path compression and union by size
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        UnionFind { parent: (0..n).collect(), size: vec![1; n] }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut node = x;
+        while self.parent[node] != root {
+            let next = self.parent[node];
+            self.parent[node] = root;
+            node = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut ra, mut rb) = (self.find(a), self.find(b));
+        if ra == rb {
+            return;
+        }
+        if self.size[ra] < self.size[rb] {
+            std::mem::swap(&mut ra, &mut rb);
+        }
+        self.parent[rb] = ra;
+        self.size[ra] += self.size[rb];
+    }
+}
+
+/// Single-linkage clustering of weighted gene pairs (the connected components of the graph).
+///
+/// Edges below `min_edge_weight` are dropped, then (if `min_jaccard` > 0) edges whose genes
+/// share too few neighbours, as in `slclust -j`. Returns cluster ID (from 1) to genes, with
+/// genes sorted within clusters and clusters ordered by their first gene.
+pub fn single_linkage(edges: &[(String, String, f64)], params: &LinkageParams) -> HashMap<usize, Vec<String>> {
+
+    // index genes
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    let mut names: Vec<&str> = Vec::new();
+    let mut links: Vec<(usize, usize)> = Vec::new();
+    for (a, b, weight) in edges {
+        if *weight < params.min_edge_weight {
+            continue;
+        }
+        let mut ids = [0usize; 2];
+        for (k, name) in [a.as_str(), b.as_str()].into_iter().enumerate() {
+            ids[k] = *index.entry(name).or_insert_with(|| {
+                names.push(name);
+                names.len() - 1
+            });
+        }
+        links.push((ids[0], ids[1]));
+    }
+
+    // neighbourhood filter
+    if params.min_jaccard > 0.0 {
+        let mut neighbours: Vec<HashSet<usize>> = (0..names.len()).map(|i| HashSet::from([i])).collect();
+        for &(a, b) in &links {
+            neighbours[a].insert(b);
+            neighbours[b].insert(a);
+        }
+        links.retain(|&(a, b)| {
+            let shared = neighbours[a].intersection(&neighbours[b]).count();
+            let total = neighbours[a].len() + neighbours[b].len() - shared;
+            shared as f64 / total as f64 >= params.min_jaccard
+        });
+    }
+
+    let mut sets = UnionFind::new(names.len());
+    for &(a, b) in &links {
+        sets.union(a, b);
+    }
+
+    // genes whose edges were all filtered out are left unclustered
+    let mut linked = vec![false; names.len()];
+    for &(a, b) in &links {
+        linked[a] = true;
+        linked[b] = true;
+    }
+
+    let mut components: HashMap<usize, BTreeSet<String>> = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        if linked[i] {
+            components.entry(sets.find(i)).or_default().insert(name.to_string());
+        }
+    }
+
+    let mut clusters: Vec<Vec<String>> = components.into_values().map(|genes| genes.into_iter().collect()).collect();
+    clusters.sort();
+
+    clusters.into_iter().enumerate().map(|(i, genes)| (i + 1, genes)).collect()
+}
+
+/// Write clusters one per line (genes separated by spaces), in cluster ID order
+pub fn write_clusters(path: &Path, clusters: &HashMap<usize, Vec<String>>, logger: &Logger) {
+    let mut writer = open_bufwrite(path, logger, "write_clusters");
+
+    let mut cluster_ids: Vec<&usize> = clusters.keys().collect();
+    cluster_ids.sort_unstable();
+
+    for cluster_id in cluster_ids {
+        if let Err(e) = writeln!(writer, "{}", clusters[cluster_id].join(" ")) {
+            logger.error(&format!("write_clusters: write error {}: {}", path.display(), e));
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = writer.flush() {
+        logger.error(&format!("write_clusters: write error {}: {}", path.display(), e));
+        std::process::exit(1);
+    }
+
+    logger.information(&format!("write_clusters: {} clusters written to {}", clusters.len(), path.display()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges(pairs: &[(&str, &str, f64)]) -> Vec<(String, String, f64)> {
+        pairs.iter().map(|(a, b, w)| (a.to_string(), b.to_string(), *w)).collect()
+    }
+
+    fn genes(names: &[&str]) -> Vec<String> {
+        names.iter().map(|g| g.to_string()).collect()
+    }
+
+    // two triangles joined by the bridge c-d
+    fn bridged_triangles() -> Vec<(String, String, f64)> {
+        edges(&[("a", "b", 1.0), ("b", "c", 1.0), ("a", "c", 1.0), ("c", "d", 1.0), ("d", "e", 1.0), ("e", "f", 1.0), ("d", "f", 1.0)])
+    }
+
+    #[test]
+    fn connected_components() {
+        let params = LinkageParams { min_edge_weight: 0.0, min_jaccard: 0.0 };
+        let clusters = single_linkage(&edges(&[("c", "b", 1.0), ("b", "a", 1.0), ("e", "d", 1.0)]), &params);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[&1], genes(&["a", "b", "c"]));
+        assert_eq!(clusters[&2], genes(&["d", "e"]));
+    }
+
+    #[test]
+    fn weak_edges_dropped() {
+        let params = LinkageParams { min_edge_weight: 10.0, min_jaccard: 0.0 };
+        let clusters = single_linkage(&edges(&[("a", "b", 50.0), ("b", "c", 5.0), ("c", "d", 20.0), ("e", "f", 1.0)]), &params);
+        // e and f have no edge left, so are not clustered
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[&1], genes(&["a", "b"]));
+        assert_eq!(clusters[&2], genes(&["c", "d"]));
+    }
+
+    #[test]
+    fn bridge_kept_without_jaccard() {
+        let params = LinkageParams { min_edge_weight: 0.0, min_jaccard: 0.0 };
+        let clusters = single_linkage(&bridged_triangles(), &params);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[&1], genes(&["a", "b", "c", "d", "e", "f"]));
+    }
+
+    #[test]
+    fn bridge_removed_by_jaccard() {
+        // c-d: {a,b,c,d} and {c,d,e,f} share 2 of 6 (0.33); a-c: 3 of 4 (0.75); a-b: 3 of 3
+        let params = LinkageParams { min_edge_weight: 0.0, min_jaccard: 0.5 };
+        let clusters = single_linkage(&bridged_triangles(), &params);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[&1], genes(&["a", "b", "c"]));
+        assert_eq!(clusters[&2], genes(&["d", "e", "f"]));
+
+        let params = LinkageParams { min_edge_weight: 0.0, min_jaccard: 0.3 };
+        assert_eq!(single_linkage(&bridged_triangles(), &params).len(), 1);
+    }
+}
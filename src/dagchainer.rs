@@ -19,10 +19,17 @@ pub struct ClusterMember {
     pub trans_id: String, // e.g. "CA1280:7000010362857299"
 }
 
+/// One run_DAG_chainer.pl command and the number of hit pairs it chains (its cost)
+#[derive(Debug, Clone)]
+pub struct DagchainerJob {
+    pub cmd: String,
+    pub n_pairs: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct DagchainerPaths {
     pub annot_gff: PathBuf,
-    pub genome_fasta: PathBuf,
+    pub genome_fasta: Option<PathBuf>, // None in proteome-only mode
 }
 
 // cluster_id -> list of members
@@ -136,7 +143,7 @@ pub fn process_orthocluster_results_into_hit_pairs(cluster_to_genes: &ClusterToG
 /// Uses paths already stored in `RepoEntry.files`.
 /// Expected keys (adjust if your keys differ):
 ///   - "gff_parsed" : parsed GFF3 written in step 1
-///   - "genome"     : genome FASTA from the repo spec
+///   - "genome"     : genome FASTA from the repo spec (optional, proteome-only genomes have none)
 pub fn save_genome_paths_for_dagchainer(repo_entries: &[RepoEntry], logger: &Logger) -> GenomePathMap {
 
     let mut map: GenomePathMap = BTreeMap::new();
@@ -160,14 +167,14 @@ pub fn save_genome_paths_for_dagchainer(repo_entries: &[RepoEntry], logger: &Log
             continue;
         };
 
-        // 2. Genome FASTA
+        // 2. Genome FASTA (DAGchainer only needs the annotation, so this is optional)
         let genome_fasta_path = if let Some(genome_file) = entry.files.get("genome") {
-            PathBuf::from(&genome_file.path)
+            Some(PathBuf::from(&genome_file.path))
         } else if let Some(genome_file) = entry.files.get("genome_parsed") {
-            PathBuf::from(&genome_file.path)
+            Some(PathBuf::from(&genome_file.path))
         } else {
-            logger.warning(&format!("save_genome_paths_for_dagchainer: no genome FASTA for genome {}, skipping", genome));
-            continue;
+            logger.information(&format!("save_genome_paths_for_dagchainer: no genome FASTA for genome {} (proteome-only mode)", genome));
+            None
         };
 
         map.insert(
@@ -180,7 +187,7 @@ pub fn save_genome_paths_for_dagchainer(repo_entries: &[RepoEntry], logger: &Log
     }
 
     if map.is_empty() {
-        logger.error("save_genome_paths_for_dagchainer: no genomes with both GFF and genome FASTA paths found");
+        logger.error("save_genome_paths_for_dagchainer: no genomes with GFF paths found");
         std::process::exit(1);
     }
 
@@ -194,7 +201,7 @@ pub fn save_genome_paths_for_dagchainer(repo_entries: &[RepoEntry], logger: &Log
 ///  - *.dagchainer.conf files for each genome pair
 ///  - dagchainer.cmds listing one run_DAG_chainer.pl command per pair
 ///
-/// Returns the list of command lines written to dagchainer.cmds.
+/// Returns one job per command line written to dagchainer.cmds.
 pub fn write_dagchainer_conf_file(
     dagchainer_rundir: &Path,
     dagchainer_prog: &Path,                       // run_DAG_chainer.pl
@@ -204,7 +211,7 @@ pub fn write_dagchainer_conf_file(
     dagchainer_args: &str,                        // e.g. "-v" or ""
     min_pairs: usize,
     logger: &Logger,
-) -> Vec<String> {
+) -> Vec<DagchainerJob> {
 
     // Ensure run directory exists
     mkdir(dagchainer_rundir, logger, "write_dagchainer_conf_file");
@@ -222,7 +229,7 @@ pub fn write_dagchainer_conf_file(
 
     logger.information(&format!("write_dagchainer_conf_file: writing DAGchainer config + hit_pairs for {} genomes to {}", genomes.len(), dagchainer_rundir.display()));
 
-    let mut all_cmds: Vec<String> = Vec::new();
+    let mut all_jobs: Vec<DagchainerJob> = Vec::new();
 
     // all unordered genome pairs i<j
     for i in 0..genomes.len() {
@@ -271,13 +278,11 @@ pub fn write_dagchainer_conf_file(
                 g2_annot = paths_j.annot_gff.display(),
             );
 
-            let genome_seq_section_text = format!(
-                "{g1} = {g1_genome}\n{g2} = {g2_genome}\n",
-                g1 = genome_i,
-                g1_genome = paths_i.genome_fasta.display(),
-                g2 = genome_j,
-                g2_genome = paths_j.genome_fasta.display(),
-            );
+            // genomes without a genome FASTA (proteome-only mode) are left out of [GenomeSequences]
+            let genome_seq_section_text: String = [(genome_i, paths_i), (genome_j, paths_j)]
+                .iter()
+                .filter_map(|(g, p)| p.genome_fasta.as_ref().map(|fasta| format!("{} = {}\n", g, fasta.display())))
+                .collect();
 
             // Write hit_pairs file for this genome pair
             let hit_pairs_path = dagchainer_rundir.join(format!("{g1}_vs_{g2}.hit_pairs", g1 = genome_i, g2 = genome_j));
@@ -331,7 +336,7 @@ pub fn write_dagchainer_conf_file(
                 std::process::exit(1);
             }
 
-            all_cmds.push(cmd);
+            all_jobs.push(DagchainerJob { cmd, n_pairs: gene_pairs.len() });
         }
     }
 
@@ -340,9 +345,9 @@ pub fn write_dagchainer_conf_file(
         std::process::exit(1);
     }
 
-    logger.information(&format!("write_dagchainer_conf_file: wrote {} DAGchainer command(s) to {}", all_cmds.len(), cmds_path.display()));
+    logger.information(&format!("write_dagchainer_conf_file: wrote {} DAGchainer command(s) to {}", all_jobs.len(), cmds_path.display()));
 
-    all_cmds
+    all_jobs
 }
 
 /// Build the full DAGchainer configuration file contents.
@@ -1,9 +1,13 @@
 use crate::logger::Logger;
 use crate::Args;
+use crate::aligner;
+use crate::msa;
+use crate::import_search::SearchProvenance;
+use crate::import_orthogroups::OrthogroupProvenance;
 use crate::synima::{ToolInfo, CitationInfo};
 use crate::util;
-use crate::ortholog_summary;
-use crate::ortholog_summary::OrthologyMethod;
+use crate::ortholog_summary::{OrthologyMethod, OrthologySource};
+use crate::ortholog_summary_plot::ClassThresholds;
 
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
@@ -85,28 +89,36 @@ pub fn find_executable(program: &str, bin_dir: &Path, logger: &Logger) -> PathBu
     std::process::exit(1);
 }
 
+/// Bundled executable if there is one, otherwise the bare name (run from PATH), for version reporting
+pub fn executable_for_version(program: &str, bin_dir: &Path) -> PathBuf {
+    let bundled_path = bin_dir.join(program);
+    if bundled_path.is_file() {
+        bundled_path
+    } else {
+        PathBuf::from(program)
+    }
+}
+
 /// Build the `tools[]` vector for the Methods page
 pub fn build_tools_vector(
     args: &Args,
-    preferred_method: Option<OrthologyMethod>,
-    orthofinder_out_dir: &Path,
-    omcl_out_dir: &Path,
-    rbh_out_dir: &Path,
-    logger: &Logger) -> Vec<ToolInfo> {
+    bin_dir: &Path,
+    source: &OrthologySource,
+    imported_search: Option<&SearchProvenance>,
+    imported_orthogroups: Option<&OrthogroupProvenance>) -> Vec<ToolInfo> {
 
     let synima_version = "2.0.0".to_string();
     let fasttree_version = "2.1.11 SSE3".to_string(); // bundled version
-    let muscle_version_raw   = util::get_version("muscle", &["-version"]).unwrap_or_else(|| "Unknown".into());
-    let muscle_version = util::clean_muscle_version(&muscle_version_raw);
-    let diamond_version_raw  = util::get_version("diamond", &["--version"]).unwrap_or_else(|| "Unknown".into());
-    let diamond_version = util::clean_diamond_version(&diamond_version_raw);
-    let blastp_version_raw   = util::get_version("blastp", &["-version"]).unwrap_or_else(|| "Unknown".into());
-    let blastp_version = util::clean_blast_version(&blastp_version_raw);
+    // Imported search results were not produced by any aligner run here
+    let aligner = if imported_search.is_some() { None } else { aligner::get_aligner(&args.aligner) };
 
     // Orthology method
-    let source = ortholog_summary::detect_orthology_source(preferred_method, &orthofinder_out_dir, &omcl_out_dir, &rbh_out_dir, &logger);
     let method_label = source.method_label();
-    let orthology_version = util::get_orthology_tool_version(method_label);
+    // Imported orthogroups: record the tool and file they came from
+    let orthology_version = match imported_orthogroups {
+        Some(p) => format!("{} orthogroups ({})", p.tool, p.path),
+        None => util::get_orthology_tool_version(method_label),
+    };
 
     // BUILD THE tools[] VECTOR
     let mut tools: Vec<ToolInfo> = Vec::new();
@@ -126,31 +138,35 @@ pub fn build_tools_vector(
     });
 
     // Aligner + versions
-    let aligner_version = match args.aligner.as_str() {
-        "diamond"    => diamond_version.clone(),
-        "blastplus"  => blastp_version.clone(),
-        "legacy"     => "Legacy BLAST (no version reporting)".into(),
-        _            => "Unknown".into(),
+    let (aligner_name, aligner_version) = match (&aligner, imported_search) {
+        (_, Some(p)) => ("imported".to_string(), format!("external search results ({})", p.path)),
+        (Some(a), None) => {
+            let (_, search_prog) = a.executables(&args.alignment_type);
+            (a.name().to_string(), a.version(&executable_for_version(search_prog, bin_dir)))
+        }
+        (None, None) => (args.aligner.clone(), "Unknown".into()),
     };
 
     tools.push(ToolInfo {
         category: "Aligner".into(),
-        name: args.aligner.clone(),
+        name: aligner_name,
         version: aligner_version,
     });
 
     // Aligner parameters
-    tools.push(ToolInfo {
-        category: "Aligner parameters".into(),
-        name: "max_target_seqs".into(),
-        version: args.max_target_seqs.to_string(),
-    });
+    if imported_search.is_none() {
+        tools.push(ToolInfo {
+            category: "Aligner parameters".into(),
+            name: "max_target_seqs".into(),
+            version: args.max_target_seqs.to_string(),
+        });
 
-    tools.push(ToolInfo {
-        category: "Aligner parameters".into(),
-        name: "evalue".into(),
-        version: args.evalue.clone(),
-    });
+        tools.push(ToolInfo {
+            category: "Aligner parameters".into(),
+            name: "evalue".into(),
+            version: args.evalue.clone(),
+        });
+    }
 
     tools.push(ToolInfo {
         category: "Orthology tool".into(),
@@ -158,25 +174,134 @@ pub fn build_tools_vector(
         version: orthology_version,
     });
 
-    if args.aligner == "diamond" {
+    // Orthology parameters (strict reciprocal best hits)
+    if method_label == "rbh" {
         tools.push(ToolInfo {
-            category: "Aligner parameters".into(),
-            name: "diamond_sensitivity".into(),
-            version: args.diamond_sensitivity.clone(),
+            category: "Orthology parameters".into(),
+            name: "rbh_tie_tolerance".into(),
+            version: args.rbh_tie_tolerance.to_string(),
+        });
+        tools.push(ToolInfo {
+            category: "Orthology parameters".into(),
+            name: "rbh_min_coverage".into(),
+            version: args.rbh_min_coverage.to_string(),
+        });
+        tools.push(ToolInfo {
+            category: "Orthology parameters".into(),
+            name: "rbh_min_link_bitscore".into(),
+            version: args.rbh_min_link_bitscore.to_string(),
+        });
+        tools.push(ToolInfo {
+            category: "Orthology parameters".into(),
+            name: "rbh_min_jaccard".into(),
+            version: args.rbh_min_jaccard.to_string(),
         });
     }
 
-    tools.push(ToolInfo {
-        category: "Multiple aligner".into(),
-        name: "MUSCLE".into(),
-        version: muscle_version.clone(),
-    });
+    // Orthology parameters (native OrthoMCL)
+    if method_label == "orthomcl" {
+        tools.push(ToolInfo {
+            category: "Orthology parameters".into(),
+            name: "omcl_evalue_cutoff".into(),
+            version: args.omcl_evalue_cutoff.to_string(),
+        });
+        tools.push(ToolInfo {
+            category: "Orthology parameters".into(),
+            name: "inflation".into(),
+            version: args.inflation.to_string(),
+        });
+    }
 
-    tools.push(ToolInfo {
-        category: "Tree builder".into(),
-        name: "FastTree".into(),
-        version: fasttree_version,
-    });
+    // Orthology parameters (Markov clustering)
+    if method_label == "mcl" {
+        tools.push(ToolInfo {
+            category: "Orthology parameters".into(),
+            name: "mcl_graph".into(),
+            version: args.mcl_graph.clone(),
+        });
+        tools.push(ToolInfo {
+            category: "Orthology parameters".into(),
+            name: "inflation".into(),
+            version: args.inflation.to_string(),
+        });
+    }
+
+    // Orthology parameters (synteny-aware orthology)
+    if method_label == "synteny" {
+        tools.push(ToolInfo {
+            category: "Orthology parameters".into(),
+            name: "inflation".into(),
+            version: args.inflation.to_string(),
+        });
+        tools.push(ToolInfo {
+            category: "Orthology parameters".into(),
+            name: "synteny_max_gap".into(),
+            version: args.synteny_max_gap.to_string(),
+        });
+        tools.push(ToolInfo {
+            category: "Orthology parameters".into(),
+            name: "synteny_min_anchors".into(),
+            version: args.synteny_min_anchors.to_string(),
+        });
+        tools.push(ToolInfo {
+            category: "Orthology parameters".into(),
+            name: "synteny_top_hits".into(),
+            version: args.synteny_top_hits.to_string(),
+        });
+    }
+
+    // Aligner specific parameters (e.g. diamond_sensitivity)
+    if let Some(a) = &aligner {
+        for (name, value) in a.parameters(args) {
+            tools.push(ToolInfo {
+                category: "Aligner parameters".into(),
+                name,
+                version: value,
+            });
+        }
+    }
+
+    // Orthogroup classification (% of genomes)
+    let thresholds = ClassThresholds::from_args(args);
+    for (name, value) in [("core_threshold", thresholds.core), ("soft_core_threshold", thresholds.soft_core), ("cloud_threshold", thresholds.cloud)] {
+        tools.push(ToolInfo {
+            category: "Orthogroup classification".into(),
+            name: name.into(),
+            version: value.to_string(),
+        });
+    }
+
+    if let Some(msa) = msa::get_msa(&args.msa) {
+        tools.push(ToolInfo {
+            category: "Multiple aligner".into(),
+            name: msa.display_name().into(),
+            version: msa.version(&executable_for_version(msa.executable(), bin_dir)),
+        });
+    }
+
+    if args.codon_alignment {
+        tools.push(ToolInfo {
+            category: "Multiple alignment parameters".into(),
+            name: "codon_alignment".into(),
+            version: format!("genetic code {}", args.genetic_code),
+        });
+    }
+
+    // The rooted species tree of a full OrthoFinder run replaces the FastTree supermatrix tree
+    let species_tree = matches!(source, OrthologySource::OrthoFinder(dir) if dir.join("SpeciesTree_rooted.txt").is_file());
+    if species_tree {
+        tools.push(ToolInfo {
+            category: "Tree builder".into(),
+            name: "OrthoFinder species tree".into(),
+            version: util::get_orthology_tool_version("orthofinder"),
+        });
+    } else {
+        tools.push(ToolInfo {
+            category: "Tree builder".into(),
+            name: "FastTree".into(),
+            version: fasttree_version,
+        });
+    }
 
     tools.push(ToolInfo {
         category: "Synteny chaining".into(),
@@ -194,7 +319,7 @@ pub fn build_tools_vector(
 }
 
 /// Build the `citations[]` vector for the Methods page
-pub fn build_citations_vector(args: &Args, preferred_method: Option<OrthologyMethod>) -> Vec<CitationInfo> {
+pub fn build_citations_vector(args: &Args, preferred_method: Option<OrthologyMethod>, imported_search: bool, imported_orthogroups: Option<&OrthogroupProvenance>) -> Vec<CitationInfo> {
 
     let method_label = match preferred_method {
         Some(m) => m.as_str(),
@@ -211,23 +336,11 @@ pub fn build_citations_vector(args: &Args, preferred_method: Option<OrthologyMet
     });
 
     // --- Aligner citations ---
-    match args.aligner.as_str() {
-        "diamond" => {
-            citations.push(CitationInfo {
-                tool: "DIAMOND".into(),
-                citation: "Buchfink B et al., Nat Methods (2015)".into(),
-                link: "https://pubmed.ncbi.nlm.nih.gov/25402007/".into(),
-            });
-        }
-
-        "blastplus" | "legacy" => {
-            citations.push(CitationInfo {
-                tool: "BLAST+".into(),
-                citation: "Camacho C et al., BMC Bioinformatics (2009)".into(),
-                link: "https://pubmed.ncbi.nlm.nih.gov/20003500/".into(),
-            });
+    // (none for imported search results, the aligner used is unknown)
+    if !imported_search {
+        if let Some(citation) = aligner::get_aligner(&args.aligner).and_then(|a| a.citation()) {
+            citations.push(citation);
         }
-        _ => {}
     }
 
     // --- Orthology tool citations ---
@@ -238,6 +351,11 @@ pub fn build_citations_vector(args: &Args, preferred_method: Option<OrthologyMet
                 citation: "Li L et al., Genome Res (2003)".into(),
                 link: "https://pubmed.ncbi.nlm.nih.gov/12952885/".into(),
             });
+            citations.push(CitationInfo {
+                tool: "MCL".into(),
+                citation: "Enright AJ et al., Nucleic Acids Res (2002)".into(),
+                link: "https://pubmed.ncbi.nlm.nih.gov/11917018/".into(),
+            });
         }
 
         "orthofinder" => {
@@ -252,9 +370,44 @@ pub fn build_citations_vector(args: &Args, preferred_method: Option<OrthologyMet
             // No citation needed; part of Synima2
         }
 
+        "mcl" | "synteny" => {
+            citations.push(CitationInfo {
+                tool: "MCL".into(),
+                citation: "Enright AJ et al., Nucleic Acids Res (2002)".into(),
+                link: "https://pubmed.ncbi.nlm.nih.gov/11917018/".into(),
+            });
+        }
+
         _ => {}
     }
 
+    // Tool that produced imported orthogroups (none for the two-column format)
+    if let Some(p) = imported_orthogroups {
+        match p.format.as_str() {
+            "orthofinder" => citations.push(CitationInfo {
+                tool: "OrthoFinder".into(),
+                citation: "Emms DM & Kelly S. Genome Biol. (2019)".into(),
+                link: "https://pubmed.ncbi.nlm.nih.gov/31727128/".into(),
+            }),
+            "proteinortho" => citations.push(CitationInfo {
+                tool: "Proteinortho".into(),
+                citation: "Lechner M et al., BMC Bioinformatics (2011)".into(),
+                link: "https://pubmed.ncbi.nlm.nih.gov/21526987/".into(),
+            }),
+            "broccoli" => citations.push(CitationInfo {
+                tool: "Broccoli".into(),
+                citation: "Derelle R et al., Mol Biol Evol (2020)".into(),
+                link: "https://pubmed.ncbi.nlm.nih.gov/32602888/".into(),
+            }),
+            "sonicparanoid" => citations.push(CitationInfo {
+                tool: "SonicParanoid".into(),
+                citation: "Cosentino S & Iwasaki W, Bioinformatics (2019)".into(),
+                link: "https://pubmed.ncbi.nlm.nih.gov/30032215/".into(),
+            }),
+            _ => {}
+        }
+    }
+
     // FastTree
     citations.push(CitationInfo {
         tool: "FastTree".into(),
@@ -262,12 +415,10 @@ pub fn build_citations_vector(args: &Args, preferred_method: Option<OrthologyMet
         link: "https://pubmed.ncbi.nlm.nih.gov/20224823/".into(),
     });
 
-    // muscle
-    citations.push(CitationInfo {
-        tool: "MUSCLE".into(),
-        citation: "Edgar RC et al., Nat Commun. (2022)".into(),
-        link: "https://pubmed.ncbi.nlm.nih.gov/36379955/".into(),
-    });
+    // Multiple aligner (--msa)
+    if let Some(msa) = msa::get_msa(&args.msa) {
+        citations.push(msa.citation());
+    }
 
     // DAGChainer
     citations.push(CitationInfo {
@@ -0,0 +1,304 @@
+use crate::logger::Logger;
+use crate::Args;
+use crate::search_io::{self, list_pair_files};
+use crate::util::open_bufwrite;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+
+/// One line of all-vs-all search output:
+/// qseqid sseqid pident length mismatch gapopen qstart qend sstart send evalue bitscore qlen slen
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub query: String,
+    pub subject: String,
+    pub pident: f64,
+    pub qstart: usize,
+    pub qend: usize,
+    pub sstart: usize,
+    pub send: usize,
+    pub bitscore: f64,
+    pub qlen: Option<usize>, // None for 12-column output from older runs
+    pub slen: Option<usize>,
+}
+
+impl Hit {
+    /// Percentage of the query covered by the alignment
+    pub fn query_coverage(&self) -> Option<f64> {
+        self.qlen.filter(|&l| l > 0).map(|l| span(self.qstart, self.qend) as f64 / l as f64 * 100.0)
+    }
+
+    /// Percentage of the subject covered by the alignment
+    pub fn subject_coverage(&self) -> Option<f64> {
+        self.slen.filter(|&l| l > 0).map(|l| span(self.sstart, self.send) as f64 / l as f64 * 100.0)
+    }
+}
+
+// nucleotide hits can be on the reverse strand (start > end)
+fn span(start: usize, end: usize) -> usize {
+    start.max(end) - start.min(end) + 1
+}
+
+/// Parse a tab-separated hit line (12 or 14 columns)
+pub fn parse_hit_line(line: &str) -> Result<Hit, String> {
+    let f: Vec<&str> = line.trim_end().split('\t').collect();
+    if f.len() < 12 {
+        return Err(format!("expected at least 12 columns, found {}: {}", f.len(), line));
+    }
+
+    fn num<T: std::str::FromStr>(f: &[&str], i: usize, name: &str) -> Result<T, String> {
+        f[i].trim().parse::<T>().map_err(|_| format!("invalid {} '{}'", name, f[i]))
+    }
+
+    Ok(Hit {
+        query: f[0].to_string(),
+        subject: f[1].to_string(),
+        pident: num(&f, 2, "pident")?,
+        qstart: num(&f, 6, "qstart")?,
+        qend: num(&f, 7, "qend")?,
+        sstart: num(&f, 8, "sstart")?,
+        send: num(&f, 9, "send")?,
+        bitscore: num(&f, 11, "bitscore")?,
+        qlen: if f.len() >= 14 { Some(num(&f, 12, "qlen")?) } else { None },
+        slen: if f.len() >= 14 { Some(num(&f, 13, "slen")?) } else { None },
+    })
+}
+
+/// Hit filters shared by all orthology methods (0 disables a filter)
+#[derive(Debug, Clone)]
+pub struct HitFilter {
+    pub min_pident: f64,
+    pub min_qcov: f64,
+    pub min_scov: f64,
+    pub min_bitscore_ratio: f64,
+}
+
+impl HitFilter {
+    pub fn from_args(args: &Args) -> Self {
+        HitFilter {
+            min_pident: args.min_pident,
+            min_qcov: args.min_qcov,
+            min_scov: args.min_scov,
+            min_bitscore_ratio: args.min_bitscore_ratio,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.min_pident > 0.0 || self.min_qcov > 0.0 || self.min_scov > 0.0 || self.min_bitscore_ratio > 0.0
+    }
+
+    /// Name of the first filter that removes this hit, if any
+    fn rejects(&self, hit: &Hit, self_scores: &HashMap<String, f64>) -> Option<FilterReason> {
+        if hit.pident < self.min_pident {
+            return Some(FilterReason::Pident);
+        }
+        if let Some(qcov) = hit.query_coverage() {
+            if qcov < self.min_qcov {
+                return Some(FilterReason::QueryCoverage);
+            }
+        }
+        if let Some(scov) = hit.subject_coverage() {
+            if scov < self.min_scov {
+                return Some(FilterReason::SubjectCoverage);
+            }
+        }
+        if self.min_bitscore_ratio > 0.0 {
+            if let Some(&self_score) = self_scores.get(&hit.query) {
+                if self_score > 0.0 && hit.bitscore / self_score < self.min_bitscore_ratio {
+                    return Some(FilterReason::BitscoreRatio);
+                }
+            }
+        }
+        None
+    }
+}
+
+enum FilterReason {
+    Pident,
+    QueryCoverage,
+    SubjectCoverage,
+    BitscoreRatio,
+}
+
+/// Hits removed per filter for one search output
+#[derive(Default, Clone)]
+struct FilterCounts {
+    total: usize,
+    pident: usize,
+    qcov: usize,
+    scov: usize,
+    bitscore_ratio: usize,
+    kept: usize,
+    has_lengths: bool,
+}
+
+impl FilterCounts {
+    fn add(&mut self, other: &FilterCounts) {
+        self.total += other.total;
+        self.pident += other.pident;
+        self.qcov += other.qcov;
+        self.scov += other.scov;
+        self.bitscore_ratio += other.bitscore_ratio;
+        self.kept += other.kept;
+    }
+}
+
+/// Best self-hit bitscore for every query, from the {g}_vs_{g}.out files
+fn read_self_scores(pairs: &[(String, String, PathBuf)], logger: &Logger) -> HashMap<String, f64> {
+    let mut self_scores: HashMap<String, f64> = HashMap::new();
+
+    for (q, s, path) in pairs {
+        if q != s {
+            continue;
+        }
+        let reader = search_io::open_search_reader(path, logger, "read_self_scores");
+        for line in reader.lines().map_while(Result::ok) {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Ok(hit) = parse_hit_line(&line) else { continue };
+            if hit.query == hit.subject {
+                let best = self_scores.entry(hit.query).or_insert(0.0);
+                if hit.bitscore > *best {
+                    *best = hit.bitscore;
+                }
+            }
+        }
+    }
+    self_scores
+}
+
+/// All-vs-all search output read by the orthology methods. When filters are set, each hit line
+/// is checked as it is read, so no filtered copy of the search output is written.
+pub struct SearchHits {
+    pub dir: PathBuf,
+    filter: Option<(HitFilter, HashMap<String, f64>)>, // filters and best self-hit bitscores
+}
+
+impl SearchHits {
+    /// Search output read without filters
+    pub fn unfiltered(dir: &Path) -> Self {
+        SearchHits { dir: dir.to_path_buf(), filter: None }
+    }
+
+    /// False if a filter removes this hit line (lines that do not parse are left to the reader)
+    pub fn keeps(&self, line: &str) -> bool {
+        let Some((filter, self_scores)) = &self.filter else {
+            return true;
+        };
+        match parse_hit_line(line) {
+            Ok(hit) => filter.rejects(&hit, self_scores).is_none(),
+            Err(_) => true,
+        }
+    }
+}
+
+/// Set up the hit filters for every {q}_vs_{s}.out in `blast_out_dir`.
+///
+/// The number of hits removed by each filter (first failing filter only) is counted with `threads`
+/// threads and written to `filter_summary.tsv` alongside the search output. Returns the search output
+/// with the filters attached, for the orthology methods to read.
+pub fn filter_all_hits(blast_out_dir: &Path, filter: &HitFilter, threads: usize, logger: &Logger) -> SearchHits {
+
+    logger.information(&format!("filter_all_hits: min_pident={} min_qcov={} min_scov={} min_bitscore_ratio={}",
+        filter.min_pident, filter.min_qcov, filter.min_scov, filter.min_bitscore_ratio));
+
+    let _ = fs::remove_dir_all(blast_out_dir.join("filtered")); // filtered copies written by earlier versions
+
+    let pairs = list_pair_files(blast_out_dir, logger);
+    if pairs.is_empty() {
+        logger.error(&format!("filter_all_hits: no search output ({{q}}_vs_{{s}}.out) found in {}", blast_out_dir.display()));
+        std::process::exit(1);
+    }
+
+    let self_scores = if filter.min_bitscore_ratio > 0.0 {
+        read_self_scores(&pairs, logger)
+    } else {
+        HashMap::new()
+    };
+
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads.max(1)).build().unwrap_or_else(|e| {
+        logger.error(&format!("filter_all_hits: failed to start {} threads: {}", threads, e));
+        std::process::exit(1);
+    });
+    let results: Vec<Result<FilterCounts, String>> = pool.install(|| {
+        pairs
+            .par_iter()
+            .map(|(_, _, path)| count_pair_file(path, filter, &self_scores, logger))
+            .collect()
+    });
+
+    // Summary
+    let summary_path = blast_out_dir.join("filter_summary.tsv");
+    let mut writer = open_bufwrite(&summary_path, logger, "filter_all_hits");
+    let mut totals = FilterCounts::default();
+
+    let write_row = |writer: &mut std::io::BufWriter<fs::File>, name: &str, c: &FilterCounts| {
+        if let Err(e) = writeln!(writer, "{}\t{}\t{}\t{}\t{}\t{}\t{}", name, c.total, c.pident, c.qcov, c.scov, c.bitscore_ratio, c.kept) {
+            logger.error(&format!("filter_all_hits: write error {}: {}", summary_path.display(), e));
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = writeln!(writer, "pair\ttotal\tremoved_pident\tremoved_qcov\tremoved_scov\tremoved_bitscore_ratio\tkept") {
+        logger.error(&format!("filter_all_hits: write error {}: {}", summary_path.display(), e));
+        std::process::exit(1);
+    }
+
+    for ((q, s, _), result) in pairs.iter().zip(results) {
+        let counts = result.unwrap_or_else(|e| {
+            logger.error(&format!("filter_all_hits: {}", e));
+            std::process::exit(1);
+        });
+
+        if !counts.has_lengths && (filter.min_qcov > 0.0 || filter.min_scov > 0.0) {
+            logger.warning(&format!("filter_all_hits: {} vs {} has no qlen/slen columns, coverage filters not applied (re-run align-all)", q, s));
+        }
+
+        write_row(&mut writer, &format!("{}_vs_{}", q, s), &counts);
+        totals.add(&counts);
+    }
+    write_row(&mut writer, "total", &totals);
+    if let Err(e) = writer.flush() {
+        logger.error(&format!("filter_all_hits: write error {}: {}", summary_path.display(), e));
+        std::process::exit(1);
+    }
+
+    logger.information(&format!(
+        "filter_all_hits: kept {} of {} hits (removed: pident {}, qcov {}, scov {}, bitscore_ratio {}). Summary: {}",
+        totals.kept, totals.total, totals.pident, totals.qcov, totals.scov, totals.bitscore_ratio, summary_path.display()));
+
+    SearchHits { dir: blast_out_dir.to_path_buf(), filter: Some((filter.clone(), self_scores)) }
+}
+
+/// Count the hits of one search output removed by each filter
+fn count_pair_file(path: &Path, filter: &HitFilter, self_scores: &HashMap<String, f64>, logger: &Logger) -> Result<FilterCounts, String> {
+    let reader = search_io::open_search_reader(path, logger, "count_pair_file");
+    let mut counts = FilterCounts { has_lengths: true, ..Default::default() };
+
+    for (lnum, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("read {}: {}", path.display(), e))?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let hit = parse_hit_line(&line).map_err(|e| format!("{}:{}: {}", path.display(), lnum + 1, e))?;
+        counts.total += 1;
+        if hit.qlen.is_none() {
+            counts.has_lengths = false;
+        }
+
+        match filter.rejects(&hit, self_scores) {
+            Some(FilterReason::Pident) => counts.pident += 1,
+            Some(FilterReason::QueryCoverage) => counts.qcov += 1,
+            Some(FilterReason::SubjectCoverage) => counts.scov += 1,
+            Some(FilterReason::BitscoreRatio) => counts.bitscore_ratio += 1,
+            None => counts.kept += 1,
+        }
+    }
+
+    Ok(counts)
+}
@@ -0,0 +1,301 @@
+use crate::logger::Logger;
+use crate::RepoEntry;
+use crate::blast_rbh;
+use crate::import_search::IdIndex;
+use crate::read_repo;
+use crate::util::{mkdir, open_bufread};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::BufRead;
+use std::path::Path;
+
+/// Written next to the imported clusters, for the methods page
+pub const PROVENANCE_FILE: &str = "imported_orthogroups.json";
+
+/// Imported clusters, in the same format as the RBH .OrthoClusters file
+pub const CLUSTERS_FILE: &str = "imported_orthogroups.OrthoClusters";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OrthogroupProvenance {
+    pub format: String,
+    pub tool: String,
+    pub path: String,
+    pub groups_imported: usize,
+    pub genes_imported: usize,
+    pub ids_remapped: usize,
+    pub ids_skipped: usize,
+}
+
+/// Orthogroup file formats accepted by import-orthogroups (--orthogroups_format)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrthogroupFormat {
+    OrthoFinder,   // Orthogroups.tsv (or N0.tsv): group, then one column per genome, comma separated
+    ProteinOrtho,  // .proteinortho.tsv: species, genes, alg.-conn., then one column per genome, '*' if absent
+    Broccoli,      // orthologous_groups.txt: group, space separated genes
+    SonicParanoid, // ortholog_groups.tsv: 4 summary columns, then genome and score columns
+    TwoColumn,     // orthogroup<TAB>gene, one gene per line
+}
+
+impl OrthogroupFormat {
+    pub fn from_arg(value: &str) -> Option<Self> {
+        match value {
+            "orthofinder" => Some(OrthogroupFormat::OrthoFinder),
+            "proteinortho" => Some(OrthogroupFormat::ProteinOrtho),
+            "broccoli" => Some(OrthogroupFormat::Broccoli),
+            "sonicparanoid" => Some(OrthogroupFormat::SonicParanoid),
+            "two-column" => Some(OrthogroupFormat::TwoColumn),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrthogroupFormat::OrthoFinder => "orthofinder",
+            OrthogroupFormat::ProteinOrtho => "proteinortho",
+            OrthogroupFormat::Broccoli => "broccoli",
+            OrthogroupFormat::SonicParanoid => "sonicparanoid",
+            OrthogroupFormat::TwoColumn => "two-column",
+        }
+    }
+
+    /// Tool that produced the orthogroups (for the methods page and citations)
+    pub fn tool_name(&self) -> &'static str {
+        match self {
+            OrthogroupFormat::OrthoFinder => "OrthoFinder",
+            OrthogroupFormat::ProteinOrtho => "Proteinortho",
+            OrthogroupFormat::Broccoli => "Broccoli",
+            OrthogroupFormat::SonicParanoid => "SonicParanoid",
+            OrthogroupFormat::TwoColumn => "external",
+        }
+    }
+
+    /// Guess the format from the header line
+    fn detect(first_line: &str) -> Option<Self> {
+        let cols: Vec<&str> = first_line.split('\t').collect();
+        if first_line.starts_with("# Species") {
+            Some(OrthogroupFormat::ProteinOrtho)
+        } else if first_line.starts_with("#OG_name") {
+            Some(OrthogroupFormat::Broccoli)
+        } else if first_line.starts_with("group_id\tgroup_size") {
+            Some(OrthogroupFormat::SonicParanoid)
+        } else if cols[0] == "Orthogroup" || cols[0] == "HOG" {
+            Some(OrthogroupFormat::OrthoFinder)
+        } else if cols.len() == 2 {
+            Some(OrthogroupFormat::TwoColumn)
+        } else {
+            None
+        }
+    }
+}
+
+/// Provenance of imported orthogroups in `out_dir`, if any
+pub fn read_orthogroup_provenance(out_dir: &Path) -> Option<OrthogroupProvenance> {
+    let text = fs::read_to_string(out_dir.join(PROVENANCE_FILE)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Genome named by a column header: the header itself, or without a FASTA extension (e.g. "CNB2.faa")
+fn header_genome(header: &str, genomes: &HashSet<String>) -> Option<String> {
+    let header = header.trim();
+    if genomes.contains(header) {
+        return Some(header.to_string());
+    }
+    let (stem, _) = header.rsplit_once('.')?;
+    genomes.contains(stem).then(|| stem.to_string())
+}
+
+/// One orthogroup: (gene ID as written by the tool, genome of its column if known)
+type RawGroup = Vec<(String, Option<String>)>;
+
+fn split_cell(cell: &str, genome: &Option<String>, group: &mut RawGroup) {
+    for id in cell.split([',', ' ']) {
+        let id = id.trim();
+        if !id.is_empty() && id != "*" {
+            group.push((id.to_string(), genome.clone()));
+        }
+    }
+}
+
+/// Parse an orthogroup file into groups of raw IDs
+fn parse_groups(path: &Path, format: OrthogroupFormat, genomes: &HashSet<String>, logger: &Logger) -> Vec<RawGroup> {
+    let reader = open_bufread(path, logger, "import_orthogroups");
+    let mut lines = reader.lines().map(|l| {
+        l.unwrap_or_else(|e| {
+            logger.error(&format!("import_orthogroups: failed to read {}: {}", path.display(), e));
+            std::process::exit(1);
+        })
+    });
+
+    let mut groups: Vec<RawGroup> = Vec::new();
+    match format {
+        OrthogroupFormat::OrthoFinder | OrthogroupFormat::ProteinOrtho | OrthogroupFormat::SonicParanoid => {
+            let header = lines.next().unwrap_or_default();
+            let header_cols: Vec<&str> = header.trim_end_matches(['\n', '\r']).split('\t').collect();
+
+            // columns holding genes, with the genome each column belongs to
+            let first = match format {
+                OrthogroupFormat::OrthoFinder if header_cols[0] == "HOG" => 3,
+                OrthogroupFormat::OrthoFinder => 1,
+                OrthogroupFormat::ProteinOrtho => 3,
+                _ => 4,
+            };
+            let gene_cols: Vec<(usize, Option<String>)> = header_cols
+                .iter()
+                .enumerate()
+                .skip(first)
+                .filter(|(_, h)| !h.ends_with("_corr_score"))
+                .map(|(i, h)| (i, header_genome(h, genomes)))
+                .collect();
+            for (i, genome) in &gene_cols {
+                if genome.is_none() {
+                    logger.warning(&format!("import_orthogroups: column '{}' does not match a genome, IDs are matched on their own", header_cols[*i]));
+                }
+            }
+
+            for line in lines {
+                if line.trim().is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let cols: Vec<&str> = line.split('\t').collect();
+                let mut group: RawGroup = Vec::new();
+                for (i, genome) in &gene_cols {
+                    if let Some(cell) = cols.get(*i) {
+                        split_cell(cell, genome, &mut group);
+                    }
+                }
+                groups.push(group);
+            }
+        }
+        OrthogroupFormat::Broccoli => {
+            for line in lines {
+                if line.trim().is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let mut group: RawGroup = Vec::new();
+                if let Some((_, genes)) = line.split_once('\t') {
+                    split_cell(genes, &None, &mut group);
+                }
+                groups.push(group);
+            }
+        }
+        OrthogroupFormat::TwoColumn => {
+            let mut index: HashMap<String, usize> = HashMap::new();
+            for line in lines {
+                if line.trim().is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let Some((og, gene)) = line.split_once('\t') else {
+                    logger.warning(&format!("import_orthogroups: skipping line without a tab: {}", line));
+                    continue;
+                };
+                let i = *index.entry(og.trim().to_string()).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[i].push((gene.trim().to_string(), None));
+            }
+        }
+    }
+    groups
+}
+
+/// Import orthogroups from another tool (`format` None = detect from the header) into
+/// `out_dir`, mapping gene IDs to the genome|gene IDs written by create-repo.
+///
+/// Genes that cannot be mapped are skipped, and genes listed in more than one group are
+/// kept in the first. Groups are written in the RBH .OrthoClusters format.
+pub fn import_orthogroups(input: &Path, format: Option<OrthogroupFormat>, repo: &[RepoEntry], alignment_type: &str, out_dir: &Path, logger: &Logger) {
+
+    logger.information(&format!("import_orthogroups: importing {} into {}", input.display(), out_dir.display()));
+    if !input.is_file() {
+        logger.error(&format!("import_orthogroups: {} is not a file", input.display()));
+        std::process::exit(1);
+    }
+
+    let format = format.unwrap_or_else(|| {
+        let first_line = open_bufread(input, logger, "import_orthogroups").lines().next().and_then(|l| l.ok()).unwrap_or_default();
+        OrthogroupFormat::detect(&first_line).unwrap_or_else(|| {
+            logger.error(&format!("import_orthogroups: cannot detect the format of {}, use --orthogroups_format", input.display()));
+            std::process::exit(1);
+        })
+    });
+    logger.information(&format!("import_orthogroups: format {}", format.as_str()));
+
+    let (index, genome_list) = IdIndex::build(repo, alignment_type, "import_orthogroups", logger);
+    let genomes: HashSet<String> = genome_list.into_iter().collect();
+    let raw_groups = parse_groups(input, format, &genomes, logger);
+
+    let mut clusters: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let (mut n_remapped, mut n_skipped, mut n_repeated) = (0usize, 0usize, 0usize);
+    let mut unmapped_examples: Vec<String> = Vec::new();
+
+    for raw_group in raw_groups {
+        let mut genes: Vec<String> = Vec::new();
+        let mut in_group: HashSet<String> = HashSet::new();
+        let mut group_remapped = 0usize;
+        for (raw, genome) in raw_group {
+            let mapped = match &genome {
+                Some(g) => index.resolve_in_genome(&raw, g),
+                None => index.resolve(&raw),
+            };
+            let Some(id) = mapped else {
+                n_skipped += 1;
+                if unmapped_examples.len() < 5 {
+                    unmapped_examples.push(raw);
+                }
+                continue;
+            };
+            if seen.contains(&id) || !in_group.insert(id.clone()) {
+                n_repeated += 1;
+                continue;
+            }
+            if id != raw {
+                group_remapped += 1;
+            }
+            genes.push(id);
+        }
+        // genes of dropped groups stay free for later groups
+        if genes.len() >= 2 {
+            seen.extend(genes.iter().cloned());
+            n_remapped += group_remapped;
+            genes.sort();
+            clusters.insert(clusters.len() + 1, genes);
+        }
+    }
+
+    if n_skipped > 0 {
+        logger.warning(&format!("import_orthogroups: skipped {} gene IDs that do not match create-repo IDs (e.g. {})", n_skipped, unmapped_examples.join(", ")));
+    }
+    if n_repeated > 0 {
+        logger.warning(&format!("import_orthogroups: {} genes were listed in more than one orthogroup, kept in the first", n_repeated));
+    }
+    if clusters.is_empty() {
+        logger.error(&format!("import_orthogroups: no orthogroups with 2 or more genes could be mapped from {}", input.display()));
+        std::process::exit(1);
+    }
+
+    mkdir(out_dir, logger, "import_orthogroups");
+    let gene_to_struct = read_repo::build_gene_struct_map(repo, logger);
+    blast_rbh::write_final_rbh_clusters(out_dir.join(CLUSTERS_FILE), &clusters, &HashMap::new(), &gene_to_struct, logger);
+
+    let provenance = OrthogroupProvenance {
+        format: format.as_str().to_string(),
+        tool: format.tool_name().to_string(),
+        path: input.display().to_string(),
+        groups_imported: clusters.len(),
+        genes_imported: clusters.values().map(|genes| genes.len()).sum(),
+        ids_remapped: n_remapped,
+        ids_skipped: n_skipped,
+    };
+    let provenance_path = out_dir.join(PROVENANCE_FILE);
+    let json = serde_json::to_string_pretty(&provenance).unwrap_or_default();
+    if let Err(e) = fs::write(&provenance_path, json) {
+        logger.error(&format!("import_orthogroups: failed to write {}: {}", provenance_path.display(), e));
+        std::process::exit(1);
+    }
+
+    logger.information(&format!("import_orthogroups: imported {} orthogroups with {} genes ({} IDs remapped)", clusters.len(), seen.len(), n_remapped));
+}
@@ -0,0 +1,294 @@
+use crate::logger::Logger;
+use crate::RepoEntry;
+use crate::read_fasta;
+use crate::search_io::{self, QueryPairWriters, SearchCompression};
+use crate::util::{mkdir, open_bufread, open_bufwrite};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Written next to imported search output so later steps (and the methods page)
+/// know Synima did not run the all-vs-all itself
+pub const PROVENANCE_FILE: &str = "imported_search.json";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SearchProvenance {
+    pub source: String,
+    pub path: String,
+    pub input_files: usize,
+    pub hits_imported: usize,
+    pub ids_remapped: usize,
+    pub hits_skipped: usize,
+    pub missing_pairs: Vec<String>,
+}
+
+/// Provenance of imported search results in `blast_out_dir`, if any
+pub fn read_search_provenance(blast_out_dir: &Path) -> Option<SearchProvenance> {
+    let text = fs::read_to_string(blast_out_dir.join(PROVENANCE_FILE)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Remove the provenance file (align-all replaces imported results)
+pub fn clear_search_provenance(blast_out_dir: &Path) {
+    let _ = fs::remove_file(blast_out_dir.join(PROVENANCE_FILE));
+}
+
+/// Sequence IDs written by create-repo (genome|gene), with lengths and a lookup by bare gene ID
+pub struct IdIndex {
+    lengths: HashMap<String, u64>,
+    by_gene: HashMap<String, Vec<String>>,
+}
+
+impl IdIndex {
+    pub fn build(repo: &[RepoEntry], alignment_type: &str, context: &str, logger: &Logger) -> (Self, Vec<String>) {
+        let key = format!("{}_parsed", alignment_type);
+        let mut lengths = HashMap::new();
+        let mut by_gene: HashMap<String, Vec<String>> = HashMap::new();
+        let mut genomes = Vec::new();
+
+        for entry in repo {
+            if entry.name == "synima_all" {
+                continue;
+            }
+            let Some(file) = entry.files.get(&key) else {
+                logger.error(&format!("{}: no parsed {} FASTA for genome {} (run create-repo first)", context, alignment_type, entry.name));
+                std::process::exit(1);
+            };
+
+            let genome_lengths = read_fasta::fasta_id_to_seq_length_hash(Path::new(&file.path)).unwrap_or_else(|e| {
+                logger.error(&format!("{}: failed to read {}: {}", context, file.path, e));
+                std::process::exit(1);
+            });
+
+            for (id, len) in genome_lengths {
+                if let Some((_, gene)) = id.split_once('|') {
+                    by_gene.entry(gene.to_string()).or_default().push(id.clone());
+                }
+                lengths.insert(id, len);
+            }
+            genomes.push(entry.name.clone());
+        }
+        genomes.sort();
+
+        (IdIndex { lengths, by_gene }, genomes)
+    }
+
+    /// Map an ID listed under a known genome (e.g. a per-genome column) to genome|gene,
+    /// falling back to `resolve`
+    pub fn resolve_in_genome(&self, id: &str, genome: &str) -> Option<String> {
+        let qualified = format!("{}|{}", genome, id);
+        if self.lengths.contains_key(&qualified) {
+            return Some(qualified);
+        }
+        self.resolve(id)
+    }
+
+    /// Map an imported ID to a genome|gene ID: exact match, then unique bare gene ID
+    /// (whole ID, or the part after the last '|', e.g. for "lcl|gene")
+    pub fn resolve(&self, id: &str) -> Option<String> {
+        if self.lengths.contains_key(id) {
+            return Some(id.to_string());
+        }
+
+        let candidates = [Some(id), id.rsplit_once('|').map(|(_, gene)| gene)];
+        candidates.into_iter().flatten().find_map(|gene| match self.by_gene.get(gene) {
+            Some(ids) if ids.len() == 1 => Some(ids[0].clone()),
+            _ => None,
+        })
+    }
+}
+
+/// Input files: every regular, non-hidden file in a directory, or a single file
+fn list_input_files(input: &Path, logger: &Logger) -> Vec<PathBuf> {
+    if input.is_file() {
+        return vec![input.to_path_buf()];
+    }
+
+    let read_dir = fs::read_dir(input).unwrap_or_else(|e| {
+        logger.error(&format!("import_search: cannot read {}: {}", input.display(), e));
+        std::process::exit(1);
+    });
+
+    let mut files: Vec<PathBuf> = read_dir
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && !p.file_name().and_then(|f| f.to_str()).unwrap_or(".").starts_with('.'))
+        .collect();
+    files.sort();
+
+    if files.is_empty() {
+        logger.error(&format!("import_search: no files found in {}", input.display()));
+        std::process::exit(1);
+    }
+    files
+}
+
+/// Import precomputed all-vs-all results (tabular BLAST/m8, 12 or more columns) into
+/// `blast_out_dir` as {q}_vs_{s}.out files (compressed per --compress_search), in place of the align-all step.
+///
+/// Query/subject IDs are checked against the genome|gene IDs written by create-repo and
+/// remapped from bare gene IDs where unambiguous. Hits with IDs that cannot be mapped are
+/// skipped. Only the 12 standard columns are kept (any extra columns depend on the outfmt
+/// used), and qlen/slen are always added from the parsed FASTA.
+///
+/// Hits are first spooled to one file per query genome, then written out one query genome
+/// at a time, so only the outputs of one query genome are open at once.
+pub fn import_search_results(input: &Path, repo: &[RepoEntry], alignment_type: &str, compression: SearchCompression, blast_out_dir: &Path, logger: &Logger) {
+
+    logger.information(&format!("import_search_results: importing {} into {}", input.display(), blast_out_dir.display()));
+
+    let (index, genomes) = IdIndex::build(repo, alignment_type, "import_search", logger);
+    let input_files = list_input_files(input, logger);
+
+    // Remove results from an earlier align-all or import
+    mkdir(blast_out_dir, logger, "import_search_results");
+    if let Ok(entries) = fs::read_dir(blast_out_dir) {
+        for path in entries.flatten().map(|e| e.path()) {
+            let name = path.file_name().and_then(|f| f.to_str()).unwrap_or("");
+            if path.is_file() && (search_io::parse_pair_file_name(name).is_some() || name == "failed_searches.tsv" || name == "filter_summary.tsv") {
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+    let _ = fs::remove_dir_all(blast_out_dir.join("filtered"));
+
+    let spool_path = |q: &str| blast_out_dir.join(format!(".{}.import_spool", q));
+    let mut spools: BTreeMap<String, BufWriter<File>> = BTreeMap::new();
+    let mut pairs_written: HashSet<(String, String)> = HashSet::new();
+    let mut resolved: HashMap<String, Option<String>> = HashMap::new();
+    let mut n_hits = 0usize;
+    let mut n_remapped = 0usize;
+    let mut n_skipped = 0usize;
+    let mut unmapped_examples: Vec<String> = Vec::new();
+
+    for file in &input_files {
+        // .gz and .zst input is decompressed
+        let reader = search_io::open_search_reader(file, logger, "import_search_results");
+
+        for (lnum, line) in reader.lines().enumerate() {
+            let line = line.unwrap_or_else(|e| {
+                logger.error(&format!("import_search_results: failed to read {}: {}", file.display(), e));
+                std::process::exit(1);
+            });
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 12 {
+                logger.error(&format!("import_search_results: {}:{} has {} columns, expected tabular BLAST output (at least the 12 standard columns)", file.display(), lnum + 1, fields.len()));
+                std::process::exit(1);
+            }
+            // keep the 12 standard columns (qlen/slen are added below)
+            fields.truncate(12);
+
+            let mut ids: [String; 2] = Default::default();
+            let mut ok = true;
+            for (k, raw) in fields[..2].iter().enumerate() {
+                let mapped = resolved.entry(raw.to_string()).or_insert_with(|| {
+                    let id = index.resolve(raw);
+                    // count each remapped ID once
+                    if id.as_deref().is_some_and(|id| id != *raw) {
+                        n_remapped += 1;
+                    }
+                    id
+                });
+                match mapped {
+                    Some(id) => ids[k] = id.clone(),
+                    None => {
+                        ok = false;
+                        if unmapped_examples.len() < 5 && !unmapped_examples.iter().any(|e| e == raw) {
+                            unmapped_examples.push(raw.to_string());
+                        }
+                    }
+                }
+            }
+            if !ok {
+                n_skipped += 1;
+                continue;
+            }
+
+            let (q_genome, _) = ids[0].split_once('|').unwrap_or((&ids[0], ""));
+            let (s_genome, _) = ids[1].split_once('|').unwrap_or((&ids[1], ""));
+            pairs_written.insert((q_genome.to_string(), s_genome.to_string()));
+
+            let spool = spools.entry(q_genome.to_string()).or_insert_with(|| {
+                open_bufwrite(&spool_path(q_genome), logger, "import_search_results")
+            });
+
+            let rest = fields[2..].join("\t");
+            if let Err(e) = writeln!(spool, "{}\t{}\t{}\t{}\t{}", ids[0], ids[1], rest, index.lengths[&ids[0]], index.lengths[&ids[1]]) {
+                logger.error(&format!("import_search_results: write error for {}: {}", spool_path(q_genome).display(), e));
+                std::process::exit(1);
+            }
+            n_hits += 1;
+        }
+    }
+
+    // Write each query genome's hits to its {q}_vs_{s} files
+    for (q, mut spool) in spools {
+        if let Err(e) = spool.flush() {
+            logger.error(&format!("import_search_results: flush error for {}: {}", spool_path(&q).display(), e));
+            std::process::exit(1);
+        }
+        drop(spool);
+
+        let mut writers = QueryPairWriters::open(blast_out_dir, &q, &genomes, compression, logger, "import_search_results");
+        for line in open_bufread(&spool_path(&q), logger, "import_search_results").lines() {
+            let line = line.unwrap_or_else(|e| {
+                logger.error(&format!("import_search_results: failed to read {}: {}", spool_path(&q).display(), e));
+                std::process::exit(1);
+            });
+            let s_genome = line.split('\t').nth(1).and_then(|id| id.split_once('|')).map(|(g, _)| g).unwrap_or("");
+            writers.write_line(s_genome, &line, logger, "import_search_results");
+        }
+        writers.finish(logger, "import_search_results");
+        let _ = fs::remove_file(spool_path(&q));
+    }
+
+    if n_skipped > 0 {
+        logger.warning(&format!("import_search_results: skipped {} hits with IDs not found in create-repo output (e.g. {})", n_skipped, unmapped_examples.join(", ")));
+    }
+    if n_hits == 0 {
+        logger.error(&format!("import_search_results: no hits could be matched to genome|gene IDs from create-repo in {}", input.display()));
+        std::process::exit(1);
+    }
+    if n_remapped > 0 {
+        logger.information(&format!("import_search_results: remapped {} IDs to genome|gene IDs", n_remapped));
+    }
+
+    // Report genome pairs without any hits
+    let mut missing_pairs = Vec::new();
+    for q in &genomes {
+        for s in &genomes {
+            if !pairs_written.contains(&(q.clone(), s.clone())) {
+                missing_pairs.push(format!("{}_vs_{}", q, s));
+            }
+        }
+    }
+    if !missing_pairs.is_empty() {
+        logger.warning(&format!("import_search_results: {} genome pair(s) have no imported hits: {}", missing_pairs.len(), missing_pairs.join(", ")));
+    }
+
+    // Provenance
+    let provenance = SearchProvenance {
+        source: "import-search".to_string(),
+        path: fs::canonicalize(input).unwrap_or_else(|_| input.to_path_buf()).display().to_string(),
+        input_files: input_files.len(),
+        hits_imported: n_hits,
+        ids_remapped: n_remapped,
+        hits_skipped: n_skipped,
+        missing_pairs,
+    };
+    let provenance_path = blast_out_dir.join(PROVENANCE_FILE);
+    let json = serde_json::to_string_pretty(&provenance).unwrap_or_default();
+    if let Err(e) = fs::write(&provenance_path, json) {
+        logger.error(&format!("import_search_results: failed to write {}: {}", provenance_path.display(), e));
+        std::process::exit(1);
+    }
+
+    logger.information(&format!("import_search_results: imported {} hits into {} genome pair files", n_hits, pairs_written.len()));
+}
@@ -5,7 +5,6 @@ use std::fs;
 use std::process::Command;
 use std::process::Stdio;
 use std::path::PathBuf;
-use rayon::prelude::*;
 
 mod args;
 mod logger;
@@ -13,17 +12,34 @@ mod util;
 mod read_repo;
 mod read_fasta;
 mod read_gff;
+mod read_gene_table;
 mod write_fasta;
 mod write_gff;
 mod read_fasta_and_gff;
+mod aligner;
 mod blast;
+mod hits;
+mod import_search;
+mod import_orthogroups;
+mod scheduler;
+mod search_io;
 mod external_tools;
 mod parse_dna_and_peptide;
 mod omcl;
+mod mcl;
+mod msa;
 mod blast_rbh;
+mod blast_mcl;
+mod blast_synteny;
+mod clustering;
+mod clades;
 mod orthofinder;
 mod ortholog_summary;
 mod ortholog_summary_plot;
+mod pangenome;
+mod plot;
+mod presence_absence;
+mod query;
 mod tree;
 mod dagchainer;
 mod synima;
@@ -32,7 +48,7 @@ mod write_repo_from_ncbi;
 use args::{Args, SynimaStep}; //
 use logger::Logger;
 use read_repo::{RepoEntry};
-use crate::ortholog_summary::OrthologySource;
+use crate::ortholog_summary::{OrthologyDirs, OrthologySource};
 use crate::util::mkdir;
 use crate::synima::OrthoParams;
 use crate::synima::{MethodsData};
@@ -42,12 +58,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut args = Args::parse();
     let logger = Logger;
 
+    // Subcommands on the results of an earlier run
+    if let Some(args::Command::Query(query_args)) = &args.command {
+        query::run_query(query_args, &logger);
+        return Ok(());
+    }
+
     // Validate steps
     args::validate_step_sequence(&args.synima_step, &logger);
 
     // Validate aligner vs alignment_type compatibility
     args::validate_alignment_compatibility(&args, &logger);
 
+    // Validate core/soft-core/shell/cloud percentages
+    args::validate_class_thresholds(&args, &logger);
+
+    // Validate RBH best-hit tie tolerance and MCL inflation
+    args::validate_orthology_parameters(&args, &logger);
+
     // Set input subdirs
     let exe = std::env::current_exe()?;
     let exe_dir = exe.parent().unwrap();
@@ -97,12 +125,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let blast_out_dir = main_output_dir.join("synima_step2-align-all");
     let rbh_out_dir = main_output_dir.join("synima_step3-rbh");
     let omcl_out_dir = main_output_dir.join("synima_step3-orthomcl");
+    let mcl_out_dir = main_output_dir.join("synima_step3-mcl");
+    let synteny_out_dir = main_output_dir.join("synima_step3-synteny");
     let orthofinder_out_dir = main_output_dir.join("synima_step3-orthofinder");
+    let imported_out_dir = main_output_dir.join("synima_step3-import-orthogroups");
     let gene_clusters_out_dir = main_output_dir.join("synima_step4-ortholog-summary");
     let tree_out_dir = main_output_dir.join("synima_step5-tree");
     let dagchainer_out_dir = main_output_dir.join("synima_step6-dagchainer");
     let synima_out_dir = main_output_dir.join("synima_step7-synima");
     mkdir(&main_output_dir, &logger, "main");
+    let orthology_dirs = OrthologyDirs {
+        orthofinder: orthofinder_out_dir.clone(),
+        omcl: omcl_out_dir.clone(),
+        rbh: rbh_out_dir.clone(),
+        mcl: mcl_out_dir.clone(),
+        synteny: synteny_out_dir.clone(),
+        imported: imported_out_dir.clone(),
+    };
 
     // Input/Output filenames
     let combined_fasta_filename = format!("{}.all.{}", repo_basename, &args.alignment_type);
@@ -161,13 +200,45 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         logger.information("──────────────────────────");
 
         // Create BLAST databases (diamond, makeblastdb or formatdb) (tools.db_builder and tools.searcher)
-        let tools = blast::resolve_aligner_tools(&args.aligner, &args.alignment_type,  &bin_dir, &logger);
-        blast::create_all_dbs(&repo, &args.alignment_type, tools.db_builder, &blast_out_dir, &logger);
+        // Results computed here replace any imported ones
+        import_search::clear_search_provenance(&blast_out_dir);
+
+        let aligner = aligner::get_aligner_or_exit(&args.aligner, &logger);
+        let tools = aligner::resolve_aligner_tools(aligner.as_ref(), &args.alignment_type, &bin_dir, &logger);
+        if args.combined_db == "off" {
+            blast::create_all_dbs(&repo, &args.alignment_type, aligner.as_ref(), &tools, &blast_out_dir, &logger);
+
+            // Run all-vs-all BLAST
+            blast::run_all_vs_all(&repo, aligner.as_ref(), &tools, &args, &blast_out_dir, &logger);
+        } else {
+            // One combined database, hits split back into {q}_vs_{s}.out
+            blast::run_combined_db_search(&repo, aligner.as_ref(), &tools, &args, &blast_out_dir, &logger);
+        }
+    }
+
+    if args.synima_step.contains(&SynimaStep::ImportSearch) {
+        logger.information("──────────────────────────────");
+        logger.information("Running Step 2: import-search");
+        logger.information("──────────────────────────────");
 
-        // Run all-vs-all BLAST
-        blast::run_all_vs_all(&repo, &tools.searcher, &args, &blast_out_dir, &logger);
+        let Some(import_path) = &args.import_search else {
+            logger.error("The import-search step requires --import_search <directory or file>.");
+            std::process::exit(1);
+        };
+        let compression = search_io::SearchCompression::from_arg(&args.compress_search);
+        import_search::import_search_results(Path::new(import_path), &repo, &args.alignment_type, compression, &blast_out_dir, &logger);
     }
 
+    // Hit filters (identity, coverage, bitscore ratio) are set up once and applied by all orthology methods as they read the hits
+    // (imported orthogroups do not use the all-vs-all hits)
+    let hit_filter = hits::HitFilter::from_args(&args);
+    let uses_hits = preferred_method.is_some_and(|m| !matches!(m, ortholog_summary::OrthologyMethod::Imported));
+    let search_hits = if uses_hits && hit_filter.is_active() {
+        hits::filter_all_hits(&blast_out_dir, &hit_filter, args.threads, &logger)
+    } else {
+        hits::SearchHits::unfiltered(&blast_out_dir)
+    };
+
     if args.synima_step.contains(&SynimaStep::BlastToOrthomcl) {
         logger.information("─────────────────────────────────");
         logger.information("Running Step 3: blast-to-orthomcl");
@@ -175,26 +246,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // make output directory
         mkdir(&omcl_out_dir, &logger, "main (blast-to-orthomcl)");
+        let _ = fs::remove_file(omcl_out_dir.join("all_vs_all.out")); // concatenated copy left by earlier versions
 
         // output files
-        let all_vs_all_path = omcl_out_dir.join("all_vs_all.out");
         let code_out_path = omcl_out_dir.join("genome_codes.tsv");
         let blast_m8_output_path = omcl_out_dir.join("all_vs_all.gcoded.m8");
         let omcl_prefix = omcl_out_dir.join("omcl_in"); // will create omcl_in.bpo and omcl_in.gg
         let omcl_log_path = omcl_out_dir.join("omcl.log");
 
-        // Concatenate BLAST results
-        blast::concatenate_unique_blast_pairs(&blast_out_dir, &all_vs_all_path, &logger);
-
         // Assign genome codes to genes for omcl
         let genome_set = omcl::parse_genome_map_from_gff(&combined_gff_path, &logger)?;
         let genome_to_code = omcl::assign_genome_codes(&genome_set, &code_out_path, &logger)?;
-        omcl::write_gcoded_m8_and_sort(&genome_to_code, &all_vs_all_path, &blast_m8_output_path, &logger);
+        omcl::write_gcoded_m8_and_sort(&genome_to_code, &search_hits, &blast_m8_output_path, &logger);
         let (bpo_path, gg_path) = omcl::convert_m8_to_orthomcl_format(&blast_m8_output_path, &omcl_prefix, &genome_to_code, &logger)?;
 
         // run OrthoMCL
-        let orthomcl_script = bin_dir.join("../OrthoMCL.pl");
-        omcl::run_orthomcl_clustering(&orthomcl_script, &bpo_path, &gg_path, &omcl_log_path, &logger)?;
+        let omcl_params = omcl::OmclParams::from_args(&args);
+        omcl::run_orthomcl_clustering(&bpo_path, &gg_path, &omcl_out_dir.join("all_orthomcl.out"), &omcl_log_path, &omcl_params, &logger)?;
     }
  
     if args.synima_step.contains(&SynimaStep::BlastToRbh) {
@@ -202,37 +270,77 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         logger.information("Running Step 3: blast-to-rbh");
         logger.information("────────────────────────────");
 
-        // get slclust
-        let slclust_path = external_tools::find_executable("slclust", &bin_dir, &logger);
-
         // make output directory
         mkdir(&rbh_out_dir, &logger, "main (blast-to-rbh)");
+        let _ = fs::remove_file(rbh_out_dir.join("all_vs_all.out")); // concatenated copy left by earlier versions
 
-        // Concatenate BLAST results
-        let all_vs_all_path = rbh_out_dir.join("all_vs_all.out");
-        blast::concatenate_unique_blast_pairs(&blast_out_dir, &all_vs_all_path, &logger);
-
-        // Save just the first 2 columns
-        let rbh_pairs_path = blast_rbh::write_blast_pairs(&all_vs_all_path, &logger)?;
+        // Strict reciprocal best hits per genome pair, with the top RBH score per gene
+        let rbh_params = blast_rbh::RbhParams::from_args(&args);
+        let (rbh_pairs, gene_to_top_ortho_blast_score) = blast_rbh::write_rbh_pairs(&search_hits, &rbh_out_dir.join("all_vs_all.out.pairs"), &rbh_params, &logger)?;
 
-        // Run slclust
-        let slclust_output = blast_rbh::run_slclust_on_pairs(&slclust_path, &rbh_pairs_path, &logger)?;
-
-        // Parse clusters and map genes to their cluster IDs
-        let cluster_map = blast_rbh::parse_clusters(&slclust_output.as_ref(), &logger)?;
+        // Single-linkage clustering of the RBH pairs, and map genes to their cluster IDs
+        let linkage = clustering::LinkageParams { min_edge_weight: args.rbh_min_link_bitscore, min_jaccard: args.rbh_min_jaccard };
+        let cluster_map = clustering::single_linkage(&rbh_pairs, &linkage);
+        let slclust_output = rbh_out_dir.join("all_vs_all.out.pairs.slclust");
+        clustering::write_clusters(&slclust_output, &cluster_map, &logger);
         let gene_to_cluster = blast_rbh::map_gene_to_cluster_id(&cluster_map);
 
-        // Get top BLAST score per orthologous gene
-        let gene_to_top_ortho_blast_score = blast_rbh::get_top_ortho_blast_score(&repo, &blast_out_dir, &logger)?;
-
         // Get Inparalogs (paralogs within a genome)
-        let cluster_id_to_in_paralogs = blast_rbh::get_inparalogs(&repo, &blast_out_dir, &gene_to_top_ortho_blast_score, &gene_to_cluster, &logger)?;
+        let cluster_id_to_in_paralogs = blast_rbh::get_inparalogs(&repo, &search_hits, &gene_to_top_ortho_blast_score, &gene_to_cluster, &logger)?;
     
         let gene_to_struct = read_repo::build_gene_struct_map(&repo, &logger);
         let out_file = slclust_output.with_file_name(format!("{}{}", slclust_output.file_name().unwrap().to_string_lossy(), ".OrthoClusters"));
         blast_rbh::write_final_rbh_clusters(&out_file, &cluster_map, &cluster_id_to_in_paralogs, &gene_to_struct, &logger);
     }
 
+    if args.synima_step.contains(&SynimaStep::BlastToMcl) {
+        logger.information("────────────────────────────");
+        logger.information("Running Step 3: blast-to-mcl");
+        logger.information("────────────────────────────");
+
+        // make output directory
+        mkdir(&mcl_out_dir, &logger, "main (blast-to-mcl)");
+
+        // Weighted gene graph (all hits or reciprocal best hits), saved in mcl's ABC format
+        let mcl_graph = blast_mcl::MclGraph::from_args(&args);
+        let edges = blast_mcl::build_graph(mcl_graph, &search_hits, &mcl_out_dir.join("rbh_pairs.tsv"), &args, &logger)?;
+        blast_mcl::write_abc_graph(&mcl_out_dir.join("all_vs_all.mcl.abc"), &edges, &logger);
+
+        // Markov clustering
+        let cluster_map = mcl::cluster_graph(&edges, &mcl::MclParams::with_inflation(args.inflation));
+        clustering::write_clusters(&mcl_out_dir.join("all_vs_all.mcl.clusters"), &cluster_map, &logger);
+
+        // Same cluster format as the RBH pipeline (no separate in-paralogs: MCL clusters include them)
+        let gene_to_struct = read_repo::build_gene_struct_map(&repo, &logger);
+        blast_rbh::write_final_rbh_clusters(mcl_out_dir.join("all_vs_all.mcl.OrthoClusters"), &cluster_map, &HashMap::new(), &gene_to_struct, &logger);
+    }
+
+    if args.synima_step.contains(&SynimaStep::BlastToSynteny) {
+        logger.information("────────────────────────────────");
+        logger.information("Running Step 3: blast-to-synteny");
+        logger.information("────────────────────────────────");
+
+        // make output directory
+        mkdir(&synteny_out_dir, &logger, "main (blast-to-synteny)");
+
+        // Homology clusters: MCL on the bitscore graph (recent paralogs end up together)
+        let edges = blast_mcl::build_bitscore_graph(&search_hits, &logger);
+        let homology_clusters = mcl::cluster_graph(&edges, &mcl::MclParams::with_inflation(args.inflation));
+        clustering::write_clusters(&synteny_out_dir.join("all_vs_all.homology.clusters"), &homology_clusters, &logger);
+
+        // Syntenic anchors: collinear chains of hits along the gene order of the parsed GFF
+        let synteny_params = blast_synteny::SyntenyParams::from_args(&args);
+        let positions = blast_synteny::gene_positions(&read_gff::load_parsed_gff(&combined_gff_path, &logger));
+        let candidates = blast_synteny::candidate_anchors(&search_hits, &synteny_params, &logger);
+        let anchors = blast_synteny::find_syntenic_anchors(&candidates, &positions, &synteny_params, &logger);
+        blast_synteny::write_anchors(&synteny_out_dir.join("all_vs_all.synteny.anchors"), &anchors, &logger);
+
+        // Positional orthologs, with syntenic/non-syntenic membership
+        let groups = blast_synteny::split_clusters(&homology_clusters, &anchors, &edges);
+        blast_synteny::write_synteny_clusters(&synteny_out_dir.join("all_vs_all.synteny.OrthoClusters"), &groups, &logger);
+        logger.information(&format!("main (blast-to-synteny): split {} homology clusters into {} positional ortholog groups", homology_clusters.len(), groups.len()));
+    }
+
     if args.synima_step.contains(&SynimaStep::BlastToOrthofinder) {
         logger.information("────────────────────────────────────");
         logger.information("Running Step 3: blast-to-orthofinder");
@@ -245,7 +353,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         mkdir(&orthofinder_out_dir, &logger, "main (blast-to-orthofinder)");
 
         // Prepare Orthofinder input folder
-        if let Err(e) = orthofinder::prepare_orthofinder_blast(&repo, &args.alignment_type, &blast_out_dir, &orthofinder_out_dir, &logger) {
+        if let Err(e) = orthofinder::prepare_orthofinder_blast(&repo, &args.alignment_type, &search_hits, &orthofinder_out_dir, &logger) {
             logger.error(&format!("Error: unable to prepare orthofinder BLAST folder: {}", e));
             std::process::exit(1);
         }
@@ -253,10 +361,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         // Run Orthofinder
         logger.information(&format!("Run orthofinder: {}" , &orthofinder_out_dir.display()));
 
-        let output = Command::new(&orthofinder_path)
-            .arg("-b")
-            .arg(orthofinder_out_dir.join("Blast"))
-            .arg("-og")  // stop after orthogroups
+        let mut orthofinder_cmd = Command::new(&orthofinder_path);
+        orthofinder_cmd.arg("-b").arg(orthofinder_out_dir.join("Blast"));
+        if !args.orthofinder_full {
+            orthofinder_cmd.arg("-og");  // stop after orthogroups
+        }
+        orthofinder::remove_full_results(&orthofinder_out_dir);
+
+        let output = orthofinder_cmd
             // .current_dir(&orthofinder_out_dir)  // optional, if you want cwd there
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -293,6 +405,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Ok(path) => logger.information(&format!("Orthogroups.tsv saved to {}", path.display())),
             Err(e) => logger.information(&format!("Did not find Orthogroups.tsv: {}", e)),
         }
+
+        // hierarchical orthogroups, duplications and species tree of a full run
+        if args.orthofinder_full {
+            match orthofinder::harvest_full_results(&combined, &orthofinder_out_dir) {
+                Ok(paths) => {
+                    for path in paths {
+                        logger.information(&format!("OrthoFinder result saved to {}", path.display()));
+                    }
+                    orthofinder::summarise_duplications(&orthofinder_out_dir, &logger)?;
+                }
+                Err(e) => {
+                    logger.error(&format!("Did not find the full OrthoFinder results: {}", e));
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    if args.synima_step.contains(&SynimaStep::ImportOrthogroups) {
+        logger.information("───────────────────────────────────");
+        logger.information("Running Step 3: import-orthogroups");
+        logger.information("───────────────────────────────────");
+
+        let Some(import_path) = &args.import_orthogroups else {
+            logger.error("The import-orthogroups step requires --import_orthogroups <file>.");
+            std::process::exit(1);
+        };
+        let format = import_orthogroups::OrthogroupFormat::from_arg(&args.orthogroups_format);
+        import_orthogroups::import_orthogroups(Path::new(import_path), format, &repo, &args.alignment_type, &imported_out_dir, &logger);
     }
 
     if args.synima_step.contains(&SynimaStep::OrthologSummary) {
@@ -307,7 +448,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let all_features = read_gff::load_parsed_gff(&combined_gff_path, &logger);
 
         // Detect which ortholog clustering was used:
-        let source = ortholog_summary::detect_orthology_source(preferred_method, &orthofinder_out_dir, &omcl_out_dir, &rbh_out_dir, &logger);
+        let source = ortholog_summary::detect_orthology_source(preferred_method, &orthology_dirs, &logger);
         let method_label = source.method_label();
 
         let clusters_and_unique = match &source {
@@ -320,14 +461,36 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             OrthologySource::Rbh(dir) => {
                 ortholog_summary::from_rbh(dir, &args.alignment_type, &gene_clusters_out_dir, &all_features, &logger)
             }
+            OrthologySource::Mcl(dir) => {
+                ortholog_summary::from_mcl(dir, &args.alignment_type, &gene_clusters_out_dir, &all_features, &logger)
+            }
+            OrthologySource::Synteny(dir) => {
+                ortholog_summary::from_synteny(dir, &args.alignment_type, &gene_clusters_out_dir, &all_features, &logger)
+            }
+            OrthologySource::Imported(dir) => {
+                ortholog_summary::from_imported(dir, &args.alignment_type, &gene_clusters_out_dir, &all_features, &logger)
+            }
         };
 
         // Write cluster dist per genome
         let cluster_dist_path = gene_clusters_out_dir.join(format!("GENE_CLUSTERS_SUMMARIES.{}.{}.cluster_dist_per_genome.txt", &args.alignment_type, method_label));
         ortholog_summary::write_cluster_dist_per_genome(&clusters_and_unique, &cluster_dist_path, &logger);
 
+        // Roary-compatible presence/absence matrix
+        let gene_annotations = presence_absence::read_gene_annotations(&repo, &logger);
+        let matrix_prefix = format!("GENE_CLUSTERS_SUMMARIES.{}.{}", &args.alignment_type, method_label);
+        presence_absence::write_presence_absence(&clusters_and_unique, &gene_clusters_out_dir, &matrix_prefix, &all_features, &gene_annotations, &logger);
+
+        // Orthogroups shared by genomes and groups (repo spec 'group' rows and --clade)
+        let mut genome_groups = clades::groups_from_repo(&repo);
+        genome_groups.extend(clades::groups_from_clades(&args.clade, &tree::tree_path_for_method(&tree_out_dir, &args.alignment_type, method_label), &logger));
+        clades::write_group_reports(&clusters_and_unique, &gene_clusters_out_dir, &matrix_prefix, &genome_groups, args.group_tolerance, &logger);
+
         // barchart of orthologs
-        ortholog_summary_plot::write_cluster_dist_stats_and_plot(&cluster_dist_path, &gene_clusters_out_dir, &logger);
+        ortholog_summary_plot::write_cluster_dist_stats_and_plot(&cluster_dist_path, &gene_clusters_out_dir, &ortholog_summary_plot::ClassThresholds::from_args(&args), &logger);
+
+        // pan/core-genome accumulation curves and Heaps' law fit
+        pangenome::write_pangenome_curves(&cluster_dist_path, &pangenome::PangenomeParams::from_args(&args), &logger);
 
     }
 
@@ -340,7 +503,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         mkdir(&tree_out_dir, &logger, "main (tree)");
 
         // Save clusters
-        let source = ortholog_summary::detect_orthology_source(preferred_method, &orthofinder_out_dir, &omcl_out_dir, &rbh_out_dir, &logger);
+        let source = ortholog_summary::detect_orthology_source(preferred_method, &orthology_dirs, &logger);
         let method_label = source.method_label();
 
         //let cluster_dist_path = gene_clusters_out_dir.join(format!("GENE_CLUSTERS_SUMMARIES.{}.{}.cluster_dist_per_genome.txt", &args.alignment_type, method_label));
@@ -351,34 +514,62 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         let (cluster_to_genes, genomes_parsed) = dagchainer::save_gene_ids_from_ortholog_file(&clusters_and_unique_path, &logger);
 
-        // make MALIGN output directory
-        let malign = PathBuf::from(format!("GENE_CLUSTERS_SUMMARIES.{}.{}.clusters_and_uniques.MALIGN_DIR", args.alignment_type, method_label));
-        let malign_outdir = tree_out_dir.join(malign);
-        mkdir(&malign_outdir, &logger, "main (tree)");
+        // Rooted species tree from a full OrthoFinder run replaces the supermatrix tree
+        let species_tree = orthofinder_out_dir.join("SpeciesTree_rooted.txt");
+        let supermatrix_tree_path = tree::supermatrix_tree_path(&tree_out_dir, &args.alignment_type, method_label);
+        let species_tree_path = tree::species_tree_path(&tree_out_dir, &args.alignment_type, method_label);
+        if matches!(source, OrthologySource::OrthoFinder(_)) && species_tree.is_file() {
+            let _ = fs::remove_file(&supermatrix_tree_path);
+            tree::import_species_tree(&species_tree, &species_tree_path, &genomes_parsed, &logger);
+        } else {
+            let _ = fs::remove_file(&species_tree_path);
+
+            // make MALIGN output directory
+            let malign = PathBuf::from(format!("GENE_CLUSTERS_SUMMARIES.{}.{}.clusters_and_uniques.MALIGN_DIR", args.alignment_type, method_label));
+            let malign_outdir = tree_out_dir.join(malign);
+            mkdir(&malign_outdir, &logger, "main (tree)");
+
+            // Load genes
+            let all_fasta = read_fasta::read_fasta(&combined_fasta_path, &logger);
+            let mut pep_by_id: HashMap<String, String> = HashMap::new();
+            for rec in all_fasta {
+                pep_by_id.insert(rec.id.clone(), rec.seq.clone());
+            }
 
-        // Load genes
-        let all_fasta = read_fasta::read_fasta(&combined_fasta_path, &logger);
-        let mut pep_by_id: HashMap<String, String> = HashMap::new();
-        for rec in all_fasta {
-            pep_by_id.insert(rec.id.clone(), rec.seq.clone());
+            // Write MALIGN cds/pep files
+            let min_genomes = ortholog_summary_plot::ClassThresholds::min_genomes(args.core_threshold, genomes_parsed.len());
+            tree::write_malign_files(&cluster_to_genes, &args.alignment_type, &pep_by_id, &malign_outdir, &genomes_parsed, min_genomes, &logger);
+
+            // Run the multiple aligner (--msa) on all cluster pep files, in parallel
+            let msa = msa::get_msa_or_exit(&args.msa, &logger);
+            let msa_path = external_tools::find_executable(msa.executable(), &bin_dir, &logger);
+            let alignment_suffix = if args.codon_alignment {
+                // Align the translated CDS, then thread the CDS through the protein alignments
+                tree::write_codon_peptides(&malign_outdir, args.genetic_code, &logger);
+                tree::run_msa_on_clusters(&malign_outdir, msa.as_ref(), &msa_path, "pep", &args, &logger);
+                tree::thread_cds_through_alignments(&malign_outdir, &logger);
+                ".cds.codon.mfa".to_string()
+            } else {
+                tree::run_msa_on_clusters(&malign_outdir, msa.as_ref(), &msa_path, &args.alignment_type, &args, &logger);
+                format!(".{}.mfa", &args.alignment_type)
+            };
+
+            // Concatenate into a single fasta and build a tree
+            let concat_out_path = tree_out_dir.join(format!("SC_core_concat.{}.{}.mfa", args.alignment_type, method_label));
+            tree::concatenate_alignments_and_write(&malign_outdir, &genomes_parsed, &alignment_suffix, &concat_out_path, &logger);
+            logger.information(&format!("Concatenated core single-copy alignment written to {}", concat_out_path.display()));
+            let fasttree_path = external_tools::find_executable("fasttree", &bin_dir, &logger);
+            let is_nt = args.alignment_type == "cds";
+            tree::run_fasttree_on_alignment(&fasttree_path, &concat_out_path, is_nt, &logger);
         }
 
-        // Write MALIGN cds/pep files
-        tree::write_malign_files(&cluster_to_genes, &args.alignment_type, &pep_by_id, &malign_outdir, &genomes_parsed, &logger);
-
-        // Run MUSCLE on all cluster pep files, in parallel
-        let muscle_path = external_tools::find_executable("muscle", &bin_dir, &logger);
-        tree::run_muscle_on_clusters(&malign_outdir, &muscle_path, &args, &logger);
-
-        // Concatenate into a single fasta and build a tree
-        let concat_out_path = tree_out_dir.join(format!("SC_core_concat.{}.{}.mfa", args.alignment_type, method_label));
-        let alignment_suffix = format!(".{}.mfa", &args.alignment_type);
-        tree::concatenate_alignments_and_write(&malign_outdir, &genomes_parsed, &alignment_suffix, &concat_out_path, &logger);
-        logger.information(&format!("Concatenated core single-copy alignment written to {}", concat_out_path.display()));
-        let fasttree_path = external_tools::find_executable("fasttree", &bin_dir, &logger);
-        let is_nt = args.alignment_type == "cds";
-        tree::run_fasttree_on_alignment(&fasttree_path, &concat_out_path, is_nt, &logger);
-
+        // Clades of the new tree (--clade) for the group-specific orthogroup report
+        if !args.clade.is_empty() {
+            let mut genome_groups = clades::groups_from_repo(&repo);
+            genome_groups.extend(clades::groups_from_clades(&args.clade, &tree::tree_path_for_method(&tree_out_dir, &args.alignment_type, method_label), &logger));
+            let prefix = format!("GENE_CLUSTERS_SUMMARIES.{}.{}", &args.alignment_type, method_label);
+            clades::write_group_reports(&clusters_and_unique_path, &gene_clusters_out_dir, &prefix, &genome_groups, args.group_tolerance, &logger);
+        }
     }
 
     if args.synima_step.contains(&SynimaStep::Dagchainer) {
@@ -392,7 +583,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         mkdir(&dagchainer_out_subdir, &logger, "dagchainer");
 
         // Save clusters
-        let source = ortholog_summary::detect_orthology_source(preferred_method, &orthofinder_out_dir, &omcl_out_dir, &rbh_out_dir, &logger);
+        let source = ortholog_summary::detect_orthology_source(preferred_method, &orthology_dirs, &logger);
         let method_label = source.method_label();
 
         //let cluster_dist_path = gene_clusters_out_dir.join(format!("GENE_CLUSTERS_SUMMARIES.{}.{}.cluster_dist_per_genome.txt", &args.alignment_type, method_label));
@@ -417,7 +608,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let dagchainer_wrapper = bin_dir.join("../run_DAG_chainer.pl");
         let dagchainer_wrapper2 = bin_dir.join("../dagchainer_to_chain_spans.pl");
 
-        let dagchainer_cmds = dagchainer::write_dagchainer_conf_file(
+        let dagchainer_jobs = dagchainer::write_dagchainer_conf_file(
             &dagchainer_out_subdir,
             &dagchainer_wrapper,
             &genomes_parsed,
@@ -428,19 +619,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             &logger,
         );
 
-        // Run DAGchainer commands in parallel
-        let total_threads = args.threads.max(1);
+        // Run DAGchainer commands in parallel (single threaded each), largest genome pairs first
+        let costs: Vec<u64> = dagchainer_jobs.iter().map(|job| job.n_pairs as u64).collect();
+        let plan = scheduler::plan_threads(args.threads, dagchainer_jobs.len(), 1);
+        scheduler::log_plan("dagchainer", dagchainer_jobs.len(), &plan, &logger);
 
-        logger.information(&format!("dagchainer: running {} DAGchainer jobs in parallel (rayon threads = {})", dagchainer_cmds.len(), total_threads));
-
-        rayon::ThreadPoolBuilder::new()
-            .num_threads(total_threads)
-            .build()
-            .expect("dagchainer: failed to build Rayon thread pool")
-            .install(|| {
-                dagchainer_cmds.par_iter().for_each(|cmd| {
-                    util::run_shell_cmd(cmd, &logger, "dagchainer");
-                });});
+        scheduler::run_largest_first(&dagchainer_jobs, &costs, plan.concurrent_jobs, |job| {
+            util::run_shell_cmd(&job.cmd, &logger, "dagchainer");
+        });
 
         // Concatenate
         dagchainer::concatenate_aligncoords_and_make_spans(&dagchainer_out_subdir, &dagchainer_out_dir, Path::new(repo_spec_file), &dagchainer_wrapper2, &logger);
@@ -457,11 +643,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let index_path = synima_out_dir.join("Synima.html");
 
         // update orthologs
+        let imported_search = import_search::read_search_provenance(&blast_out_dir);
         let params = OrthoParams {
-            aligner: args.aligner.clone(),
+            aligner: if imported_search.is_some() { "imported".to_string() } else { args.aligner.clone() },
             max_target_seqs: args.max_target_seqs,
             diamond_sensitivity: args.diamond_sensitivity.clone(),
+            mmseqs_sensitivity: args.mmseqs_sensitivity,
             evalue: args.evalue.clone(),
+            rbh_tie_tolerance: args.rbh_tie_tolerance,
+            rbh_min_coverage: args.rbh_min_coverage,
+            omcl_evalue_cutoff: args.omcl_evalue_cutoff,
+            inflation: args.inflation,
+            mcl_graph: args.mcl_graph.clone(),
+            synteny_max_gap: args.synteny_max_gap,
+            synteny_min_anchors: args.synteny_min_anchors,
             dagchainer_chains: args.dagchainer_chains,
             genetic_code: args.genetic_code,
         };
@@ -473,15 +668,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
 
         // update methods
-        let tools = external_tools::build_tools_vector(&args, preferred_method, &orthofinder_out_dir, &omcl_out_dir, &rbh_out_dir, &logger);
-        let citations = external_tools::build_citations_vector(&args, preferred_method);
+        let source = ortholog_summary::detect_orthology_source(preferred_method, &orthology_dirs, &logger);
+        let imported_orthogroups = match &source {
+            OrthologySource::Imported(dir) => import_orthogroups::read_orthogroup_provenance(dir),
+            _ => None,
+        };
+        let tools = external_tools::build_tools_vector(&args, &bin_dir, &source, imported_search.as_ref(), imported_orthogroups.as_ref());
+        let citations = external_tools::build_citations_vector(&args, preferred_method, imported_search.is_some(), imported_orthogroups.as_ref());
         let json = serde_json::to_string(&MethodsData { tools, citations })?;
         synima::inject_json_into_html(&index_path, "data-methods", &json)?;
 
         // Determine the genome order from the tree
-        let source = ortholog_summary::detect_orthology_source(preferred_method, &orthofinder_out_dir, &omcl_out_dir, &rbh_out_dir, &logger);
         let method_label = source.method_label();
-        let tree_file = tree_out_dir.join(format!("SC_core_concat.{}.{}.mfa.tree", args.alignment_type, method_label));
+        let tree_file = tree::tree_path_for_method(&tree_out_dir, &args.alignment_type, method_label);
         let newick = fs::read_to_string(tree_file)?;
         let leaf_order = tree::extract_leaf_order_from_newick(&newick);
 
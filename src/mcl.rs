@@ -0,0 +1,212 @@
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+
+/// Markov Cluster (MCL) settings
+#[derive(Debug, Clone)]
+pub struct MclParams {
+    pub inflation: f64,
+    pub prune_threshold: f64,   // entries below this are removed after inflation
+    pub max_per_column: usize,  // at most this many entries are kept per column
+    pub max_iterations: usize,
+    pub chaos_limit: f64,       // converged once every column is (nearly) homogeneous
+}
+
+impl MclParams {
+    pub fn with_inflation(inflation: f64) -> Self {
+        MclParams {
+            inflation,
+            prune_threshold: 1e-5,
+            max_per_column: 1000,
+            max_iterations: 100,
+            chaos_limit: 1e-4,
+        }
+    }
+}
+
+/// Sparse column: (row, value), sorted by row
+type Column = Vec<(usize, f64)>;
+
+fn normalise(col: &mut Column) {
+    let sum: f64 = col.iter().map(|(_, v)| v).sum();
+    if sum > 0.0 {
+        for (_, v) in col.iter_mut() {
+            *v /= sum;
+        }
+    }
+}
+
+/// Column j of M x M
+fn expand_column(matrix: &[Column], j: usize, acc: &mut HashMap<usize, f64>) -> Column {
+    acc.clear();
+    for &(k, m_kj) in &matrix[j] {
+        for &(i, m_ik) in &matrix[k] {
+            *acc.entry(i).or_insert(0.0) += m_ik * m_kj;
+        }
+    }
+    let mut col: Column = acc.iter().map(|(&i, &v)| (i, v)).collect();
+    col.sort_by_key(|&(i, _)| i);
+    col
+}
+
+/// Inflate, prune and renormalise a column. Returns its chaos (max - sum of squares)
+fn inflate_column(col: &mut Column, params: &MclParams) -> f64 {
+    for (_, v) in col.iter_mut() {
+        *v = v.powf(params.inflation);
+    }
+    normalise(col);
+
+    col.retain(|&(_, v)| v >= params.prune_threshold);
+    if col.len() > params.max_per_column {
+        col.sort_by(|a, b| b.1.total_cmp(&a.1));
+        col.truncate(params.max_per_column);
+        col.sort_by_key(|&(i, _)| i);
+    }
+    normalise(col);
+
+    let max = col.iter().map(|&(_, v)| v).fold(0.0, f64::max);
+    let sum_sq: f64 = col.iter().map(|&(_, v)| v * v).sum();
+    max - sum_sq
+}
+
+/// Cluster a weighted undirected graph of `n_nodes` nodes with MCL.
+///
+/// Self-loops are added with the node's largest edge weight, as the mcl program does.
+/// Nodes attracted to the same attractors form a cluster. Returns clusters (sorted node
+/// indices), largest first. Nodes without edges are returned as singletons.
+pub fn mcl_cluster(n_nodes: usize, edges: &[(usize, usize, f64)], params: &MclParams) -> Vec<Vec<usize>> {
+
+    // symmetric weights, keeping the largest for repeated edges
+    let mut weights: Vec<HashMap<usize, f64>> = vec![HashMap::new(); n_nodes];
+    for &(a, b, w) in edges {
+        if a == b || w <= 0.0 {
+            continue;
+        }
+        for (x, y) in [(a, b), (b, a)] {
+            let entry = weights[y].entry(x).or_insert(0.0);
+            *entry = entry.max(w);
+        }
+    }
+
+    let mut matrix: Vec<Column> = weights
+        .into_iter()
+        .enumerate()
+        .map(|(j, col)| {
+            let loop_weight = col.values().cloned().fold(0.0, f64::max);
+            let mut col: Column = col.into_iter().collect();
+            col.push((j, if loop_weight > 0.0 { loop_weight } else { 1.0 }));
+            col.sort_by_key(|&(i, _)| i);
+            normalise(&mut col);
+            col
+        })
+        .collect();
+
+    // expansion and inflation of each column are independent, so columns are processed in parallel
+    for _ in 0..params.max_iterations {
+        let next: Vec<(Column, f64)> = (0..n_nodes)
+            .into_par_iter()
+            .map_init(HashMap::new, |acc, j| {
+                let mut col = expand_column(&matrix, j, acc);
+                let chaos = inflate_column(&mut col, params);
+                (col, chaos)
+            })
+            .collect();
+        let chaos = next.iter().map(|(_, c)| *c).fold(0.0, f64::max);
+        matrix = next.into_iter().map(|(col, _)| col).collect();
+        if chaos < params.chaos_limit {
+            break;
+        }
+    }
+
+    // each node joins the cluster of the attractors it flows to (merged if it flows to several)
+    let mut parent: Vec<usize> = (0..n_nodes).collect();
+    fn root(parent: &mut [usize], mut x: usize) -> usize {
+        while parent[x] != x {
+            parent[x] = parent[parent[x]];
+            x = parent[x];
+        }
+        x
+    }
+    for (j, col) in matrix.iter().enumerate() {
+        for &(i, v) in col {
+            if v > 0.0 {
+                let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+                if ri != rj {
+                    parent[ri] = rj;
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, BTreeSet<usize>> = HashMap::new();
+    for node in 0..n_nodes {
+        let r = root(&mut parent, node);
+        groups.entry(r).or_default().insert(node);
+    }
+
+    let mut clusters: Vec<Vec<usize>> = groups.into_values().map(|g| g.into_iter().collect()).collect();
+    clusters.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    clusters
+}
+
+/// Cluster a graph of named genes with MCL. Returns cluster ID (from 1) to genes, largest
+/// clusters first and genes sorted within clusters. Clusters of one gene are left out
+pub fn cluster_graph(edges: &[(String, String, f64)], params: &MclParams) -> HashMap<usize, Vec<String>> {
+
+    let mut names: Vec<&str> = edges.iter().flat_map(|(a, b, _)| [a.as_str(), b.as_str()]).collect();
+    names.sort_unstable();
+    names.dedup();
+    let index: HashMap<&str, usize> = names.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+
+    let indexed: Vec<(usize, usize, f64)> = edges.iter().map(|(a, b, w)| (index[a.as_str()], index[b.as_str()], *w)).collect();
+
+    mcl_cluster(names.len(), &indexed, params)
+        .into_iter()
+        .filter(|c| c.len() > 1)
+        .enumerate()
+        .map(|(i, c)| (i + 1, c.into_iter().map(|n| names[n].to_string()).collect()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cliques 0-3 and 4-7 (weight 1) joined by a weak 3-4 edge
+    fn two_cliques(bridge: f64) -> Vec<(usize, usize, f64)> {
+        let mut edges = Vec::new();
+        for clique in [0..4, 4..8] {
+            let nodes: Vec<usize> = clique.collect();
+            for (k, &a) in nodes.iter().enumerate() {
+                for &b in &nodes[k + 1..] {
+                    edges.push((a, b, 1.0));
+                }
+            }
+        }
+        edges.push((3, 4, bridge));
+        edges
+    }
+
+    #[test]
+    fn separates_two_cliques() {
+        let clusters = mcl_cluster(9, &two_cliques(0.1), &MclParams::with_inflation(2.0));
+        // node 8 has no edges and stays on its own
+        assert_eq!(clusters, vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8]]);
+    }
+
+    #[test]
+    fn named_clusters_without_singletons() {
+        let edges: Vec<(String, String, f64)> = two_cliques(0.1)
+            .into_iter()
+            .map(|(a, b, w)| (format!("g{}", a), format!("g{}", b), w))
+            .chain([("g8".to_string(), "g8".to_string(), 1.0)])
+            .collect();
+        let clusters = cluster_graph(&edges, &MclParams::with_inflation(2.0));
+        assert_eq!(clusters.len(), 2);
+        let mut found: Vec<Vec<String>> = clusters.into_values().collect();
+        found.sort();
+        assert_eq!(found, vec![
+            vec!["g0", "g1", "g2", "g3"].into_iter().map(String::from).collect::<Vec<_>>(),
+            vec!["g4", "g5", "g6", "g7"].into_iter().map(String::from).collect::<Vec<_>>(),
+        ]);
+    }
+}
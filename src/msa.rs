@@ -0,0 +1,188 @@
+use crate::logger::Logger;
+use crate::synima::CitationInfo;
+use crate::util::{clean_muscle_version, get_version};
+
+use std::fs::File;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// A multiple sequence aligner used by the tree step for each single-copy orthogroup.
+///
+/// Implementations only describe how to call their tool. Scheduling, skipping of
+/// existing alignments and logging are handled in `tree.rs`.
+pub trait MultipleAligner: Sync {
+    /// Name used with --msa
+    fn name(&self) -> &'static str;
+
+    /// Name used on the methods page and for citations
+    fn display_name(&self) -> &'static str;
+
+    /// Executable looked up in the bundled bin dir, then PATH
+    fn executable(&self) -> &'static str;
+
+    /// Sequence types ("pep", "cds") this aligner can align
+    fn supported_alphabets(&self) -> &'static [&'static str];
+
+    /// Longest sequence this aligner is given (None = no limit); larger clusters are skipped
+    fn max_length(&self, _is_pep: bool) -> Option<usize> {
+        None
+    }
+
+    /// Command aligning `input` to the FASTA `output` with `threads` threads
+    fn align_command(&self, exe: &Path, input: &Path, output: &Path, is_pep: bool, threads: usize) -> Result<Command, String>;
+
+    /// Version string for the methods page, from the resolved executable
+    fn version(&self, exe: &Path) -> String;
+
+    /// Citation for the methods page
+    fn citation(&self) -> CitationInfo;
+}
+
+/// All aligners accepted by --msa
+pub const MSA_NAMES: [&str; 3] = ["muscle", "mafft", "famsa"];
+
+/// Look up a multiple aligner by its --msa name
+pub fn get_msa(name: &str) -> Option<Box<dyn MultipleAligner>> {
+    match name {
+        "muscle" => Some(Box::new(Muscle)),
+        "mafft" => Some(Box::new(Mafft)),
+        "famsa" => Some(Box::new(Famsa)),
+        _ => None,
+    }
+}
+
+/// Look up a multiple aligner, or exit with the list of valid names
+pub fn get_msa_or_exit(name: &str, logger: &Logger) -> Box<dyn MultipleAligner> {
+    get_msa(name).unwrap_or_else(|| {
+        logger.error(&format!("get_msa: unsupported multiple aligner '{}', expected one of: {}", name, MSA_NAMES.join(", ")));
+        std::process::exit(1);
+    })
+}
+
+// MUSCLE 5
+
+const MAX_MUSCLE_AA: usize = 20000;  // Muscle5 safe limit
+const MAX_MUSCLE_NT: usize = 60_000;   // 60k nt limit for nucleotide alignments
+
+pub struct Muscle;
+
+impl MultipleAligner for Muscle {
+    fn name(&self) -> &'static str { "muscle" }
+
+    fn display_name(&self) -> &'static str { "MUSCLE" }
+
+    fn executable(&self) -> &'static str { "muscle" }
+
+    fn supported_alphabets(&self) -> &'static [&'static str] { &["pep", "cds"] }
+
+    fn max_length(&self, is_pep: bool) -> Option<usize> {
+        Some(if is_pep { MAX_MUSCLE_AA } else { MAX_MUSCLE_NT })
+    }
+
+    fn align_command(&self, exe: &Path, input: &Path, output: &Path, _is_pep: bool, threads: usize) -> Result<Command, String> {
+        let mut cmd = Command::new(exe);
+        cmd.arg("-align").arg(input)
+            .arg("-output").arg(output)
+            .arg("-threads").arg(threads.to_string())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        Ok(cmd)
+    }
+
+    fn version(&self, exe: &Path) -> String {
+        let raw = get_version(exe, &["-version"]).unwrap_or_else(|| "Unknown".into());
+        clean_muscle_version(&raw)
+    }
+
+    fn citation(&self) -> CitationInfo {
+        CitationInfo {
+            tool: "MUSCLE".into(),
+            citation: "Edgar RC et al., Nat Commun. (2022)".into(),
+            link: "https://pubmed.ncbi.nlm.nih.gov/36379955/".into(),
+        }
+    }
+}
+
+// MAFFT (writes the alignment to stdout)
+
+pub struct Mafft;
+
+impl MultipleAligner for Mafft {
+    fn name(&self) -> &'static str { "mafft" }
+
+    fn display_name(&self) -> &'static str { "MAFFT" }
+
+    fn executable(&self) -> &'static str { "mafft" }
+
+    fn supported_alphabets(&self) -> &'static [&'static str] { &["pep", "cds"] }
+
+    fn align_command(&self, exe: &Path, input: &Path, output: &Path, is_pep: bool, threads: usize) -> Result<Command, String> {
+        let out = File::create(output).map_err(|e| format!("failed to create {}: {}", output.display(), e))?;
+        let mut cmd = Command::new(exe);
+        cmd.arg("--auto")
+            .arg(if is_pep { "--amino" } else { "--nuc" })
+            .arg("--thread").arg(threads.to_string())
+            .arg("--quiet")
+            .arg(input)
+            .stdout(out)
+            .stderr(Stdio::null());
+        Ok(cmd)
+    }
+
+    fn version(&self, exe: &Path) -> String {
+        // "v7.520 (2023/Mar/22)" on stderr
+        get_version(exe, &["--version"])
+            .map(|v| v.trim().trim_start_matches('v').to_string())
+            .unwrap_or_else(|| "Unknown".into())
+    }
+
+    fn citation(&self) -> CitationInfo {
+        CitationInfo {
+            tool: "MAFFT".into(),
+            citation: "Katoh K & Standley DM, Mol Biol Evol (2013)".into(),
+            link: "https://pubmed.ncbi.nlm.nih.gov/23329690/".into(),
+        }
+    }
+}
+
+// FAMSA (protein only)
+
+pub struct Famsa;
+
+impl MultipleAligner for Famsa {
+    fn name(&self) -> &'static str { "famsa" }
+
+    fn display_name(&self) -> &'static str { "FAMSA" }
+
+    fn executable(&self) -> &'static str { "famsa" }
+
+    fn supported_alphabets(&self) -> &'static [&'static str] { &["pep"] }
+
+    fn align_command(&self, exe: &Path, input: &Path, output: &Path, _is_pep: bool, threads: usize) -> Result<Command, String> {
+        let mut cmd = Command::new(exe);
+        cmd.arg("-t").arg(threads.to_string())
+            .arg(input)
+            .arg(output)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        Ok(cmd)
+    }
+
+    fn version(&self, exe: &Path) -> String {
+        // Usage banner: "  version 2.2.3-1669fc1 (2024-09-17)"
+        get_version(exe, &["-help"])
+            .and_then(|text| {
+                text.lines()
+                    .find_map(|l| l.trim().strip_prefix("version ").map(|v| v.split_whitespace().next().unwrap_or(v).to_string()))
+            })
+            .unwrap_or_else(|| "Unknown".into())
+    }
+
+    fn citation(&self) -> CitationInfo {
+        CitationInfo {
+            tool: "FAMSA".into(),
+            citation: "Deorowicz S et al., Sci Rep (2016)".into(),
+            link: "https://pubmed.ncbi.nlm.nih.gov/27670777/".into(),
+        }
+    }
+}
@@ -1,15 +1,17 @@
 use crate::Logger;
+use crate::hits::SearchHits;
+use crate::mcl;
+use crate::search_io;
 use crate::util::{open_bufread, open_bufwrite}; //mkdir,open_file_read,open_file_write
 
 use std::path::Path;
-use std::collections::{HashMap, HashSet};
-use std::fs;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufRead, Write};
 //use std::process::{Command, Stdio};
 use std::path::PathBuf;
-use regex::Regex;
 use std::process;
+use rayon::prelude::*;
 
 // for OMCL
 pub fn parse_genome_map_from_gff(gff_path: &Path, logger: &Logger) -> Result<HashSet<String>, String> {
@@ -75,34 +77,25 @@ pub fn assign_genome_codes<P: AsRef<Path>>(genome_set: &HashSet<String>, output_
     Ok(genome_to_code)
 }
 
-pub fn write_gcoded_m8_and_sort<P: AsRef<Path>>(
+/// Write the search output in `search_hits` (streamed per genome pair) with Gcoded IDs, sorted for OrthoMCL
+pub fn write_gcoded_m8_and_sort(
     genome_to_code: &HashMap<String, String>,
-    m8_input_path: P,
-    m8_output_path: P,
+    search_hits: &SearchHits,
+    m8_output_path: &Path,
     logger: &Logger,
 ) {
 
-    // input/output
-    let input_path = m8_input_path.as_ref();
-    let output_path = m8_output_path.as_ref();
+    // output
+    let output_path = m8_output_path;
     let tmp_path = output_path.with_extension("tmp");
-    let reader = open_bufread(&input_path, &logger, "write_gcoded_m8_and_sort");
     let mut writer = open_bufwrite(&tmp_path, &logger, "write_gcoded_m8_and_sort");
 
-    logger.information(&format!("write_gcoded_m8_and_sort: {}", input_path.display()));
-
-    for line_res in reader.lines() {
-        let line = match line_res {
-            Ok(l) => l,
-            Err(e) => {
-                logger.error(&format!("write_gcoded_m8_and_sort: Error reading m8: {}", e));
-                std::process::exit(1);
-            }
-        };
+    logger.information(&format!("write_gcoded_m8_and_sort: {}", search_hits.dir.display()));
 
+    search_io::for_each_hit_line(search_hits, logger, "write_gcoded_m8_and_sort", |line| {
         let mut fields: Vec<String> = line.split('\t').map(|s| s.to_string()).collect();
         if fields.len() < 12 {
-            continue;
+            return;
         }
 
         let acc_a = fields[0].clone();
@@ -113,7 +106,7 @@ pub fn write_gcoded_m8_and_sort<P: AsRef<Path>>(
             Some(t) => t,
             None => {
                 logger.error(&format!("write_gcoded_m8_and_sort: invalid query ID format: {}", fields[0]));
-                continue;
+                return;
             }
         };
 
@@ -121,7 +114,7 @@ pub fn write_gcoded_m8_and_sort<P: AsRef<Path>>(
             Some(t) => t,
             None => {
                 logger.error(&format!("write_gcoded_m8_and_sort: invalid subject ID format: {}", fields[1]));
-                continue;
+                return;
             }
         };
 
@@ -130,7 +123,7 @@ pub fn write_gcoded_m8_and_sort<P: AsRef<Path>>(
             Some(c) => c,
             None => {
                 logger.error(&format!("write_gcoded_m8_and_sort: missing genome code for {}", genome_a));
-                continue;
+                return;
             }
         };
 
@@ -138,7 +131,7 @@ pub fn write_gcoded_m8_and_sort<P: AsRef<Path>>(
             Some(c) => c,
             None => {
                 logger.error(&format!("write_gcoded_m8_and_sort: missing genome code for {}", genome_b));
-                continue;
+                return;
             }
         };
 
@@ -150,7 +143,7 @@ pub fn write_gcoded_m8_and_sort<P: AsRef<Path>>(
             logger.error(&format!("write_gcoded_m8_and_sort: write error to {}: {}", tmp_path.display(), e));
             std::process::exit(1);
         }
-    }
+    });
 
     drop(writer); // flush temp file
 
@@ -158,7 +151,7 @@ pub fn write_gcoded_m8_and_sort<P: AsRef<Path>>(
     logger.information("write_gcoded_m8_and_sort: sorting Gcoded m8 file...");
 
     let tmp_path_str = tmp_path.to_string_lossy();
-    let out_path_str = m8_output_path.as_ref().to_string_lossy();
+    let out_path_str = m8_output_path.to_string_lossy();
     let sort_cmd = format!("sort -T . -S 2G -k1,1 -k12,12gr {} > {}", tmp_path_str, out_path_str);
 
     let status = match std::process::Command::new("sh").arg("-c").arg(&sort_cmd).status() {
@@ -195,7 +188,7 @@ pub fn convert_m8_to_orthomcl_format(
     let mut gg_writer = open_bufwrite(&gg_path, &logger, "convert_m8_to_orthomcl_format");
 
     // For .gg: genome code -> set of gene IDs
-    let mut org_to_accs: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut org_to_accs: BTreeMap<String, HashSet<String>> = BTreeMap::new();
 
     // For deduping m8 rows
     let mut seen: HashSet<String> = HashSet::new();
@@ -274,74 +267,346 @@ pub fn convert_m8_to_orthomcl_format(
     Ok((bpo_path, gg_path))
 }
 
-pub fn run_orthomcl_clustering<P: AsRef<Path>>(
-    orthomcl_script: &Path,
-    bpo_path: &Path,
-    gg_path: P,
-    log_path: &Path,
-    logger: &Logger,
-) -> Result<(), String> {
+/// OrthoMCL settings (--omcl_evalue_cutoff, --inflation)
+#[derive(Debug, Clone)]
+pub struct OmclParams {
+    pub evalue_cutoff: f64,
+    pub inflation: f64,
+}
+
+impl OmclParams {
+    pub fn from_args(args: &crate::args::Args) -> Self {
+        OmclParams {
+            evalue_cutoff: args.omcl_evalue_cutoff,
+            inflation: args.inflation,
+        }
+    }
+}
+
+/// Weight used for hits with an e-value of 0 (-log10 of the smallest e-value BLAST reports)
+const OMCL_MAX_WEIGHT: f64 = 316.0;
+
+/// Genes and hits read from the .gg and .bpo files, genes indexed in sorted order
+struct OmclInput {
+    genes: Vec<String>,
+    taxa: Vec<String>,
+    taxon_of: Vec<usize>,
+    taxon_genes: Vec<Vec<usize>>,
+    hits: Vec<Vec<(usize, f64)>>, // per query: (subject, evalue) by ascending e-value, then descending bitscore
+}
+
+impl OmclInput {
+    /// E-value of the first hit of `a` to `b`
+    fn evalue(&self, a: usize, b: usize) -> Option<f64> {
+        self.hits[a].iter().find(|(s, _)| *s == b).map(|(_, e)| *e)
+    }
+}
+
+/// Pairs found by one OrthoMCL step (in-paralogs of a taxon, or orthologs of a taxon pair)
+#[derive(Default)]
+struct OmclPairs {
+    edges: BTreeMap<usize, Vec<usize>>,
+    weights: HashMap<(usize, usize), f64>, // both orientations
+    sum_weight: f64,
+    count: usize,
+    ortholog_genes: Vec<usize>, // genes in ortholog pairs (taxon pairs only)
+}
+
+impl OmclPairs {
+    fn add(&mut self, a: usize, b: usize, weight: f64) {
+        self.edges.entry(a).or_default().push(b);
+        self.edges.entry(b).or_default().push(a);
+        self.weights.insert((a, b), round3(weight));
+        self.weights.insert((b, a), round3(weight));
+        self.sum_weight += weight;
+        self.count += 1;
+    }
+}
+
+fn round3(x: f64) -> f64 {
+    (x * 1000.0).round() / 1000.0
+}
+
+fn evalue_weight(evalue: f64) -> f64 {
+    if evalue <= 0.0 { OMCL_MAX_WEIGHT } else { -evalue.log10() }
+}
+
+fn read_omcl_input(bpo_path: &Path, gg_path: &Path, logger: &Logger) -> OmclInput {
+
+    // .gg: "G001: G001|gene1 G001|gene2 ..."
+    let mut taxon_members: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let reader = open_bufread(gg_path, logger, "read_omcl_input");
+    for line in reader.lines() {
+        let line = line.unwrap_or_else(|e| {
+            logger.error(&format!("read_omcl_input: failed to read {}: {}", gg_path.display(), e));
+            std::process::exit(1);
+        });
+        let Some((taxon, members)) = line.split_once(':') else { continue };
+        taxon_members.entry(taxon.trim().to_string()).or_default().extend(members.split_whitespace().map(|g| g.to_string()));
+    }
+
+    let taxa: Vec<String> = taxon_members.keys().cloned().collect();
+    let mut gene_taxon: BTreeMap<String, usize> = BTreeMap::new();
+    for (t, members) in taxon_members.values().enumerate() {
+        for gene in members {
+            gene_taxon.insert(gene.clone(), t);
+        }
+    }
+    let genes: Vec<String> = gene_taxon.keys().cloned().collect();
+    let taxon_of: Vec<usize> = gene_taxon.values().cloned().collect();
+    let index: HashMap<&str, usize> = genes.iter().enumerate().map(|(i, g)| (g.as_str(), i)).collect();
+    let mut taxon_genes: Vec<Vec<usize>> = vec![Vec::new(); taxa.len()];
+    for (i, &t) in taxon_of.iter().enumerate() {
+        taxon_genes[t].push(i);
+    }
+
+    // .bpo: "sim;query;0;subject;0;evalue;bitscore;aln_len"
+    let mut scored: Vec<Vec<(usize, f64, f64)>> = vec![Vec::new(); genes.len()];
+    let mut skipped = 0usize;
+    let reader = open_bufread(bpo_path, logger, "read_omcl_input");
+    for line in reader.lines() {
+        let line = line.unwrap_or_else(|e| {
+            logger.error(&format!("read_omcl_input: failed to read {}: {}", bpo_path.display(), e));
+            std::process::exit(1);
+        });
+        let fields: Vec<&str> = line.split(';').collect();
+        if fields.len() < 6 {
+            continue;
+        }
+        let (Some(&q), Some(&s), Ok(evalue)) = (index.get(fields[1]), index.get(fields[3]), fields[5].parse::<f64>()) else {
+            skipped += 1;
+            continue;
+        };
+        let bitscore = fields.get(6).and_then(|b| b.parse::<f64>().ok()).unwrap_or(0.0);
+        scored[q].push((s, evalue, bitscore));
+    }
+    if skipped > 0 {
+        logger.warning(&format!("read_omcl_input: skipped {} hits with unknown genes or e-values in {}", skipped, bpo_path.display()));
+    }
 
-    // Convert full paths to filenames for in-place output
-    let bpo_file = bpo_path.file_name().ok_or("Invalid BPO file path")?;
-    let gg_file = gg_path.as_ref().file_name().ok_or("Invalid GG file path")?;
+    // the .bpo is sorted by bitscore, but best hits are found by e-value
+    let hits: Vec<Vec<(usize, f64)>> = scored.into_iter().map(|mut q_hits| {
+        q_hits.sort_by(|a, b| a.1.total_cmp(&b.1).then(b.2.total_cmp(&a.2)));
+        q_hits.into_iter().map(|(s, evalue, _)| (s, evalue)).collect()
+    }).collect();
 
-    logger.information(&format!("run_orthomcl_clustering: {} and {}", bpo_file.to_string_lossy(), gg_file.to_string_lossy()));
+    OmclInput { genes, taxa, taxon_of, taxon_genes, hits }
+}
 
-    // Build command
-    let mut cmd = std::process::Command::new("perl");
-    cmd.arg(orthomcl_script)
-        .arg("--mode")
-        .arg("4")
-        .arg("--bpo_file").arg(bpo_file)
-        .arg("--gg_file").arg(gg_file);
+/// Keep pairs where each gene is among the other's best hits, weighted by the mean -log10(e-value)
+fn reciprocal_pairs(best: &BTreeMap<usize, Vec<usize>>, evalues: &HashMap<(usize, usize), f64>) -> OmclPairs {
+    let mut pairs = OmclPairs::default();
+    for (&q, subjects) in best {
+        for &s in subjects {
+            if pairs.weights.contains_key(&(q, s)) {
+                continue;
+            }
+            if best.get(&s).is_some_and(|back| back.contains(&q)) {
+                let weight = (evalue_weight(evalues[&(q, s)]) + evalue_weight(evalues[&(s, q)])) / 2.0;
+                pairs.add(q, s, weight);
+            }
+        }
+    }
+    pairs
+}
 
-    let work_dir = bpo_path.parent().ok_or("bpo_path has no parent directory")?;
-    cmd.current_dir(work_dir).stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::piped());
+/// Reciprocal better hits within a taxon (hits scoring better than any hit to another taxon)
+fn find_inparalogs(input: &OmclInput, taxon: usize, cutoff: f64) -> OmclPairs {
+    let mut best: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    let mut evalues: HashMap<(usize, usize), f64> = HashMap::new();
+
+    for &q in &input.taxon_genes[taxon] {
+        // within runs of equal e-values, hits to the same taxon come first
+        let mut sorted: Vec<(usize, f64)> = Vec::with_capacity(input.hits[q].len());
+        let mut run: Vec<(usize, f64)> = Vec::new();
+        for &hit in &input.hits[q] {
+            if run.last().is_some_and(|last| last.1 != hit.1) {
+                run.sort_by_key(|(s, _)| input.taxon_of[*s] != taxon);
+                sorted.append(&mut run);
+            }
+            run.push(hit);
+        }
+        run.sort_by_key(|(s, _)| input.taxon_of[*s] != taxon);
+        sorted.append(&mut run);
 
-    // Run and capture output
-    let output = cmd.output().map_err(|e| format!("Failed to run OrthoMCL: {}", e))?;
+        for (s, evalue) in sorted {
+            if s == q {
+                continue;
+            }
+            if input.taxon_of[s] != taxon || evalue > cutoff {
+                break;
+            }
+            best.entry(q).or_default().push(s);
+            evalues.insert((q, s), evalue);
+        }
+    }
 
-    // Write stdout and stderr to the log
-    let mut log_file = File::create(&log_path).map_err(|e| format!("Cannot write log file: {}", e))?;
-    let _ = log_file.write_all(&output.stdout);
-    let _ = log_file.write_all(&output.stderr);
+    reciprocal_pairs(&best, &evalues)
+}
 
-    //logger.information(&format!("OrthoMCL clustering complete. See log: {}", log_path.as_ref().display()));
-    let log_contents = fs::read_to_string(&log_path).unwrap_or_else(|_| "[Unable to read log output]".to_string());
-    logger.information(&format!("OrthoMCL output:\n{}", log_contents));
+/// Reciprocal best hits between two taxa (ties on e-value included)
+fn find_orthologs(input: &OmclInput, ta: usize, tb: usize, cutoff: f64) -> OmclPairs {
+    let mut best: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    let mut evalues: HashMap<(usize, usize), f64> = HashMap::new();
+
+    for (from, to) in [(ta, tb), (tb, ta)] {
+        for &q in &input.taxon_genes[from] {
+            let mut top: Option<f64> = None;
+            for &(s, evalue) in &input.hits[q] {
+                if input.taxon_of[s] != to {
+                    continue;
+                }
+                match top {
+                    None => top = Some(evalue),
+                    Some(t) if t != evalue => break,
+                    _ => {}
+                }
+                if evalue <= cutoff {
+                    best.entry(q).or_default().push(s);
+                    evalues.insert((q, s), evalue);
+                }
+            }
+        }
+    }
 
-    // read the log output to find location of output, and then move file and delete tmp folder
-    let reader = open_bufread(&log_path, &logger, "run_orthomcl_clustering");
+    reciprocal_pairs(&best, &evalues)
+}
+
+/// Add co-orthologs (pairs linked through in-paralogs of orthologs) and normalise the pair
+/// weights by their average
+fn add_coorthologs(input: &OmclInput, pairs: &mut OmclPairs, inparalogs: &[&OmclPairs], cutoff: f64) {
+    let orthologs: Vec<(usize, Vec<usize>)> = pairs.edges.iter().map(|(&n, partners)| (n, partners.clone())).collect();
+    pairs.ortholog_genes = pairs.edges.keys().cloned().collect();
+    let paralogs_of = |n: usize| -> Vec<usize> {
+        inparalogs.iter().filter_map(|p| p.edges.get(&n)).flatten().cloned().collect()
+    };
 
-    let mut orthomcl_out_path: Option<PathBuf> = None;
-    let pattern = Regex::new(r"Final ORTHOMCL Result: (\S+)").unwrap();
+    for (n, partners) in orthologs {
+        let mut nodes1: BTreeSet<usize> = BTreeSet::from([n]);
+        nodes1.extend(paralogs_of(n));
+        let mut nodes2: BTreeSet<usize> = BTreeSet::new();
+        for m in partners {
+            nodes2.insert(m);
+            nodes2.extend(paralogs_of(m));
+        }
+
+        for &a in &nodes1 {
+            for &b in &nodes2 {
+                if pairs.weights.contains_key(&(a, b)) {
+                    continue;
+                }
+                let (Some(ab), Some(ba)) = (input.evalue(a, b), input.evalue(b, a)) else { continue };
+                if ab > cutoff || ba > cutoff {
+                    continue;
+                }
+                pairs.add(a, b, (evalue_weight(ab) + evalue_weight(ba)) / 2.0);
+            }
+        }
+    }
 
-    for line in reader.lines().flatten() {
-        if let Some(captures) = pattern.captures(&line) {
-            orthomcl_out_path = Some(work_dir.join(&captures[1]));
-            break;
+    if pairs.count > 0 {
+        let average = pairs.sum_weight / pairs.count as f64;
+        for w in pairs.weights.values_mut() {
+            *w = round3(*w / average);
         }
     }
+}
+
+/// Normalise in-paralog weights by the average over pairs where either gene has an ortholog
+/// (or over all pairs, if none do)
+fn normalise_inparalogs(pairs: &mut OmclPairs, has_ortholog: &HashSet<usize>) {
+    let with_ortholog: Vec<f64> = pairs.weights.iter()
+        .filter(|((a, b), _)| has_ortholog.contains(a) || has_ortholog.contains(b))
+        .map(|(_, &w)| w)
+        .collect();
+    let all: Vec<f64> = pairs.weights.values().cloned().collect();
+    let used = if with_ortholog.is_empty() { &all } else { &with_ortholog };
+    if used.is_empty() {
+        return;
+    }
+    let average = used.iter().sum::<f64>() / used.len() as f64;
+    for w in pairs.weights.values_mut() {
+        *w = round3(*w / average);
+    }
+}
 
-    // Use the path extracted from the log
-    let orthomcl_out_path = orthomcl_out_path.ok_or("Failed to extract final .out file from OrthoMCL log")?;
-    let final_filename = orthomcl_out_path.file_name().ok_or("Failed to extract final .out filename")?;
-    let tmp_dir = &work_dir.join(
-    orthomcl_out_path.parent().and_then(|p| p.file_name()).ok_or("Cannot resolve tmp dir from output path")?);
+/// Cluster the .bpo/.gg data with the OrthoMCL algorithm (in-paralogs, orthologs and
+/// co-orthologs, per-taxon-pair normalisation, then MCL) and write `all_orthomcl.out`
+pub fn run_orthomcl_clustering(
+    bpo_path: &Path,
+    gg_path: &Path,
+    out_path: &Path,
+    log_path: &Path,
+    params: &OmclParams,
+    logger: &Logger,
+) -> Result<(), String> {
 
-    let final_destination = work_dir.join(final_filename);
+    logger.information(&format!("run_orthomcl_clustering: {} and {}", bpo_path.display(), gg_path.display()));
+    let input = read_omcl_input(bpo_path, gg_path, logger);
+    let mut log_lines: Vec<String> = vec![
+        format!("{} genes in {} taxa", input.genes.len(), input.taxa.len()),
+        format!("e-value cutoff {}, inflation {}", params.evalue_cutoff, params.inflation),
+    ];
+
+    let mut inparalogs: Vec<OmclPairs> = (0..input.taxa.len())
+        .into_par_iter()
+        .map(|t| find_inparalogs(&input, t, params.evalue_cutoff))
+        .collect();
+    for (t, pairs) in inparalogs.iter().enumerate() {
+        log_lines.push(format!("{}: {} in-paralog pairs", input.taxa[t], pairs.count));
+    }
 
-    // Move the result file
-    fs::rename(&orthomcl_out_path, &final_destination)
-        .map_err(|e| format!("Failed to move {} to {}: {}", orthomcl_out_path.display(), final_destination.display(), e))?;
+    let taxon_pairs: Vec<(usize, usize)> = (0..input.taxa.len())
+        .flat_map(|a| (a + 1..input.taxa.len()).map(move |b| (a, b)))
+        .collect();
+    let orthologs: Vec<(OmclPairs, usize)> = taxon_pairs
+        .par_iter()
+        .map(|&(a, b)| {
+            let mut pairs = find_orthologs(&input, a, b, params.evalue_cutoff);
+            let n_orthologs = pairs.count;
+            add_coorthologs(&input, &mut pairs, &[&inparalogs[a], &inparalogs[b]], params.evalue_cutoff);
+            (pairs, n_orthologs)
+        })
+        .collect();
+
+    // genes with an ortholog (before co-orthologs were added)
+    let mut has_ortholog: HashSet<usize> = HashSet::new();
+    for (&(a, b), (pairs, n_orthologs)) in taxon_pairs.iter().zip(&orthologs) {
+        log_lines.push(format!("{} - {}: {} ortholog pairs, {} co-ortholog pairs", input.taxa[a], input.taxa[b], n_orthologs, pairs.count - n_orthologs));
+        has_ortholog.extend(pairs.ortholog_genes.iter().cloned());
+    }
+    for pairs in inparalogs.iter_mut() {
+        normalise_inparalogs(pairs, &has_ortholog);
+    }
 
-    logger.information(&format!("Relocated {} to {}", orthomcl_out_path.display(), final_destination.display()));
+    // graph of all normalised pairs
+    let mut edges: Vec<(usize, usize, f64)> = Vec::new();
+    for pairs in inparalogs.iter().chain(orthologs.iter().map(|(p, _)| p)) {
+        edges.extend(pairs.weights.iter().filter(|((a, b), _)| a < b).map(|(&(a, b), &w)| (a, b, w)));
+    }
+    log_lines.push(format!("{} weighted pairs", edges.len()));
+
+    let clusters = mcl::mcl_cluster(input.genes.len(), &edges, &mcl::MclParams::with_inflation(params.inflation));
+
+    // ORTHOMCL{id}({n} genes,{t} taxa):\t gene(taxon) gene(taxon) ...
+    let mut writer = open_bufwrite(out_path, logger, "run_orthomcl_clustering");
+    let mut n_clusters = 0usize;
+    for cluster in clusters.iter().filter(|c| c.len() > 1) {
+        let n_taxa = cluster.iter().map(|&g| input.taxon_of[g]).collect::<HashSet<_>>().len();
+        let members: Vec<String> = cluster.iter().map(|&g| format!("{}({})", input.genes[g], input.taxa[input.taxon_of[g]])).collect();
+        if let Err(e) = writeln!(writer, "ORTHOMCL{}({} genes,{} taxa):\t {}", n_clusters, cluster.len(), n_taxa, members.join(" ")) {
+            logger.error(&format!("run_orthomcl_clustering: write error to {}: {}", out_path.display(), e));
+            std::process::exit(1);
+        }
+        n_clusters += 1;
+    }
+    writer.flush().map_err(|e| format!("Failed to write {}: {}", out_path.display(), e))?;
+    log_lines.push(format!("{} clusters written to {}", n_clusters, out_path.display()));
 
-    // Clean up the tmp subdirectory
-    if tmp_dir != work_dir {
-        fs::remove_dir_all(tmp_dir).map_err(|e| format!("Failed to remove temporary directory {}: {}", tmp_dir.display(), e))?;
-        logger.information(&format!("Cleaned up temporary OrthoMCL directory: {}", tmp_dir.display()));
+    let mut log_file = File::create(log_path).map_err(|e| format!("Cannot write log file: {}", e))?;
+    for line in &log_lines {
+        logger.information(&format!("run_orthomcl_clustering: {}", line));
+        let _ = writeln!(log_file, "{}", line);
     }
 
     Ok(())
@@ -1,5 +1,7 @@
 use crate::logger::Logger;
 use crate::RepoEntry;
+use crate::hits::SearchHits;
+use crate::search_io;
 use crate::write_fasta;
 use crate::util::{mkdir, open_bufread, open_bufwrite};
 
@@ -15,13 +17,9 @@ fn generate_species_ids(blast_dir: &Path, out_dir: &Path, logger: &Logger) -> Re
 
     for entry in fs::read_dir(blast_dir).map_err(|e| format!("Failed to read blast dir: {e}"))? {
         let path = entry.map_err(|e| format!("Dir entry error: {e}"))?.path();
-        if path.extension().and_then(|s| s.to_str()) == Some("out") {
-            if let Some(file_stem) = path.file_stem().and_then(|s| s.to_str()) {
-                if let Some((a, b)) = file_stem.split_once("_vs_") {
-                    species_set.insert(a.to_string(), ());
-                    species_set.insert(b.to_string(), ());
-                }
-            }
+        if let Some((a, b)) = path.file_name().and_then(|s| s.to_str()).and_then(search_io::parse_pair_file_name) {
+            species_set.insert(a.to_string(), ());
+            species_set.insert(b.to_string(), ());
         }
     }
 
@@ -48,7 +46,7 @@ fn generate_species_ids(blast_dir: &Path, out_dir: &Path, logger: &Logger) -> Re
 
 /// Rewrite BLAST .out files to OrthoFinder .m8:
 pub fn rewrite_blast_files(
-    blast_dir: &Path,
+    search_hits: &SearchHits,
     of_out_dir: &Path,
     species_ids: &BTreeMap<String, usize>,
     seq_id_map: &HashMap<String, String>,
@@ -59,24 +57,20 @@ pub fn rewrite_blast_files(
     let of_blast = of_out_dir.join("Blast");
     mkdir(&of_blast, &logger, "rewrite_blast_files");
 
-    for entry in fs::read_dir(blast_dir).map_err(|e| format!("Failed to read blast dir: {e}"))? {
-        let path = entry.map_err(|e| format!("Dir entry error: {e}"))?.path();
-        if path.extension().and_then(|s| s.to_str()) != Some("out") {
-            continue;
-        }
-        let file_stem = path.file_stem().and_then(|s| s.to_str()).ok_or_else(|| format!("Non-UTF8 file name: {}", path.display()))?;
-        let (a_name, b_name) = file_stem.split_once("_vs_").ok_or_else(|| format!("Unexpected BLAST filename (need A_vs_B.out): {}", path.display()))?;
-        let i = *species_ids.get(a_name).ok_or_else(|| format!("Species not found in ID map: {a_name}"))?;
-        let j = *species_ids.get(b_name).ok_or_else(|| format!("Species not found in ID map: {b_name}"))?;
+    let mut written: Vec<(usize, usize)> = Vec::new();
+
+    for (a_name, b_name, path) in search_io::list_pair_files(&search_hits.dir, logger) {
+        let i = *species_ids.get(&a_name).ok_or_else(|| format!("Species not found in ID map: {a_name}"))?;
+        let j = *species_ids.get(&b_name).ok_or_else(|| format!("Species not found in ID map: {b_name}"))?;
 
         // Input/Output
-        let reader = open_bufread(&path, &logger, "rewrite_blast_files");
+        let reader = search_io::open_search_reader(&path, logger, "rewrite_blast_files");
         let out_path = of_blast.join(format!("Blast{}_{}.txt", i, j));
         let mut writer = open_bufwrite(&out_path, &logger, "rewrite_blast_files");
 
         for (lnum, line_res) in reader.lines().enumerate() {
             let line = line_res.map_err(|e| format!("Read error {}: {e}", path.display()))?;
-            if line.is_empty() || line.starts_with('#') { continue; }
+            if line.is_empty() || line.starts_with('#') || !search_hits.keeps(&line) { continue; }
             let mut cols: Vec<&str> = line.split('\t').collect();
             if cols.len() < 12 {
                 return Err(format!("Line {} in {} has <12 columns", lnum + 1, path.display()));
@@ -94,11 +88,26 @@ pub fn rewrite_blast_files(
             cols[0] = q_new;
             cols[1] = s_new;
 
+            // OrthoFinder expects the 12 standard columns (drop qlen/slen)
+            cols.truncate(12);
+
             // write back
             writer.write_all(cols.join("\t").as_bytes()).map_err(|e| format!("Write {}: {}", out_path.display(), e))?;
             writer.write_all(b"\n").map_err(|e| format!("Write {}: {}", out_path.display(), e))?;
         }
         writer.flush().map_err(|e| format!("Flush error {}: {e}", out_path.display()))?;
+        written.push((i, j));
+    }
+
+    // OrthoFinder expects every species pair, so pairs without search output (failed searches) are left empty
+    for (a_name, &i) in species_ids {
+        for (b_name, &j) in species_ids {
+            if !written.contains(&(i, j)) {
+                logger.warning(&format!("rewrite_blast_files: no search output for {} vs {}, writing an empty Blast{}_{}.txt", a_name, b_name, i, j));
+                let out_path = of_blast.join(format!("Blast{}_{}.txt", i, j));
+                fs::write(&out_path, "").map_err(|e| format!("Write {}: {}", out_path.display(), e))?;
+            }
+        }
     }
     Ok(())
 }
@@ -107,13 +116,13 @@ pub fn rewrite_blast_files(
 pub fn prepare_orthofinder_blast(
     repo: &[RepoEntry],
     alignment_type: &str,
-    blast_out_dir: &Path, 
+    search_hits: &SearchHits,
     orthofinder_out_dir: &Path, 
     logger: &Logger) -> Result<(), String> {
 
     // speciesID.txt
     logger.information(&format!("prepare_orthofinder_blast: generate species ids: {}", orthofinder_out_dir.display()));
-    let species_ids = generate_species_ids(blast_out_dir, orthofinder_out_dir, &logger)?;
+    let species_ids = generate_species_ids(&search_hits.dir, orthofinder_out_dir, &logger)?;
 
     // Process FASTAs -> Blast/Species<ID>.fa and SequenceIDs.txt, and build seq map
     logger.information(&format!("prepare_orthofinder_blast: rewrite FASTA files with species codes: {}", orthofinder_out_dir.display()));
@@ -121,7 +130,7 @@ pub fn prepare_orthofinder_blast(
 
     // Rewrite BLAST files using sequence map
     logger.information(&format!("prepare_orthofinder_blast: rewrite BLAST files with species codes: {}", orthofinder_out_dir.display()));
-    rewrite_blast_files(blast_out_dir, orthofinder_out_dir, &species_ids, &seq_id_map, &logger)?;
+    rewrite_blast_files(search_hits, orthofinder_out_dir, &species_ids, &seq_id_map, &logger)?;
 
    Ok(())
 }
@@ -215,4 +224,79 @@ pub fn harvest_orthogroups(log: &str, orthofinder_out_dir: &Path) -> Result<Path
         .map_err(|e| format!("copy {} -> {} failed: {}", src.display(), dst.display(), e))?;
 
     Ok(dst)
-}
\ No newline at end of file
+}
+
+/// Results of a full OrthoFinder run imported into `<orthofinder_out_dir>`: (path in the results directory, file name)
+const FULL_RUN_FILES: [(&str, &str); 3] = [
+    ("Phylogenetic_Hierarchical_Orthogroups/N0.tsv", "N0.tsv"),
+    ("Gene_Duplication_Events/Duplications.tsv", "Duplications.tsv"),
+    ("Species_Tree/SpeciesTree_rooted.txt", "SpeciesTree_rooted.txt"),
+];
+
+/// Remove results imported from an earlier full OrthoFinder run (before an -og only run)
+pub fn remove_full_results(orthofinder_out_dir: &Path) {
+    for (_, name) in FULL_RUN_FILES {
+        let _ = fs::remove_file(orthofinder_out_dir.join(name));
+    }
+    let _ = fs::remove_file(orthofinder_out_dir.join("Duplications_per_node.tsv"));
+}
+
+/// Harvest the hierarchical orthogroups (N0.tsv), gene duplication events and rooted species tree
+/// of a full OrthoFinder run into `<orthofinder_out_dir>`. Returns the copied files.
+pub fn harvest_full_results(log: &str, orthofinder_out_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let parsed = parse_results_dir_folded(log);
+    let results_dir = find_results_dir(orthofinder_out_dir, parsed)
+        .ok_or_else(|| "Could not resolve OrthoFinder results directory".to_string())?;
+
+    let mut copied = Vec::new();
+    for (rel, name) in FULL_RUN_FILES {
+        let src = results_dir.join(rel);
+        if !src.is_file() {
+            return Err(format!("Missing {}", src.display()));
+        }
+        let dst = orthofinder_out_dir.join(name);
+        fs::copy(&src, &dst)
+            .map_err(|e| format!("copy {} -> {} failed: {}", src.display(), dst.display(), e))?;
+        copied.push(dst);
+    }
+
+    Ok(copied)
+}
+
+/// Count OrthoFinder gene duplication events per species tree node (all, and with support >= 0.5),
+/// written to `<orthofinder_out_dir>/Duplications_per_node.tsv`
+pub fn summarise_duplications(orthofinder_out_dir: &Path, logger: &Logger) -> Result<PathBuf, String> {
+    let input_path = orthofinder_out_dir.join("Duplications.tsv");
+    let reader = open_bufread(&input_path, logger, "summarise_duplications");
+
+    // Orthogroup, Species Tree Node, Gene Tree Node, Support, Type, Genes 1, Genes 2
+    let mut per_node: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for (lnum, line_res) in reader.lines().enumerate() {
+        let line = line_res.map_err(|e| format!("Read error {}: {e}", input_path.display()))?;
+        if lnum == 0 || line.trim().is_empty() {
+            continue;
+        }
+        let cols: Vec<&str> = line.split('\t').collect();
+        if cols.len() < 4 {
+            return Err(format!("Line {} in {} has <4 columns", lnum + 1, input_path.display()));
+        }
+        let support: f64 = cols[3].trim().parse().unwrap_or(0.0);
+        let counts = per_node.entry(cols[1].to_string()).or_insert((0, 0));
+        counts.0 += 1;
+        if support >= 0.5 {
+            counts.1 += 1;
+        }
+    }
+
+    let out_path = orthofinder_out_dir.join("Duplications_per_node.tsv");
+    let mut writer = open_bufwrite(&out_path, logger, "summarise_duplications");
+    writeln!(writer, "species_tree_node\tduplications\tduplications_support_0.5").map_err(|e| format!("Write {}: {}", out_path.display(), e))?;
+    for (node, (all, supported)) in &per_node {
+        writeln!(writer, "{}\t{}\t{}", node, all, supported).map_err(|e| format!("Write {}: {}", out_path.display(), e))?;
+    }
+    writer.flush().map_err(|e| format!("Flush error {}: {e}", out_path.display()))?;
+
+    let total: usize = per_node.values().map(|(all, _)| all).sum();
+    logger.information(&format!("summarise_duplications: {} duplication events at {} species tree nodes", total, per_node.len()));
+    Ok(out_path)
+}
@@ -1,5 +1,6 @@
 use crate::logger::Logger;
 use crate::omcl;
+use crate::import_orthogroups;
 use crate::util::{mkdir, open_bufread, open_bufwrite};
 use crate::SynimaStep;
 use crate::read_gff;
@@ -10,12 +11,15 @@ use std::process;
 use std::collections::{HashMap, HashSet};
 use std::fs::{self};
 use std::io::{BufRead, Write};
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 pub enum OrthologySource {
     OrthoFinder(PathBuf),
     OrthoMcl(PathBuf),
     Rbh(PathBuf),
+    Mcl(PathBuf),
+    Synteny(PathBuf),
+    Imported(PathBuf),
 }
 
 impl OrthologySource {
@@ -24,6 +28,9 @@ impl OrthologySource {
             OrthologySource::OrthoFinder(_) => "orthofinder",
             OrthologySource::OrthoMcl(_)    => "orthomcl",
             OrthologySource::Rbh(_)         => "rbh",
+            OrthologySource::Mcl(_)         => "mcl",
+            OrthologySource::Synteny(_)     => "synteny",
+            OrthologySource::Imported(_)    => "imported",
         }
     }
 
@@ -41,6 +48,9 @@ pub enum OrthologyMethod {
     OrthoFinder,
     OrthoMcl,
     Rbh,
+    Mcl,
+    Synteny,
+    Imported,
 }
 
 impl OrthologyMethod {
@@ -49,6 +59,9 @@ impl OrthologyMethod {
             OrthologyMethod::OrthoFinder => "orthofinder",
             OrthologyMethod::OrthoMcl    => "orthomcl",
             OrthologyMethod::Rbh         => "rbh",
+            OrthologyMethod::Mcl         => "mcl",
+            OrthologyMethod::Synteny     => "synteny",
+            OrthologyMethod::Imported    => "imported",
         }
     }
 }
@@ -57,25 +70,46 @@ pub fn infer_preferred_method(steps: &[SynimaStep]) -> Option<OrthologyMethod> {
     steps.iter().find_map(|step| {
         match step {
             SynimaStep::BlastToRbh         => Some(OrthologyMethod::Rbh),
+            SynimaStep::BlastToMcl         => Some(OrthologyMethod::Mcl),
+            SynimaStep::BlastToSynteny     => Some(OrthologyMethod::Synteny),
             SynimaStep::BlastToOrthomcl    => Some(OrthologyMethod::OrthoMcl),
             SynimaStep::BlastToOrthofinder => Some(OrthologyMethod::OrthoFinder),
+            SynimaStep::ImportOrthogroups  => Some(OrthologyMethod::Imported),
             _ => None,
         }
     })
 }
 
+/// Output directories of the orthology steps
+pub struct OrthologyDirs {
+    pub orthofinder: PathBuf,
+    pub omcl: PathBuf,
+    pub rbh: PathBuf,
+    pub mcl: PathBuf,
+    pub synteny: PathBuf,
+    pub imported: PathBuf,
+}
+
 pub fn detect_orthology_source(
     preferred: Option<OrthologyMethod>,
-    orthofinder_out_dir: &Path,
-    omcl_out_dir: &Path,
-    rbh_out_dir: &Path,
+    dirs: &OrthologyDirs,
     logger: &Logger,
 ) -> OrthologySource {
 
+    let orthofinder_out_dir = dirs.orthofinder.as_path();
+    let omcl_out_dir = dirs.omcl.as_path();
+    let rbh_out_dir = dirs.rbh.as_path();
+    let mcl_out_dir = dirs.mcl.as_path();
+    let synteny_out_dir = dirs.synteny.as_path();
+    let imported_out_dir = dirs.imported.as_path();
+
     // Marker files for auto-detection
     let of_marker = orthofinder_out_dir.join("Orthogroups.tsv");
     let omcl_marker = omcl_out_dir.join("all_orthomcl.out");
     let rbh_marker = rbh_out_dir.join("all_vs_all.out.pairs.slclust.OrthoClusters");
+    let mcl_marker = mcl_out_dir.join("all_vs_all.mcl.OrthoClusters");
+    let synteny_marker = synteny_out_dir.join("all_vs_all.synteny.OrthoClusters");
+    let imported_marker = imported_out_dir.join(import_orthogroups::CLUSTERS_FILE);
 
     // 1. If the user explicitly ran rbh/orthomcl/orthofinder in this invocation,
     //    respect that first and fail loudly if its output is missing.
@@ -108,11 +142,38 @@ pub fn detect_orthology_source(
                     process::exit(1);
                 }
             }
+            OrthologyMethod::Mcl => {
+                if fs::metadata(&mcl_marker).is_ok() {
+                    logger.information(&format!("ortholog-summary: using MCL output at {}", mcl_marker.display()));
+                    return OrthologySource::Mcl(mcl_out_dir.to_path_buf());
+                } else {
+                    logger.error(&format!("ortholog-summary: MCL was requested but {} does not exist", mcl_marker.display()));
+                    process::exit(1);
+                }
+            }
+            OrthologyMethod::Synteny => {
+                if fs::metadata(&synteny_marker).is_ok() {
+                    logger.information(&format!("ortholog-summary: using synteny output at {}", synteny_marker.display()));
+                    return OrthologySource::Synteny(synteny_out_dir.to_path_buf());
+                } else {
+                    logger.error(&format!("ortholog-summary: synteny was requested but {} does not exist", synteny_marker.display()));
+                    process::exit(1);
+                }
+            }
+            OrthologyMethod::Imported => {
+                if fs::metadata(&imported_marker).is_ok() {
+                    logger.information(&format!("ortholog-summary: using imported orthogroups at {}", imported_marker.display()));
+                    return OrthologySource::Imported(imported_out_dir.to_path_buf());
+                } else {
+                    logger.error(&format!("ortholog-summary: imported orthogroups were requested but {} does not exist", imported_marker.display()));
+                    process::exit(1);
+                }
+            }
         }
     }
 
     // 2. No explicit method in this run: auto-detect in default priority:
-    //    OrthoFinder > OrthoMCL > RBH
+    //    OrthoFinder > OrthoMCL > MCL > synteny > RBH > imported
 
     // 1. OrthoFinder first
     if fs::metadata(&of_marker).is_ok() {
@@ -126,19 +187,40 @@ pub fn detect_orthology_source(
         return OrthologySource::OrthoMcl(omcl_out_dir.to_path_buf());
     }
 
-    // 3. RBH
+    // 3. MCL
+    if fs::metadata(&mcl_marker).is_ok() {
+        logger.information(&format!("ortholog-summary: using MCL output at {}", mcl_marker.display()));
+        return OrthologySource::Mcl(mcl_out_dir.to_path_buf());
+    }
+
+    // 4. Synteny
+    if fs::metadata(&synteny_marker).is_ok() {
+        logger.information(&format!("ortholog-summary: using synteny output at {}", synteny_marker.display()));
+        return OrthologySource::Synteny(synteny_out_dir.to_path_buf());
+    }
+
+    // 5. RBH
     if fs::metadata(&rbh_marker).is_ok() {
         logger.information(&format!("ortholog-summary: using RBH output at {}", rbh_marker.display()));
         return OrthologySource::Rbh(rbh_out_dir.to_path_buf());
     }
 
-    // 4. Nothing found - fail loudly
+    // 6. Imported orthogroups
+    if fs::metadata(&imported_marker).is_ok() {
+        logger.information(&format!("ortholog-summary: using imported orthogroups at {}", imported_marker.display()));
+        return OrthologySource::Imported(imported_out_dir.to_path_buf());
+    }
+
+    // 7. Nothing found - fail loudly
     logger.error(
         "ortholog-summary: could not find any orthology output.\n\
          Expected one of:\n\
          - Orthofinder at Orthogroups.tsv in orthofinder_out_dir\n\
          - OrthoMCL output in omcl_out_dir\n\
-         - RBH output in rbh_out_dir",
+         - MCL output in mcl_out_dir\n\
+         - synteny output in synteny_out_dir\n\
+         - RBH output in rbh_out_dir\n\
+         - imported orthogroups in imported_out_dir",
     );
     process::exit(1);
 }
@@ -152,13 +234,21 @@ pub fn from_orthofinder(
 
     logger.information(&format!("from_orthofinder: {}", alignment_type));
 
-    // 1. Locate Orthogroups.tsv
-    let input_path = orthofinder_dir.join("Orthogroups.tsv");
+    // 1. Locate the hierarchical orthogroups of a full run (N0.tsv), or Orthogroups.tsv
+    let hog_path = orthofinder_dir.join("N0.tsv");
+    let use_hogs = hog_path.is_file();
+    let input_path = if use_hogs { hog_path } else { orthofinder_dir.join("Orthogroups.tsv") };
     if !input_path.is_file() {
         logger.error(&format!("from_orthofinder: could not find {}", input_path.display()));
         process::exit(1);
     }
 
+    // N0.tsv starts with HOG, OG and Gene Tree Parent Clade columns; Orthogroups.tsv with Orthogroup
+    let first_genome_col = if use_hogs { 3 } else { 1 };
+    if use_hogs {
+        logger.information("from_orthofinder: using phylogenetic hierarchical orthogroups (N0.tsv)");
+    }
+
     // 2. Ensure output directory exists
     mkdir(&gene_clusters_out_dir, &logger, "from_orthofinder");
 
@@ -190,21 +280,20 @@ pub fn from_orthofinder(
     }
 
     if header_line.is_empty() {
-        logger.error("from_orthofinder: Orthogroups.tsv header line is empty");
+        logger.error(&format!("from_orthofinder: {} header line is empty", input_path.display()));
         process::exit(1);
     }
 
     let header_cols: Vec<&str> = header_line.split('\t').collect();
-    if header_cols.len() < 2 {
-        logger.error(&format!("from_orthofinder: header has fewer than 2 columns: {}", header_line));
+    if header_cols.len() < first_genome_col + 1 {
+        logger.error(&format!("from_orthofinder: header has fewer than {} columns: {}", first_genome_col + 1, header_line));
         process::exit(1);
     }
 
-    // First column is "Orthogroup"
-    // Remaining columns are genome names, which should match repo genome ids
-    let genome_headers: Vec<String> = header_cols[1..].iter().map(|s| s.to_string()).collect();
+    // Leading orthogroup columns, then genome names, which should match repo genome ids
+    let genome_headers: Vec<String> = header_cols[first_genome_col..].iter().map(|s| s.to_string()).collect();
 
-    logger.information(&format!("from_orthofinder: detected {} genomes in Orthogroups.tsv header",genome_headers.len()));
+    logger.information(&format!("from_orthofinder: detected {} genomes in {} header", genome_headers.len(), input_path.display()));
 
     // 6. Track which genes are clustered
     let mut clustered_genes: HashSet<(String, String)> = HashSet::new();
@@ -295,7 +384,7 @@ pub fn from_orthofinder(
 
         // For each genome column
         for (idx, genome_name) in genome_headers.iter().enumerate() {
-            let cell = cols[idx + 1].trim();
+            let cell = cols[idx + first_genome_col].trim();
             if cell.is_empty() {
                 continue;
             }
@@ -581,38 +670,93 @@ pub fn from_rbh(
 ) -> PathBuf {
 
     logger.information(&format!("from_rbh: {}", alignment_type));
-
-    // Input RBH clusters file
     let input_path = rbh_dir.join("all_vs_all.out.pairs.slclust.OrthoClusters");
+    from_ortho_clusters(&input_path, "rbh", alignment_type, gene_clusters_out_dir, all_genes, "from_rbh", logger)
+}
+
+pub fn from_mcl(
+    mcl_dir: &Path,
+    alignment_type: &str,
+    gene_clusters_out_dir: &Path,
+    all_genes: &HashMap<String, Vec<GffFeature>>,
+    logger: &Logger,
+) -> PathBuf {
+
+    logger.information(&format!("from_mcl: {}", alignment_type));
+    let input_path = mcl_dir.join("all_vs_all.mcl.OrthoClusters");
+    from_ortho_clusters(&input_path, "mcl", alignment_type, gene_clusters_out_dir, all_genes, "from_mcl", logger)
+}
+
+pub fn from_synteny(
+    synteny_dir: &Path,
+    alignment_type: &str,
+    gene_clusters_out_dir: &Path,
+    all_genes: &HashMap<String, Vec<GffFeature>>,
+    logger: &Logger,
+) -> PathBuf {
+
+    logger.information(&format!("from_synteny: {}", alignment_type));
+    let input_path = synteny_dir.join("all_vs_all.synteny.OrthoClusters");
+    from_ortho_clusters(&input_path, "synteny", alignment_type, gene_clusters_out_dir, all_genes, "from_synteny", logger)
+}
+
+pub fn from_imported(
+    imported_dir: &Path,
+    alignment_type: &str,
+    gene_clusters_out_dir: &Path,
+    all_genes: &HashMap<String, Vec<GffFeature>>,
+    logger: &Logger,
+) -> PathBuf {
+
+    logger.information(&format!("from_imported: {}", alignment_type));
+    let input_path = imported_dir.join(import_orthogroups::CLUSTERS_FILE);
+    from_ortho_clusters(&input_path, "imported", alignment_type, gene_clusters_out_dir, all_genes, "from_imported", logger)
+}
+
+/// Summarise a .OrthoClusters file (cluster id, Ortho/Inpara, genome, gene) as written by the rbh, mcl, synteny
+/// and import-orthogroups steps. A fifth column (syntenic/non-syntenic membership, synteny step) is kept, and
+/// uniques are then marked non-syntenic
+fn from_ortho_clusters(
+    input_path: &Path,
+    method_label: &str,
+    alignment_type: &str,
+    gene_clusters_out_dir: &Path,
+    all_genes: &HashMap<String, Vec<GffFeature>>,
+    context: &str,
+    logger: &Logger,
+) -> PathBuf {
+
+    // Input clusters file
     if !input_path.is_file() {
-        logger.error(&format!("from_rbh: could not find {}", input_path.display()));
+        logger.error(&format!("{}: could not find {}", context, input_path.display()));
         std::process::exit(1);
     }
 
     // Output directory
-    mkdir(&gene_clusters_out_dir, &logger, "from_rbh");
+    mkdir(&gene_clusters_out_dir, &logger, context);
 
     // Input/Output
-    let clusters_path = gene_clusters_out_dir.join(format!("GENE_CLUSTERS_SUMMARIES.{}.rbh.clusters", alignment_type));
-    let unique_path = gene_clusters_out_dir.join(format!("GENE_CLUSTERS_SUMMARIES.{}.rbh.unique", alignment_type));
-    let clusters_and_unique = gene_clusters_out_dir.join(format!("GENE_CLUSTERS_SUMMARIES.{}.rbh.clusters_and_uniques", alignment_type));
+    let clusters_path = gene_clusters_out_dir.join(format!("GENE_CLUSTERS_SUMMARIES.{}.{}.clusters", alignment_type, method_label));
+    let unique_path = gene_clusters_out_dir.join(format!("GENE_CLUSTERS_SUMMARIES.{}.{}.unique", alignment_type, method_label));
+    let clusters_and_unique = gene_clusters_out_dir.join(format!("GENE_CLUSTERS_SUMMARIES.{}.{}.clusters_and_uniques", alignment_type, method_label));
 
-    let reader = open_bufread(&input_path, &logger, "from_rbh");
-    let mut clusters_writer = open_bufwrite(&clusters_path, &logger, "from_rbh");
-    let mut combined_writer = open_bufwrite(&clusters_and_unique, &logger, "from_rbh");
+    let reader = open_bufread(input_path, &logger, context);
+    let mut clusters_writer = open_bufwrite(&clusters_path, &logger, context);
+    let mut combined_writer = open_bufwrite(&clusters_and_unique, &logger, context);
 
-    logger.information(&format!("from_rbh: reading {}", input_path.display()));
-    logger.information(&format!("from_rbh: writing clusters to {}", clusters_path.display()));
-    logger.information(&format!("from_rbh: writing uniques to {}", unique_path.display()));
-    logger.information(&format!("from_rbh: writing combined clusters+uniques to {}", clusters_and_unique.display()));
+    logger.information(&format!("{}: reading {}", context, input_path.display()));
+    logger.information(&format!("{}: writing clusters to {}", context, clusters_path.display()));
+    logger.information(&format!("{}: writing uniques to {}", context, unique_path.display()));
+    logger.information(&format!("{}: writing combined clusters+uniques to {}", context, clusters_and_unique.display()));
 
     // Track which genes are used in clusters
     let mut clustered_genes: HashSet<(String, String)> = HashSet::new();
+    let mut has_membership = false;
 
     // Helper to flush one RBH group to the clusters file
     let mut next_cluster_id: u64 = 0;
-    let mut group: Vec<(String, String)> = Vec::new(); // (genome, gene_id)
-    let mut flush_group = |group: &mut Vec<(String, String)>| {
+    let mut group: Vec<(String, String, String)> = Vec::new(); // (genome, gene_id, extra columns)
+    let mut flush_group = |group: &mut Vec<(String, String, String)>| {
 
         // need at least 2 genes to be a cluster
         if group.len() < 2 {
@@ -630,27 +774,27 @@ pub fn from_rbh(
         let label = format!("{:07}", next_cluster_id);
         next_cluster_id += 1;
 
-        for (genome, gene_id) in group.iter() {
-            // cluster label, genome, "Ortho", gene_id
-            let line = format!("{label}\t{genome}\tOrtho\t{gene_id}");
+        for (genome, gene_id, extra) in group.iter() {
+            // cluster label, genome, "Ortho", gene_id (, membership)
+            let line = format!("{label}\t{genome}\tOrtho\t{gene_id}{extra}");
 
             if let Err(e) = writeln!(clusters_writer, "{line}") {
-                logger.error(&format!("from_rbh: write error (clusters): {}", e));
+                logger.error(&format!("{}: write error (clusters): {}", context, e));
                 process::exit(1);
             }
             if let Err(e) = writeln!(combined_writer, "{line}") {
-                logger.error(&format!("from_rbh: write error (clusters_and_uniques): {}", e));
+                logger.error(&format!("{}: write error (clusters_and_uniques): {}", context, e));
                 process::exit(1);
             }
         }
 
         // extra blank line between groups
         if let Err(e) = writeln!(clusters_writer) {
-            logger.error(&format!("from_rbh: write error (clusters spacer): {}", e));
+            logger.error(&format!("{}: write error (clusters spacer): {}", context, e));
             process::exit(1);
         }
         if let Err(e) = writeln!(combined_writer) {
-            logger.error(&format!("from_rbh: write error (combined spacer): {}", e));
+            logger.error(&format!("{}: write error (combined spacer): {}", context, e));
             process::exit(1);
         }
 
@@ -662,7 +806,7 @@ pub fn from_rbh(
         let line = match line_res {
             Ok(l) => l,
             Err(e) => {
-                logger.error(&format!("from_rbh: read error in {}: {}", input_path.display(), e));
+                logger.error(&format!("{}: read error in {}: {}", context, input_path.display(), e));
                 process::exit(1);
             }
         };
@@ -676,23 +820,30 @@ pub fn from_rbh(
 
         let cols: Vec<&str> = trimmed.split('\t').collect();
         if cols.len() < 4 {
-            logger.error(&format!("from_rbh: expected at least 4 columns in {}, got {}: {}", input_path.display(), cols.len(), trimmed));
+            logger.error(&format!("{}: expected at least 4 columns in {}, got {}: {}", context, input_path.display(), cols.len(), trimmed));
             std::process::exit(1);
         }
 
-        // cols[0] is the original RBH cluster id, cols[1] is "Ortho"
+        // cols[0] is the original RBH cluster id, cols[1] is "Ortho", cols[4] the synteny membership
         let genome = cols[2].to_string();
         let gene_id = cols[3].to_string();
+        let extra = match cols.get(4) {
+            Some(membership) => {
+                has_membership = true;
+                format!("\t{}", membership)
+            }
+            None => String::new(),
+        };
 
         clustered_genes.insert((genome.clone(), gene_id.clone()));
-        group.push((genome, gene_id));
+        group.push((genome, gene_id, extra));
     }
 
     // Flush last group if file does not end with a blank line
     flush_group(&mut group);
 
     // Now write uniques
-    let mut unique_writer = open_bufwrite(&unique_path, &logger, "from_rbh");
+    let mut unique_writer = open_bufwrite(&unique_path, &logger, context);
 
     // Collect all genome -> gene_id from GffFeature
     let mut all_pairs: Vec<(String, String)> = Vec::new();
@@ -715,19 +866,23 @@ pub fn from_rbh(
         let label = format!("uniq_{}", uniq_counter);
         uniq_counter += 1;
         
-        let line = format!("{label}\t{genome}\tOrtho\t{gene_id}");
+        let line = if has_membership {
+            format!("{label}\t{genome}\tOrtho\t{gene_id}\tnon-syntenic")
+        } else {
+            format!("{label}\t{genome}\tOrtho\t{gene_id}")
+        };
 
         if let Err(e) = writeln!(unique_writer, "{line}") {
-            logger.error(&format!("from_rbh: write error (unique): {}", e));
+            logger.error(&format!("{}: write error (unique): {}", context, e));
             process::exit(1);
         }
         if let Err(e) = writeln!(combined_writer, "{line}") {
-            logger.error(&format!("from_rbh: write error (clusters_and_uniques): {}", e));
+            logger.error(&format!("{}: write error (clusters_and_uniques): {}", context, e));
             process::exit(1);
         }
     }
 
-    logger.information(&format!("from_rbh: wrote {} cluster groups and {} unique genes", next_cluster_id, uniq_counter.saturating_sub(1)));
+    logger.information(&format!("{}: wrote {} cluster groups and {} unique genes", context, next_cluster_id, uniq_counter.saturating_sub(1)));
 
     clusters_and_unique
 }
@@ -858,4 +1013,32 @@ pub fn write_cluster_dist_per_genome(
     }
 
     logger.information(&format!("cluster_dist_per_genome: wrote {} clusters for {} genomes to {}", cluster_to_genome_count.len(), genome_list.len(), output_path.display()));
-}
\ No newline at end of file
+}
+
+/// Members of each cluster in a clusters_and_uniques file: cluster_id -> (genome, gene_id),
+/// in file order. Uniques are single-member clusters.
+pub fn read_cluster_members(combined_clusters_path: &Path, logger: &Logger) -> BTreeMap<String, Vec<(String, String)>> {
+
+    let reader = open_bufread(combined_clusters_path, logger, "read_cluster_members");
+    let mut members: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+
+    for line_res in reader.lines() {
+        let line = line_res.unwrap_or_else(|e| {
+            logger.error(&format!("read_cluster_members: read error in {}: {}", combined_clusters_path.display(), e));
+            process::exit(1);
+        });
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        // cluster_id, genome, "Ortho", gene_id (, synteny membership)
+        let cols: Vec<&str> = trimmed.split('\t').collect();
+        if cols.len() < 4 {
+            logger.error(&format!("read_cluster_members: expected at least 4 columns, got {}: {}", cols.len(), trimmed));
+            process::exit(1);
+        }
+        members.entry(cols[0].to_string()).or_default().push((cols[1].to_string(), cols[3].to_string()));
+    }
+    members
+}
@@ -1,5 +1,7 @@
 use crate::logger::Logger;
+use crate::plot::{self, Anchor, Plot};
 use crate::util::{open_bufwrite};
+use crate::Args;
 
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
@@ -7,10 +9,82 @@ use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf}; // 
 use std::process::Command;
 
-pub fn write_cluster_dist_stats_and_plot(cluster_counts_file: &Path, _output_dir: &Path, logger: &Logger) {
+/// Orthogroup classes in the .summary file, in column order
+pub const CLASSES: [&str; 6] = ["core_1to1", "core_multi", "soft_core", "aux", "cloud", "unique"];
+
+/// Colours and legend labels of the classes, as in the Orthologs tab of Synima.html
+pub fn class_style(class: &str) -> (&'static str, &'static str) {
+    match class {
+        "core_1to1" => (plot::SET2[0], "Core (1:1)"),
+        "core_multi" => (plot::SET2[1], "Core (multi)"),
+        "soft_core" => (plot::SET2[4], "Soft-core"),
+        "aux" => (plot::SET2[2], "Aux"),
+        "cloud" => (plot::SET2[5], "Cloud"),
+        _ => (plot::SET2[3], "Unique"),
+    }
+}
+
+/// Percentage of genomes an orthogroup must be in to be core, soft-core or shell (aux).
+/// Orthogroups in fewer genomes are cloud, and those in a single genome unique.
+#[derive(Debug, Clone)]
+pub struct ClassThresholds {
+    pub core: f64,
+    pub soft_core: f64,
+    pub cloud: f64,
+}
+
+impl ClassThresholds {
+    pub fn from_args(args: &Args) -> Self {
+        ClassThresholds {
+            core: args.core_threshold,
+            soft_core: args.soft_core_threshold.unwrap_or(args.core_threshold),
+            cloud: args.cloud_threshold,
+        }
+    }
+
+    /// Smallest number of the `n` genomes that is at least `percent` of them
+    pub fn min_genomes(percent: f64, n: usize) -> usize {
+        ((percent * n as f64 / 100.0) - 1e-9).ceil().max(0.0) as usize
+    }
+
+    /// Class of an orthogroup present in `present` of `n` genomes, with one gene in each if `single_copy`
+    pub fn classify(&self, present: usize, n: usize, single_copy: bool) -> &'static str {
+        if present == 1 && n > 1 {
+            "unique"
+        } else if present >= Self::min_genomes(self.core, n).max(1) {
+            if single_copy { "core_1to1" } else { "core_multi" }
+        } else if present >= Self::min_genomes(self.soft_core, n) {
+            "soft_core"
+        } else if present >= Self::min_genomes(self.cloud, n) {
+            "aux"
+        } else {
+            "cloud"
+        }
+    }
+
+    /// Classes that can occur with these thresholds (soft-core and cloud only when enabled)
+    pub fn active_classes(&self) -> Vec<&'static str> {
+        CLASSES
+            .iter()
+            .copied()
+            .filter(|c| match *c {
+                "soft_core" => self.soft_core < self.core,
+                "cloud" => self.cloud > 0.0,
+                _ => true,
+            })
+            .collect()
+    }
+
+    /// "#thresholds" line of the .summary file
+    pub fn summary_line(&self) -> String {
+        format!("#thresholds\tcore={}\tsoft_core={}\tcloud={}", self.core, self.soft_core, self.cloud)
+    }
+}
+
+pub fn write_cluster_dist_stats_and_plot(cluster_counts_file: &Path, _output_dir: &Path, thresholds: &ClassThresholds, logger: &Logger) {
 
     // First produce the *.summary file (and single copy ortholog count) and get its path
-    let summary_path = write_cluster_dist_summary(cluster_counts_file, logger);
+    let summary_path = write_cluster_dist_summary(cluster_counts_file, thresholds, logger);
 
     logger.information(&format!("write_cluster_dist_stats_and_plot: reading {}", cluster_counts_file.display()));
 
@@ -42,10 +116,7 @@ pub fn write_cluster_dist_stats_and_plot(cluster_counts_file: &Path, _output_dir
     }
 
     let mut genomes: Vec<String> = Vec::new();
-    let mut core1_counts: Vec<u64> = Vec::new();
-    let mut corem_counts: Vec<u64> = Vec::new();
-    let mut aux_counts: Vec<u64> = Vec::new();
-    let mut uniq_counts: Vec<u64> = Vec::new();
+    let mut class_counts: Vec<Vec<u64>> = vec![Vec::new(); CLASSES.len()];
 
     // Parse each line in summary
     for line_res in lines {
@@ -67,71 +138,81 @@ pub fn write_cluster_dist_stats_and_plot(cluster_counts_file: &Path, _output_dir
         }
 
         let cols: Vec<&str> = trimmed.split('\t').collect();
-        if cols.len() != 5 {
+        if cols.len() != 1 + CLASSES.len() {
             logger.error(&format!("write_cluster_dist_stats_and_plot: skipping malformed summary line: {}", trimmed));
             continue
         }
 
-        let genome = cols[0].to_string();
-        let core1 = cols[1].parse::<u64>().unwrap_or(0);
-        let corem = cols[2].parse::<u64>().unwrap_or(0);
-        let aux   = cols[3].parse::<u64>().unwrap_or(0);
-        let uniq  = cols[4].parse::<u64>().unwrap_or(0);
-
-        genomes.push(genome);
-        core1_counts.push(core1);
-        corem_counts.push(corem);
-        aux_counts.push(aux);
-        uniq_counts.push(uniq);
+        genomes.push(cols[0].to_string());
+        for (c, counts) in class_counts.iter_mut().enumerate() {
+            counts.push(cols[1 + c].parse::<u64>().unwrap_or(0));
+        }
     }
 
     // Log what we are about to plot
-    logger.information("write_cluster_dist_stats_and_plot: values going into R plot:");
-    for i in 0..genomes.len() {
-        let g = &genomes[i];
-        let c1 = core1_counts[i];
-        let cm = corem_counts[i];
-        let a  = aux_counts[i];
-        let u  = uniq_counts[i];
-        let total = c1 + cm + a + u;
-
-        logger.information(&format!("  {}: core_1to1={}, core_multi={}, aux={}, unique={}, total={}", g, c1, cm, a, u, total));
+    logger.information("write_cluster_dist_stats_and_plot: values going into plot:");
+    for (i, g) in genomes.iter().enumerate() {
+        let values: Vec<String> = CLASSES.iter().zip(&class_counts).map(|(class, counts)| format!("{}={}", class, counts[i])).collect();
+        let total: u64 = class_counts.iter().map(|counts| counts[i]).sum();
+        logger.information(&format!("  {}: {}, total={}", g, values.join(", "), total));
     }
 
+    // Stacked bar chart as SVG and PNG, then the same chart with ggplot2 if R is installed
+    let active = thresholds.active_classes();
+    let class_names: Vec<&str> = active
+        .iter()
+        .filter_map(|c| match *c {
+            "core_1to1" => Some("core (1:1 and multi)"),
+            "soft_core" => Some("soft-core"),
+            "aux" => Some("accessory"),
+            "cloud" => Some("cloud"),
+            _ => None,
+        })
+        .collect();
+    let title = format!("Distribution of {}, and unique genes", class_names.join(", "));
+
+    let svg_path = summary_path.with_extension("summary_plot.svg");
+    let png_path = summary_path.with_extension("summary_plot.png");
+    let active_counts: Vec<(&str, &Vec<u64>)> = CLASSES.iter().copied().zip(&class_counts).filter(|(class, _)| active.contains(class)).collect();
+    cluster_dist_plot(&genomes, &active_counts, &title).write_svg_and_png(&svg_path, &png_path, 3.0, logger);
+    logger.information(&format!("write_cluster_dist_stats_and_plot: cluster distribution plot written to {} and {}", svg_path.display(), png_path.display()));
+
     // Open ggplot2 Rscript that makes a PDF
     let rscript_path = summary_path.with_extension("summary_plot.R");
     let mut rscript_writer = open_bufwrite(&rscript_path, &logger, "write_cluster_dist_stats_and_plot");
     logger.information(&format!("write_cluster_dist_stats_and_plot: writing R script to {}", rscript_path.display()));
 
-    // Write Rscript
-    let core1_str = core1_counts.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ");
-    let corem_str = corem_counts.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ");
-    let aux_str = aux_counts.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", ");
-    let uniq_str = uniq_counts.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", ");
+    // Write Rscript (classes that the thresholds cannot produce are left out)
+    let class_vectors = CLASSES
+        .iter()
+        .zip(&class_counts)
+        .filter(|(class, _)| active.contains(class))
+        .map(|(class, counts)| format!("{}_counts <- c({})", class, counts.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let classes_str = active.iter().map(|c| format!("\"{}\"", c)).collect::<Vec<_>>().join(", ");
+    let counts_str = active.iter().map(|c| format!("{}_counts", c)).collect::<Vec<_>>().join(", ");
     let genomes_str = genomes.iter().map(|g| format!("'{}'", g)).collect::<Vec<_>>().join(", ");
 
     //let pdf_path = summary_path.join("cluster_dist.pdf");
     let pdf_path = summary_path.with_extension("summary_plot.pdf");
-    let png_path = summary_path.with_extension("summary_plot.png");
+    let r_png_path = summary_path.with_extension("summary_plot.ggplot.png");
     let pdf_path_str = pdf_path.to_string_lossy().replace('\\', "/");
-    let png_path_str = png_path.to_string_lossy().replace('\\', "/");
+    let png_path_str = r_png_path.to_string_lossy().replace('\\', "/");
 
     let r_code = format!(
-        r#"core1_counts <- c({core1})
-corem_counts <- c({corem})
-aux_counts <- c({aux})
-uniq_counts <- c({uniq})
+        r##"{class_vectors}
 genomes <- c({genomes})
 
 library(ggplot2)
 
 df <- data.frame(
-  genome = rep(genomes, times = 4),
+  genome = rep(genomes, times = {n_classes}),
   class = factor(
-    rep(c("core_1to1", "core_multi", "aux", "unique"), each = length(genomes)),
-    levels = c("core_1to1", "core_multi", "aux", "unique")
+    rep(c({classes}), each = length(genomes)),
+    levels = c({classes})
   ),
-  count  = c(core1_counts, corem_counts, aux_counts, uniq_counts)
+  count  = c({counts})
 )
 
 p <- ggplot(df, aes(x = genome, y = count, fill = class)) +
@@ -145,13 +226,15 @@ p <- ggplot(df, aes(x = genome, y = count, fill = class)) +
     legend.position = "right"
   ) +
   labs(
-    title = "Distribution of core (1:1 and multi), accessory, and unique genes",
+    title = "{title}",
     x = "Genome Assembly",
     y = "Number of genes",
     fill = "Class"
   ) +
-  scale_fill_brewer(
-    palette = "Set2",
+  scale_fill_manual(
+    # Set2 colours, as in the Orthologs tab of Synima.html
+    values = c(core_1to1 = "#66C2A5", core_multi = "#FC8D62", soft_core = "#A6D854",
+               aux = "#8DA0CB", cloud = "#FFD92F", unique = "#E78AC3"),
     guide = guide_legend(reverse = TRUE)
   )
 
@@ -162,12 +245,13 @@ plot = p, width = 7, height = 5)
 # PNG output
 ggsave(filename = "{png}", 
 plot = p, width = 7, height = 5, dpi = 300)
-"#,
-        core1 = core1_str,
-        corem = corem_str,
-        aux = aux_str,
-        uniq = uniq_str,
+"##,
+        class_vectors = class_vectors,
         genomes = genomes_str,
+        n_classes = active.len(),
+        classes = classes_str,
+        counts = counts_str,
+        title = title,
         pdf = pdf_path_str,
         png = png_path_str
     );
@@ -178,27 +262,90 @@ plot = p, width = 7, height = 5, dpi = 300)
     }
     drop(rscript_writer);
 
-    // Finally, try to run Rscript. If not available, just warn and continue.
-    logger.information("write_cluster_dist_stats_and_plot: checking for Rscript to generate PDF and PNG");
+    // Finally, try to run Rscript. If not available, the SVG and PNG above are enough.
+    logger.information("write_cluster_dist_stats_and_plot: checking for Rscript to generate PDF and ggplot2 PNG");
     let status = Command::new("Rscript").arg(&rscript_path).status();
 
     match status {
         Ok(s) if s.success() => {
-            logger.information(&format!("write_cluster_dist_stats_and_plot: cluster distribution plot written to {}", pdf_path.display()));
+            logger.information(&format!("write_cluster_dist_stats_and_plot: ggplot2 cluster distribution plot written to {}", pdf_path.display()));
         }
         Ok(s) => {
-            logger.warning(&format!("write_cluster_dist_stats_and_plot: Rscript exited with status {}. Skipping plot.", s));
+            logger.warning(&format!("write_cluster_dist_stats_and_plot: Rscript exited with status {}. Skipping ggplot2 plot.", s));
         }
         Err(e) => {
-            logger.warning(&format!("write_cluster_dist_stats_and_plot: Rscript not found or failed to start ({}). Skipping plot.", e));
+            logger.information(&format!("write_cluster_dist_stats_and_plot: Rscript not found or failed to start ({}). Skipping ggplot2 plot.", e));
         }
     }
 }
 
+/// Stacked bar chart of the number of genes of each class per genome (first class at the bottom)
+fn cluster_dist_plot(genomes: &[String], class_counts: &[(&str, &Vec<u64>)], title: &str) -> Plot {
+
+    const FONT: f64 = 12.0;
+    const BAR: f64 = 28.0;
+    const SLOT: f64 = 40.0;
+    const PLOT_H: f64 = 320.0;
+
+    let longest = genomes.iter().map(|g| Plot::text_width(g, FONT)).fold(0.0, f64::max);
+    let legend_w = 30.0 + class_counts.iter().map(|(c, _)| Plot::text_width(class_style(c).1, FONT)).fold(Plot::text_width("Class", FONT), f64::max);
+    let (left, top) = (80.0, 50.0);
+    let bottom = 30.0 + longest + 30.0;
+    let plot_w = genomes.len().max(1) as f64 * SLOT;
+    let width = (left + plot_w + 30.0 + legend_w + 20.0).max(left + Plot::text_width(title, FONT * 1.2) + 20.0);
+    let height = top + PLOT_H + bottom;
+
+    let totals: Vec<u64> = (0..genomes.len()).map(|i| class_counts.iter().map(|(_, counts)| counts[i]).sum()).collect();
+    let max_total = totals.iter().copied().max().unwrap_or(0).max(1) as f64;
+    let step = plot::nice_step(max_total / 5.0);
+    let axis_max = (max_total / step).ceil() * step;
+    let y_of = |v: f64| top + PLOT_H - v / axis_max * PLOT_H;
+
+    let mut p = Plot::new(width, height);
+    p.text(left, 28.0, title, FONT * 1.2, Anchor::Start);
+
+    // grid and y axis
+    let mut v = 0.0;
+    while v <= axis_max + 1e-9 {
+        p.line(left, y_of(v), left + plot_w, y_of(v), "#E5E5E5", 1.0);
+        p.text(left - 6.0, y_of(v) + FONT * 0.35, &plot::tick_label(v), FONT, Anchor::End);
+        v += step;
+    }
+    p.vertical_text(24.0, top + PLOT_H / 2.0, "Number of genes", FONT, Anchor::Middle);
+
+    // stacked bars
+    for (i, genome) in genomes.iter().enumerate() {
+        let x = left + i as f64 * SLOT + (SLOT - BAR) / 2.0;
+        let mut base = 0.0;
+        for (class, counts) in class_counts {
+            let count = counts[i] as f64;
+            if count > 0.0 {
+                p.rect(x, y_of(base + count), BAR, y_of(base) - y_of(base + count), class_style(class).0);
+            }
+            base += count;
+        }
+        p.vertical_text(x + BAR / 2.0 + FONT * 0.35, top + PLOT_H + 8.0, genome, FONT, Anchor::End);
+    }
+    p.line(left, top + PLOT_H, left + plot_w, top + PLOT_H, "#111111", 1.0);
+    p.line(left, top, left, top + PLOT_H, "#111111", 1.0);
+    p.text(left + plot_w / 2.0, height - 10.0, "Genome Assembly", FONT, Anchor::Middle);
+
+    // legend, last class first (as stacked)
+    let lx = left + plot_w + 30.0;
+    p.text(lx, top + FONT, "Class", FONT, Anchor::Start);
+    for (k, (class, _)) in class_counts.iter().rev().enumerate() {
+        let (colour, label) = class_style(class);
+        let y = top + FONT + 10.0 + k as f64 * (FONT + 8.0);
+        p.rect(lx, y, FONT + 2.0, FONT + 2.0, colour);
+        p.text(lx + FONT + 8.0, y + FONT, label, FONT, Anchor::Start);
+    }
+    p
+}
+
 /// Read GENE_CLUSTERS_SUMMARIES.*.cluster_dist_per_genome.txt
 /// and write GENE_CLUSTERS_SUMMARIES.*.cluster_dist_per_genome.summary.
 /// Returns the path of the summary file.
-fn write_cluster_dist_summary(cluster_counts_file: &Path, logger: &Logger) -> PathBuf {
+fn write_cluster_dist_summary(cluster_counts_file: &Path, thresholds: &ClassThresholds, logger: &Logger) -> PathBuf {
 
     logger.information(&format!("write_cluster_dist_summary: reading {}", cluster_counts_file.display()));
 
@@ -299,22 +446,9 @@ fn write_cluster_dist_summary(cluster_counts_file: &Path, logger: &Logger) -> Pa
             counts_for_cluster.push((genome.clone(), count));
         }
 
-        let counts_gt_zero: Vec<&(String, u64)> = counts_for_cluster.iter().filter(|(_, c)| *c > 0).collect();
-
-        let classification = if !counts_for_cluster.is_empty() && counts_gt_zero.len() == counts_for_cluster.len() {
-            // CORE (present in all genomes)
-            let all_one = counts_for_cluster.iter().all(|(_, c)| *c == 1);
-
-            if all_one {
-                "core_1to1"
-            } else {
-                "core_multi"
-            }
-        } else if counts_gt_zero.len() == 1 {
-            "unique"
-        } else {
-            "aux"
-        };
+        let present = counts_for_cluster.iter().filter(|(_, c)| *c > 0).count();
+        let single_copy = counts_for_cluster.iter().all(|(_, c)| *c <= 1);
+        let classification = thresholds.classify(present, counts_for_cluster.len(), single_copy);
 
         for (genome, count) in counts_for_cluster {
             if count == 0 {
@@ -334,26 +468,27 @@ fn write_cluster_dist_summary(cluster_counts_file: &Path, logger: &Logger) -> Pa
     let mut summary_writer = open_bufwrite(&summary_path, &logger, "write_cluster_dist_summary");
     logger.information(&format!("write_cluster_dist_summary: writing summary to {}",summary_path.display()));
 
-    // Write summary file: one line per genome with core / aux / unique counts
-    if let Err(e) = writeln!(summary_writer, "#genome\tcore_1to1\tcore_multi\taux\tunique") {
+    // Write summary file: one line per genome with the counts of each class, then the thresholds
+    if let Err(e) = writeln!(summary_writer, "#genome\t{}\n{}", CLASSES.join("\t"), thresholds.summary_line()) {
         logger.error(&format!("write_cluster_dist_summary: write error (summary header): {}", e));
         std::process::exit(1);
     }
 
     // Also log the values we are going to use for plotting
-    logger.information("write_cluster_dist_summary: per genome counts (core + aux + unique):");
+    logger.information(&format!(
+        "write_cluster_dist_summary: per genome counts (core >= {}%, soft-core >= {}%, aux >= {}% of genomes):",
+        thresholds.core, thresholds.soft_core, thresholds.cloud
+    ));
 
     for genome in &genomes {
         let class_counts_opt = genome_to_class_count.get(genome);
-        let core_1to1 = class_counts_opt.and_then(|m| m.get("core_1to1")).copied().unwrap_or(0);
-        let core_multi = class_counts_opt.and_then(|m| m.get("core_multi")).copied().unwrap_or(0);
-        let aux = class_counts_opt.and_then(|m| m.get("aux")).copied().unwrap_or(0);
-        let uniq = class_counts_opt.and_then(|m| m.get("unique")).copied().unwrap_or(0);
-        let total = core_1to1 + core_multi + aux + uniq;
+        let counts: Vec<u64> = CLASSES.iter().map(|class| class_counts_opt.and_then(|m| m.get(*class)).copied().unwrap_or(0)).collect();
+        let values: Vec<String> = CLASSES.iter().zip(&counts).map(|(class, c)| format!("{}={}", class, c)).collect();
 
-        logger.information(&format!("  {}: core_1to1={}, core_multi={}, aux={}, unique={}, total={}", genome, core_1to1, core_multi, aux, uniq, total));
+        logger.information(&format!("  {}: {}, total={}", genome, values.join(", "), counts.iter().sum::<u64>()));
 
-        if let Err(e) = writeln!(summary_writer, "{}\t{}\t{}\t{}\t{}", genome, core_1to1, core_multi , aux, uniq) {
+        let row: Vec<String> = counts.iter().map(|c| c.to_string()).collect();
+        if let Err(e) = writeln!(summary_writer, "{}\t{}", genome, row.join("\t")) {
             logger.error(&format!("write_cluster_dist_summary: write error (summary row): {}", e));
             std::process::exit(1);
         }
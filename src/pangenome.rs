@@ -0,0 +1,356 @@
+use crate::logger::Logger;
+use crate::plot::{self, Anchor, Plot};
+use crate::Args;
+use crate::util::{open_bufread, open_bufwrite};
+
+use rayon::prelude::*;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Settings for the pan/core-genome accumulation curves
+#[derive(Debug, Clone)]
+pub struct PangenomeParams {
+    pub iterations: usize, // random genome orders
+    pub seed: u64,
+}
+
+impl PangenomeParams {
+    pub fn from_args(args: &Args) -> Self {
+        PangenomeParams {
+            iterations: args.pangenome_iterations,
+            seed: args.pangenome_seed,
+        }
+    }
+}
+
+/// Small seeded generator (SplitMix64), so curves are reproducible without an extra dependency
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Fisher-Yates shuffle
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// Summary of one curve at each number of genomes sampled
+#[derive(Debug, Clone)]
+pub struct CurveStats {
+    pub mean: f64,
+    pub sd: f64,
+    pub min: usize,
+    pub max: usize,
+}
+
+fn curve_stats(values: &[usize]) -> CurveStats {
+    let n = values.len().max(1) as f64;
+    let mean = values.iter().sum::<usize>() as f64 / n;
+    let var = values.iter().map(|&v| (v as f64 - mean).powi(2)).sum::<f64>() / n;
+    CurveStats {
+        mean,
+        sd: var.sqrt(),
+        min: values.iter().copied().min().unwrap_or(0),
+        max: values.iter().copied().max().unwrap_or(0),
+    }
+}
+
+/// Power law y = kappa * N^exponent, fitted by least squares on log-log values
+#[derive(Debug, Clone)]
+pub struct PowerLawFit {
+    pub kappa: f64,
+    pub exponent: f64,
+    pub r_squared: f64,
+}
+
+fn fit_power_law(points: &[(f64, f64)]) -> Option<PowerLawFit> {
+    let logs: Vec<(f64, f64)> = points.iter().filter(|(x, y)| *x > 0.0 && *y > 0.0).map(|(x, y)| (x.ln(), y.ln())).collect();
+    if logs.len() < 2 {
+        return None;
+    }
+    let n = logs.len() as f64;
+    let mx = logs.iter().map(|p| p.0).sum::<f64>() / n;
+    let my = logs.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = logs.iter().map(|p| (p.0 - mx).powi(2)).sum();
+    let sxy: f64 = logs.iter().map(|p| (p.0 - mx) * (p.1 - my)).sum();
+    let syy: f64 = logs.iter().map(|p| (p.1 - my).powi(2)).sum();
+    if sxx == 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    let r_squared = if syy > 0.0 { (sxy * sxy) / (sxx * syy) } else { 1.0 };
+    Some(PowerLawFit { kappa: (my - slope * mx).exp(), exponent: slope, r_squared })
+}
+
+/// Pan, core and new-cluster counts for every number of genomes sampled (1..=n)
+pub struct PangenomeCurves {
+    pub pan: Vec<CurveStats>,
+    pub core: Vec<CurveStats>,
+    pub new: Vec<CurveStats>,
+    pub heaps: Option<PowerLawFit>,    // new clusters per added genome: n = kappa * N^-alpha
+    pub pan_fit: Option<PowerLawFit>,  // pan-genome size: P = kappa * N^gamma
+}
+
+impl PangenomeCurves {
+    /// Heaps' law alpha (new clusters decay as N^-alpha)
+    pub fn alpha(&self) -> Option<f64> {
+        self.heaps.as_ref().map(|f| -f.exponent)
+    }
+
+    /// Open if new clusters keep appearing (alpha <= 1), closed otherwise (Tettelin et al. 2008)
+    pub fn openness(&self) -> &'static str {
+        match self.alpha() {
+            Some(a) if a <= 1.0 => "open",
+            Some(_) => "closed",
+            None => "NA",
+        }
+    }
+}
+
+/// Read GENE_CLUSTERS_SUMMARIES.*.cluster_dist_per_genome.txt into genomes and,
+/// per cluster, the genomes (column indices) it is present in
+fn read_cluster_presence(cluster_counts_file: &Path, logger: &Logger) -> (Vec<String>, Vec<Vec<usize>>) {
+
+    let reader = open_bufread(cluster_counts_file, logger, "read_cluster_presence");
+    let mut genomes: Vec<String> = Vec::new();
+    let mut presence: Vec<Vec<usize>> = Vec::new();
+
+    // "#genome=count..." totals, then "#cluster_id\tname\tgenome1\t...", then one row per cluster
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.unwrap_or_else(|e| {
+            logger.error(&format!("read_cluster_presence: error reading {}: {}", cluster_counts_file.display(), e));
+            std::process::exit(1);
+        });
+        if i == 0 || line.trim().is_empty() {
+            continue;
+        }
+        let cols: Vec<&str> = line.trim_end().split('\t').collect();
+        if i == 1 {
+            genomes = cols.iter().skip(2).map(|s| s.to_string()).collect();
+            continue;
+        }
+        if cols.len() < 2 + genomes.len() {
+            logger.error(&format!("read_cluster_presence: expected at least {} columns, got {}: {}", 2 + genomes.len(), cols.len(), line));
+            std::process::exit(1);
+        }
+        let present: Vec<usize> = (0..genomes.len()).filter(|&g| cols[2 + g].parse::<u64>().unwrap_or(0) > 0).collect();
+        if !present.is_empty() {
+            presence.push(present);
+        }
+    }
+    (genomes, presence)
+}
+
+/// Pan, core and new-cluster counts after each genome of one random order
+type OrderCounts = (Vec<usize>, Vec<usize>, Vec<usize>);
+
+/// Pan/core-genome accumulation over random orders of `n` genomes.
+///
+/// For each order, the pan-genome after N genomes is the number of clusters (including
+/// uniques) present in any of the first N genomes, and the core the number present in
+/// all of them. Each order uses its own generator seeded from `seed`, so results do not
+/// depend on the number of threads.
+pub fn accumulation_curves(n: usize, presence: &[Vec<usize>], params: &PangenomeParams) -> PangenomeCurves {
+
+    let runs: Vec<OrderCounts> = (0..params.iterations.max(1))
+        .into_par_iter()
+        .map(|iteration| {
+            let mut rng = SplitMix64(params.seed ^ (iteration as u64).wrapping_mul(0xA24B_AED4_963E_E407));
+            let mut order: Vec<usize> = (0..n).collect();
+            rng.shuffle(&mut order);
+            let mut rank = vec![0usize; n];
+            for (r, &g) in order.iter().enumerate() {
+                rank[g] = r;
+            }
+
+            // a cluster enters the pan-genome at its first genome, and stays core until its first missing genome
+            let mut first_in = vec![0usize; n + 1];
+            let mut core_until = vec![0usize; n + 1];
+            for present in presence {
+                let mut ranks: Vec<usize> = present.iter().map(|&g| rank[g]).collect();
+                ranks.sort_unstable();
+                first_in[ranks[0]] += 1;
+                let run = ranks.iter().enumerate().take_while(|(i, &r)| *i == r).count();
+                core_until[run] += 1;
+            }
+
+            let mut pan = Vec::with_capacity(n);
+            let mut core = Vec::with_capacity(n);
+            let mut pan_total = 0usize;
+            let mut core_total: usize = core_until.iter().sum();
+            for k in 0..n {
+                pan_total += first_in[k];
+                core_total -= core_until[k];
+                pan.push(pan_total);
+                core.push(core_total);
+            }
+            (pan, core, first_in[..n].to_vec())
+        })
+        .collect();
+
+    let column = |pick: &dyn Fn(&OrderCounts) -> usize| -> Vec<usize> { runs.iter().map(pick).collect() };
+    let pan: Vec<CurveStats> = (0..n).map(|k| curve_stats(&column(&|r| r.0[k]))).collect();
+    let core: Vec<CurveStats> = (0..n).map(|k| curve_stats(&column(&|r| r.1[k]))).collect();
+    let new: Vec<CurveStats> = (0..n).map(|k| curve_stats(&column(&|r| r.2[k]))).collect();
+
+    // new clusters are fitted from the second genome on (the first adds all of its clusters)
+    let heaps = fit_power_law(&new.iter().enumerate().skip(1).map(|(k, s)| ((k + 1) as f64, s.mean)).collect::<Vec<_>>());
+    let pan_fit = fit_power_law(&pan.iter().enumerate().map(|(k, s)| ((k + 1) as f64, s.mean)).collect::<Vec<_>>());
+
+    PangenomeCurves { pan, core, new, heaps, pan_fit }
+}
+
+/// Pan-genome and core genome means against the number of genomes, with their range over
+/// the random orders as vertical bars
+fn pangenome_plot(curves: &PangenomeCurves) -> Plot {
+
+    const FONT: f64 = 12.0;
+    const PLOT_W: f64 = 480.0;
+    const PLOT_H: f64 = 320.0;
+    let (left, top) = (80.0, 50.0);
+    let series = [("Pan-genome", &curves.pan, plot::SET2[1]), ("Core genome", &curves.core, plot::SET2[0])];
+    let width = left + PLOT_W + 30.0 + Plot::text_width("Core genome", FONT) + FONT + 30.0;
+    let height = top + PLOT_H + 60.0;
+
+    let n = curves.pan.len().max(1);
+    let max_y = curves.pan.iter().map(|s| s.max).max().unwrap_or(0).max(1) as f64;
+    let step_y = plot::nice_step(max_y / 5.0);
+    let axis_max = (max_y / step_y).ceil() * step_y;
+    let x_of = |k: usize| left + if n > 1 { (k as f64) / (n - 1) as f64 * PLOT_W } else { PLOT_W / 2.0 };
+    let y_of = |v: f64| top + PLOT_H - v / axis_max * PLOT_H;
+
+    let mut p = Plot::new(width, height);
+    p.text(left, 28.0, &format!("Pan/core-genome accumulation ({} pan-genome)", curves.openness()), FONT * 1.2, Anchor::Start);
+
+    let mut v = 0.0;
+    while v <= axis_max + 1e-9 {
+        p.line(left, y_of(v), left + PLOT_W, y_of(v), "#E5E5E5", 1.0);
+        p.text(left - 6.0, y_of(v) + FONT * 0.35, &plot::tick_label(v), FONT, Anchor::End);
+        v += step_y;
+    }
+    let step_x = plot::nice_step(n as f64 / 10.0).max(1.0) as usize;
+    for k in (0..n).filter(|k| (k + 1) % step_x == 0 || *k == 0) {
+        p.line(x_of(k), top + PLOT_H, x_of(k), top + PLOT_H + 4.0, "#111111", 1.0);
+        p.text(x_of(k), top + PLOT_H + 6.0 + FONT, &(k + 1).to_string(), FONT, Anchor::Middle);
+    }
+    p.line(left, top + PLOT_H, left + PLOT_W, top + PLOT_H, "#111111", 1.0);
+    p.line(left, top, left, top + PLOT_H, "#111111", 1.0);
+    p.text(left + PLOT_W / 2.0, height - 10.0, "Number of genomes", FONT, Anchor::Middle);
+    p.vertical_text(24.0, top + PLOT_H / 2.0, "Number of orthogroups", FONT, Anchor::Middle);
+
+    for (k, (label, stats, colour)) in series.iter().enumerate() {
+        for (i, s) in stats.iter().enumerate() {
+            p.line(x_of(i), y_of(s.min as f64), x_of(i), y_of(s.max as f64), colour, 1.0);
+            if i > 0 {
+                p.line(x_of(i - 1), y_of(stats[i - 1].mean), x_of(i), y_of(s.mean), colour, 2.0);
+            }
+            p.rect(x_of(i) - 2.5, y_of(s.mean) - 2.5, 5.0, 5.0, colour);
+        }
+        let ly = top + FONT + k as f64 * (FONT + 8.0);
+        p.rect(left + PLOT_W + 30.0, ly - FONT, FONT, FONT, colour);
+        p.text(left + PLOT_W + 36.0 + FONT, ly, label, FONT, Anchor::Start);
+    }
+    p
+}
+
+/// Write the curves (*.pangenome_curves.tsv, plotted in .pangenome_curves.svg/.png) and
+/// Heaps' law fit (*.pangenome_heaps.tsv) next to the cluster distribution file. Returns the curves file.
+pub fn write_pangenome_curves(cluster_counts_file: &Path, params: &PangenomeParams, logger: &Logger) -> PathBuf {
+
+    logger.information(&format!("write_pangenome_curves: reading {}", cluster_counts_file.display()));
+    let (genomes, presence) = read_cluster_presence(cluster_counts_file, logger);
+    let curves = accumulation_curves(genomes.len(), &presence, params);
+
+    let curves_path = cluster_counts_file.with_extension("pangenome_curves.tsv");
+    let heaps_path = cluster_counts_file.with_extension("pangenome_heaps.tsv");
+
+    let mut writer = open_bufwrite(&curves_path, logger, "write_pangenome_curves");
+    let mut lines = vec!["#genomes\tpan_mean\tpan_sd\tpan_min\tpan_max\tcore_mean\tcore_sd\tcore_min\tcore_max\tnew_mean\tnew_sd".to_string()];
+    for k in 0..genomes.len() {
+        let (p, c, n) = (&curves.pan[k], &curves.core[k], &curves.new[k]);
+        lines.push(format!(
+            "{}\t{:.2}\t{:.2}\t{}\t{}\t{:.2}\t{:.2}\t{}\t{}\t{:.2}\t{:.2}",
+            k + 1, p.mean, p.sd, p.min, p.max, c.mean, c.sd, c.min, c.max, n.mean, n.sd
+        ));
+    }
+    if let Err(e) = writeln!(writer, "{}", lines.join("\n")) {
+        logger.error(&format!("write_pangenome_curves: write error {}: {}", curves_path.display(), e));
+        std::process::exit(1);
+    }
+
+    let fmt = |v: Option<f64>| v.map(|x| format!("{:.4}", x)).unwrap_or_else(|| "NA".to_string());
+    let heaps_rows = [
+        ("genomes", genomes.len().to_string()),
+        ("clusters", presence.len().to_string()),
+        ("iterations", params.iterations.max(1).to_string()),
+        ("seed", params.seed.to_string()),
+        ("heaps_alpha", fmt(curves.alpha())),
+        ("heaps_kappa", fmt(curves.heaps.as_ref().map(|f| f.kappa))),
+        ("heaps_r_squared", fmt(curves.heaps.as_ref().map(|f| f.r_squared))),
+        ("pan_gamma", fmt(curves.pan_fit.as_ref().map(|f| f.exponent))),
+        ("pan_kappa", fmt(curves.pan_fit.as_ref().map(|f| f.kappa))),
+        ("openness", curves.openness().to_string()),
+    ];
+    let mut writer = open_bufwrite(&heaps_path, logger, "write_pangenome_curves");
+    for (key, value) in heaps_rows {
+        if let Err(e) = writeln!(writer, "{}\t{}", key, value) {
+            logger.error(&format!("write_pangenome_curves: write error {}: {}", heaps_path.display(), e));
+            std::process::exit(1);
+        }
+    }
+
+    let svg_path = cluster_counts_file.with_extension("pangenome_curves.svg");
+    let png_path = cluster_counts_file.with_extension("pangenome_curves.png");
+    pangenome_plot(&curves).write_svg_and_png(&svg_path, &png_path, 3.0, logger);
+
+    logger.information(&format!(
+        "write_pangenome_curves: {} genomes, {} iterations, Heaps' law alpha {} ({} pan-genome), written to {}",
+        genomes.len(), params.iterations.max(1), fmt(curves.alpha()), curves.openness(), curves_path.display()
+    ));
+    curves_path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn means(stats: &[CurveStats]) -> Vec<f64> {
+        stats.iter().map(|s| s.mean).collect()
+    }
+
+    #[test]
+    fn curves_on_three_genomes() {
+        // one core cluster and one unique cluster per genome: every order gives the same counts
+        let presence = vec![vec![0, 1, 2], vec![0], vec![1], vec![2]];
+        let curves = accumulation_curves(3, &presence, &PangenomeParams { iterations: 10, seed: 42 });
+        assert_eq!(means(&curves.pan), vec![2.0, 3.0, 4.0]);
+        assert_eq!(means(&curves.core), vec![2.0, 1.0, 1.0]);
+        assert_eq!(means(&curves.new), vec![2.0, 1.0, 1.0]);
+        assert!(curves.pan.iter().chain(&curves.core).all(|s| s.sd == 0.0 && s.min == s.max));
+    }
+
+    #[test]
+    fn curves_depend_on_order() {
+        // genome 1 has 3 clusters, genomes 0 and 2 have 2
+        let presence = vec![vec![0, 1, 2], vec![0, 1], vec![2], vec![1]];
+        let params = PangenomeParams { iterations: 100, seed: 7 };
+        let curves = accumulation_curves(3, &presence, &params);
+        assert_eq!((curves.pan[0].min, curves.pan[0].max), (2, 3));
+        assert_eq!((curves.core[1].min, curves.core[1].max), (1, 2));
+        // all genomes: 4 clusters, 1 in every genome
+        assert_eq!((curves.pan[2].min, curves.pan[2].max), (4, 4));
+        assert_eq!((curves.core[2].min, curves.core[2].max), (1, 1));
+        // seeded orders give the same curves
+        assert_eq!(means(&accumulation_curves(3, &presence, &params).pan), means(&curves.pan));
+    }
+}
@@ -0,0 +1,287 @@
+use crate::logger::Logger;
+use crate::util::open_bufwrite;
+
+use embedded_graphics::mono_font::iso_8859_1::{FONT_10X20, FONT_6X10, FONT_8X13};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::{Baseline, Text};
+use std::io::Write;
+use std::path::Path;
+
+/// Set2 colours, as used by the charts in Synima.html
+pub const SET2: [&str; 8] = ["#66C2A5", "#FC8D62", "#8DA0CB", "#E78AC3", "#A6D854", "#FFD92F", "#E5C494", "#B3B3B3"];
+
+const TEXT_COLOUR: &str = "#111111";
+
+/// Horizontal alignment of a text label relative to its x coordinate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+#[derive(Debug, Clone)]
+enum Shape {
+    Rect { x: f64, y: f64, w: f64, h: f64, fill: String },
+    Line { x1: f64, y1: f64, x2: f64, y2: f64, stroke: String, width: f64 },
+    Text(Label),
+}
+
+#[derive(Debug, Clone)]
+struct Label {
+    x: f64,
+    y: f64,
+    text: String,
+    size: f64,
+    anchor: Anchor,
+    vertical: bool,
+}
+
+/// A chart built from rectangles, lines and text, written as SVG and rendered to PNG
+/// without R or any system library. Coordinates are SVG pixels, and text is positioned
+/// on its baseline. Vertical text reads bottom to top.
+#[derive(Debug, Clone)]
+pub struct Plot {
+    pub width: f64,
+    pub height: f64,
+    shapes: Vec<Shape>,
+}
+
+impl Plot {
+    pub fn new(width: f64, height: f64) -> Self {
+        Plot { width, height, shapes: Vec::new() }
+    }
+
+    pub fn rect(&mut self, x: f64, y: f64, w: f64, h: f64, fill: &str) {
+        self.shapes.push(Shape::Rect { x, y, w, h, fill: fill.to_string() });
+    }
+
+    pub fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, stroke: &str, width: f64) {
+        self.shapes.push(Shape::Line { x1, y1, x2, y2, stroke: stroke.to_string(), width });
+    }
+
+    pub fn text(&mut self, x: f64, y: f64, text: &str, size: f64, anchor: Anchor) {
+        self.shapes.push(Shape::Text(Label { x, y, text: text.to_string(), size, anchor, vertical: false }));
+    }
+
+    pub fn vertical_text(&mut self, x: f64, y: f64, text: &str, size: f64, anchor: Anchor) {
+        self.shapes.push(Shape::Text(Label { x, y, text: text.to_string(), size, anchor, vertical: true }));
+    }
+
+    /// Approximate width of a label, for laying out margins
+    pub fn text_width(text: &str, size: f64) -> f64 {
+        text.chars().count() as f64 * size * 0.65
+    }
+
+    pub fn to_svg(&self) -> String {
+        let escape = |s: &str| s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;");
+        let mut out = vec![
+            format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+                w = self.width, h = self.height
+            ),
+            format!("<rect x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"#ffffff\"/>", self.width, self.height),
+        ];
+        for shape in &self.shapes {
+            out.push(match shape {
+                Shape::Rect { x, y, w, h, fill } => {
+                    format!("<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"{}\"/>", x, y, w, h, fill)
+                }
+                Shape::Line { x1, y1, x2, y2, stroke, width } => format!(
+                    "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"square\"/>",
+                    x1, y1, x2, y2, stroke, width
+                ),
+                Shape::Text(Label { x, y, text, size, anchor, vertical }) => {
+                    let anchor = match anchor {
+                        Anchor::Start => "start",
+                        Anchor::Middle => "middle",
+                        Anchor::End => "end",
+                    };
+                    let rotate = if *vertical { format!(" transform=\"rotate(-90 {:.2} {:.2})\"", x, y) } else { String::new() };
+                    format!(
+                        "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"sans-serif\" font-size=\"{}\" text-anchor=\"{}\" fill=\"{}\"{}>{}</text>",
+                        x, y, size, anchor, TEXT_COLOUR, rotate, escape(text)
+                    )
+                }
+            });
+        }
+        out.push("</svg>".to_string());
+        out.join("\n")
+    }
+
+    /// Rasterise at `scale` pixels per SVG pixel (text uses built-in bitmap fonts)
+    pub fn to_rgb(&self, scale: f64) -> (u32, u32, Vec<u8>) {
+        let mut canvas = Canvas::new((self.width * scale).ceil() as u32, (self.height * scale).ceil() as u32);
+        for shape in &self.shapes {
+            match shape {
+                Shape::Rect { x, y, w, h, fill } => {
+                    canvas.fill_rect(x * scale, y * scale, w * scale, h * scale, parse_colour(fill));
+                }
+                Shape::Line { x1, y1, x2, y2, stroke, width } => {
+                    canvas.stroke_line(x1 * scale, y1 * scale, x2 * scale, y2 * scale, (width * scale).max(1.0), parse_colour(stroke));
+                }
+                Shape::Text(label) => {
+                    canvas.draw_text(label, scale);
+                }
+            }
+        }
+        (canvas.width, canvas.height, canvas.pixels)
+    }
+
+    /// Write the SVG, and the PNG at `scale` times its size
+    pub fn write_svg_and_png(&self, svg_path: &Path, png_path: &Path, scale: f64, logger: &Logger) {
+
+        let mut svg_writer = open_bufwrite(svg_path, logger, "write_svg_and_png");
+        if let Err(e) = writeln!(svg_writer, "{}", self.to_svg()) {
+            logger.error(&format!("write_svg_and_png: write error {}: {}", svg_path.display(), e));
+            std::process::exit(1);
+        }
+
+        let (width, height, pixels) = self.to_rgb(scale);
+        let png_writer = open_bufwrite(png_path, logger, "write_svg_and_png");
+        let mut encoder = png::Encoder::new(png_writer, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let result = encoder.write_header().and_then(|mut writer| writer.write_image_data(&pixels));
+        if let Err(e) = result {
+            logger.error(&format!("write_svg_and_png: failed to write PNG {}: {}", png_path.display(), e));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Round an axis step up to 1, 2 or 5 times a power of ten
+pub fn nice_step(raw: f64) -> f64 {
+    if raw <= 0.0 || !raw.is_finite() {
+        return 1.0;
+    }
+    let power = 10f64.powf(raw.log10().floor());
+    let fraction = raw / power;
+    let nice = if fraction <= 1.0 { 1.0 } else if fraction <= 2.0 { 2.0 } else if fraction <= 5.0 { 5.0 } else { 10.0 };
+    nice * power
+}
+
+/// Format an axis tick (no decimals for whole numbers)
+pub fn tick_label(value: f64) -> String {
+    if (value - value.round()).abs() < 1e-9 {
+        format!("{}", value.round() as i64)
+    } else {
+        format!("{}", (value * 1000.0).round() / 1000.0)
+    }
+}
+
+fn parse_colour(colour: &str) -> [u8; 3] {
+    let hex = colour.trim_start_matches('#');
+    let channel = |i: usize| hex.get(i..i + 2).and_then(|h| u8::from_str_radix(h, 16).ok()).unwrap_or(0);
+    if hex.len() == 6 { [channel(0), channel(2), channel(4)] } else { [0, 0, 0] }
+}
+
+/// RGB raster the shapes are drawn into
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Self {
+        Canvas { width, height, pixels: vec![255; (width * height * 3) as usize] }
+    }
+
+    fn set(&mut self, x: i64, y: i64, colour: [u8; 3]) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let i = ((y as u32 * self.width + x as u32) * 3) as usize;
+        self.pixels[i..i + 3].copy_from_slice(&colour);
+    }
+
+    fn fill_rect(&mut self, x: f64, y: f64, w: f64, h: f64, colour: [u8; 3]) {
+        let (x0, x1) = (x.min(x + w).round() as i64, x.max(x + w).round() as i64);
+        let (y0, y1) = (y.min(y + h).round() as i64, y.max(y + h).round() as i64);
+        for py in y0..y1 {
+            for px in x0..x1 {
+                self.set(px, py, colour);
+            }
+        }
+    }
+
+    /// Thick line: squares of the line width stamped every half pixel along it
+    fn stroke_line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, width: f64, colour: [u8; 3]) {
+        let steps = ((x2 - x1).hypot(y2 - y1) * 2.0).ceil().max(1.0) as usize;
+        for s in 0..=steps {
+            let t = s as f64 / steps as f64;
+            let (cx, cy) = (x1 + (x2 - x1) * t, y1 + (y2 - y1) * t);
+            self.fill_rect(cx - width / 2.0, cy - width / 2.0, width, width, colour);
+        }
+    }
+
+    /// Text in the largest bitmap font (and integer magnification) that fits the requested
+    /// size and the width allowed for by `Plot::text_width`
+    fn draw_text(&mut self, label: &Label, scale: f64) {
+        let (x, y, size) = (label.x * scale, label.y * scale, label.size * scale);
+        let max_advance = Plot::text_width("x", size);
+        let (font, magnify) = [&FONT_6X10, &FONT_8X13, &FONT_10X20]
+            .iter()
+            .flat_map(|f| (1..=8).map(move |m| (*f, m)))
+            .filter(|(f, m)| ((f.character_size.width + f.character_spacing) * m) as f64 <= max_advance && (f.character_size.height * m) as f64 <= size * 1.15)
+            .max_by_key(|(f, m)| f.character_size.height * m)
+            .unwrap_or((&FONT_6X10, 1));
+
+        let advance = (font.character_size.width + font.character_spacing) * magnify;
+        let length = (label.text.chars().count() as u32 * advance) as f64;
+        let offset = match label.anchor {
+            Anchor::Start => 0.0,
+            Anchor::Middle => -length / 2.0,
+            Anchor::End => -length,
+        };
+
+        let mut target = TextTarget { canvas: self, x: x.round() as i64, y: y.round() as i64, offset: offset.round() as i64, magnify: magnify as i64, vertical: label.vertical };
+        let style = MonoTextStyle::new(font, Rgb888::BLACK);
+        let _ = Text::with_baseline(&label.text, Point::zero(), style, Baseline::Alphabetic).draw(&mut target);
+    }
+}
+
+/// Draw target placing magnified (and optionally rotated) glyph pixels on the canvas
+struct TextTarget<'a> {
+    canvas: &'a mut Canvas,
+    x: i64,
+    y: i64,
+    offset: i64,
+    magnify: i64,
+    vertical: bool,
+}
+
+impl OriginDimensions for TextTarget<'_> {
+    fn size(&self) -> Size {
+        Size::new(u32::MAX / 2, u32::MAX / 2)
+    }
+}
+
+impl DrawTarget for TextTarget<'_> {
+    type Color = Rgb888;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, _) in pixels {
+            let along = self.offset + point.x as i64 * self.magnify;
+            let across = point.y as i64 * self.magnify;
+            for dy in 0..self.magnify {
+                for dx in 0..self.magnify {
+                    let (px, py) = if self.vertical {
+                        (self.x + across + dy, self.y - along - dx)
+                    } else {
+                        (self.x + along + dx, self.y + across + dy)
+                    };
+                    self.canvas.set(px, py, parse_colour(TEXT_COLOUR));
+                }
+            }
+        }
+        Ok(())
+    }
+}
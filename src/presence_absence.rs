@@ -0,0 +1,275 @@
+use crate::logger::Logger;
+use crate::ortholog_summary;
+use crate::read_gff;
+use crate::read_gff::GffFeature;
+use crate::read_repo;
+use crate::RepoEntry;
+use crate::util::open_bufwrite;
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Roary gene_presence_absence.csv columns before the per-genome columns
+const ROARY_COLUMNS: [&str; 14] = [
+    "Gene",
+    "Non-unique Gene name",
+    "Annotation",
+    "No. isolates",
+    "No. sequences",
+    "Avg sequences per isolate",
+    "Genome Fragment",
+    "Order within Fragment",
+    "Accessory Fragment",
+    "Accessory Order with Fragment",
+    "QC",
+    "Min group size nuc",
+    "Max group size nuc",
+    "Avg group size nuc",
+];
+
+/// Gene name and product of one gene, from the attributes of the input GFF
+#[derive(Debug, Clone, Default)]
+pub struct GeneAnnotation {
+    pub name: Option<String>,
+    pub product: Option<String>,
+}
+
+/// Decode %XX escapes in GFF3 attribute values (e.g. %2C for ',')
+fn decode_gff_value(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(b) = value.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Attributes holding text rather than identifiers
+const TEXT_ATTRIBUTES: [&str; 5] = ["gene", "gene_name", "product", "description", "Note"];
+
+/// Gene names and products for each genome, keyed by every identifier of a feature, so
+/// they can be found from the gene IDs chosen by create-repo. A feature without a name or
+/// product takes it from its children (e.g. the product of a gene's CDS), then from its
+/// parents. Only GFF annotations have products; gene tables and BED files give none.
+pub fn read_gene_annotations(repo: &[RepoEntry], logger: &Logger) -> HashMap<String, HashMap<String, GeneAnnotation>> {
+
+    let mut annotations: HashMap<String, HashMap<String, GeneAnnotation>> = HashMap::new();
+    for entry in repo {
+        if entry.name == "synima_all" {
+            continue;
+        }
+        let Some(("gff", gff_file)) = read_repo::get_annotation_file(entry) else {
+            continue;
+        };
+
+        let features = read_gff::save_features(Path::new(&gff_file.path), logger);
+        let own: Vec<GeneAnnotation> = features
+            .iter()
+            .map(|f| {
+                let attr = |keys: &[&str]| keys.iter().find_map(|k| f.attributes.get(*k)).map(|v| decode_gff_value(v));
+                GeneAnnotation { name: attr(&["gene", "gene_name"]), product: attr(&["product", "description", "Note"]) }
+            })
+            .collect();
+
+        // feature hierarchy from ID/Parent
+        let mut by_id: HashMap<&str, usize> = HashMap::new();
+        for (i, f) in features.iter().enumerate() {
+            if let Some(id) = f.attributes.get("ID") {
+                by_id.entry(id.as_str()).or_insert(i);
+            }
+        }
+        let parents: Vec<Vec<usize>> = features
+            .iter()
+            .map(|f| f.attributes.get("Parent").map(|p| p.split(',').filter_map(|id| by_id.get(id).copied()).collect()).unwrap_or_default())
+            .collect();
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); features.len()];
+        for (i, ps) in parents.iter().enumerate() {
+            for &p in ps {
+                children[p].push(i);
+            }
+        }
+
+        let genome_annotations = annotations.entry(entry.name.clone()).or_default();
+        for (i, f) in features.iter().enumerate() {
+
+            // own attributes, then descendants, then ancestors (breadth first)
+            let mut resolved = own[i].clone();
+            for links in [&children, &parents] {
+                let mut queue: VecDeque<usize> = links[i].iter().copied().collect();
+                let mut seen: HashSet<usize> = HashSet::from([i]);
+                while let Some(j) = queue.pop_front() {
+                    if resolved.name.is_some() && resolved.product.is_some() {
+                        break;
+                    }
+                    if !seen.insert(j) {
+                        continue;
+                    }
+                    resolved.name = resolved.name.or_else(|| own[j].name.clone());
+                    resolved.product = resolved.product.or_else(|| own[j].product.clone());
+                    queue.extend(links[j].iter().copied());
+                }
+            }
+            if resolved.name.is_none() && resolved.product.is_none() {
+                continue;
+            }
+
+            for (key, value) in &f.attributes {
+                if TEXT_ATTRIBUTES.contains(&key.as_str()) || key == "Parent" {
+                    continue;
+                }
+                for id in value.split(',') {
+                    genome_annotations.entry(id.to_string()).or_insert_with(|| resolved.clone());
+                }
+            }
+        }
+    }
+    annotations
+}
+
+/// Most frequent value (ties broken alphabetically)
+fn consensus<'a>(values: impl Iterator<Item = &'a str>) -> Option<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for v in values {
+        *counts.entry(v).or_insert(0) += 1;
+    }
+    counts.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0))).map(|(v, _)| v.to_string())
+}
+
+fn csv_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// Write a Roary-compatible orthogroup x genome matrix from a clusters_and_uniques file:
+///
+/// * `<prefix>.gene_presence_absence.csv`: gene IDs per genome (paralogs tab separated),
+///   consensus gene name and product, and orthogroup size in nucleotides
+/// * `<prefix>.gene_presence_absence.Rtab`: 1/0 presence/absence
+/// * `<prefix>.gene_copy_number.Rtab`: number of genes per genome
+///
+/// Orthogroups are named by their cluster ID and sorted by the number of genomes they occur
+/// in, as Roary does, so the files can be given to Scoary and other tools that read Roary output.
+pub fn write_presence_absence(
+    combined_clusters_path: &Path,                      // GENE_CLUSTERS_SUMMARIES.*.clusters_and_uniques
+    out_dir: &Path,
+    prefix: &str,                                       // GENE_CLUSTERS_SUMMARIES.<type>.<method>
+    all_features: &HashMap<String, Vec<GffFeature>>,    // parsed GFF (gene coordinates)
+    annotations: &HashMap<String, HashMap<String, GeneAnnotation>>,
+    logger: &Logger,
+) -> PathBuf {
+
+    logger.information(&format!("write_presence_absence: reading {}", combined_clusters_path.display()));
+    let members = ortholog_summary::read_cluster_members(combined_clusters_path, logger);
+
+    // gene lengths in nucleotides, from the parsed GFF
+    let mut gene_length: HashMap<(String, String), usize> = HashMap::new();
+    for (genome, features) in all_features {
+        for feature in features {
+            let gene_id = read_gff::extract_gene_id_from_attributes(feature, logger);
+            gene_length.insert((genome.clone(), gene_id), feature.end.saturating_sub(feature.start) + 1);
+        }
+    }
+
+    let genomes: Vec<String> = all_features
+        .keys()
+        .cloned()
+        .chain(members.values().flatten().map(|(g, _)| g.clone()))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    // most widespread orthogroups first
+    let mut clusters: Vec<(&String, &Vec<(String, String)>)> = members.iter().collect();
+    let isolates = |genes: &Vec<(String, String)>| genes.iter().map(|(g, _)| g).collect::<BTreeSet<_>>().len();
+    clusters.sort_by(|a, b| isolates(b.1).cmp(&isolates(a.1)).then(b.1.len().cmp(&a.1.len())).then(a.0.cmp(b.0)));
+
+    let csv_path = out_dir.join(format!("{}.gene_presence_absence.csv", prefix));
+    let rtab_path = out_dir.join(format!("{}.gene_presence_absence.Rtab", prefix));
+    let copy_path = out_dir.join(format!("{}.gene_copy_number.Rtab", prefix));
+    let mut csv_writer = open_bufwrite(&csv_path, logger, "write_presence_absence");
+    let mut rtab_writer = open_bufwrite(&rtab_path, logger, "write_presence_absence");
+    let mut copy_writer = open_bufwrite(&copy_path, logger, "write_presence_absence");
+
+    let header: Vec<String> = ROARY_COLUMNS.iter().map(|c| c.to_string()).chain(genomes.iter().cloned()).collect();
+    let mut csv_lines = vec![header.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(",")];
+    let mut rtab_lines = vec![format!("Gene\t{}", genomes.join("\t"))];
+    let mut copy_lines = rtab_lines.clone();
+
+    for (cluster_id, genes) in clusters {
+        let mut per_genome: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (genome, gene_id) in genes {
+            per_genome.entry(genome.as_str()).or_default().push(gene_id.as_str());
+        }
+
+        let gene_annotations: Vec<&GeneAnnotation> = genes
+            .iter()
+            .filter_map(|(genome, gene_id)| annotations.get(genome).and_then(|a| a.get(gene_id)))
+            .collect();
+        let name = consensus(gene_annotations.iter().filter_map(|a| a.name.as_deref())).unwrap_or_default();
+        let product = consensus(gene_annotations.iter().filter_map(|a| a.product.as_deref())).unwrap_or_else(|| "hypothetical protein".to_string());
+
+        let lengths: Vec<usize> = genes.iter().filter_map(|(g, id)| gene_length.get(&(g.clone(), id.clone())).copied()).collect();
+        let (min_len, max_len, avg_len) = if lengths.is_empty() {
+            (String::new(), String::new(), String::new())
+        } else {
+            let avg = lengths.iter().sum::<usize>() as f64 / lengths.len() as f64;
+            (lengths.iter().min().unwrap().to_string(), lengths.iter().max().unwrap().to_string(), format!("{:.0}", avg))
+        };
+
+        let n_isolates = per_genome.len();
+        let mut row: Vec<String> = vec![
+            cluster_id.clone(),
+            name,
+            product,
+            n_isolates.to_string(),
+            genes.len().to_string(),
+            format!("{:.2}", genes.len() as f64 / n_isolates.max(1) as f64),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            min_len,
+            max_len,
+            avg_len,
+        ];
+        let mut presence = vec![cluster_id.clone()];
+        let mut copies = vec![cluster_id.clone()];
+        for genome in &genomes {
+            let ids = per_genome.get(genome.as_str()).cloned().unwrap_or_default();
+            row.push(ids.join("\t"));
+            presence.push(if ids.is_empty() { "0" } else { "1" }.to_string());
+            copies.push(ids.len().to_string());
+        }
+
+        csv_lines.push(row.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(","));
+        rtab_lines.push(presence.join("\t"));
+        copy_lines.push(copies.join("\t"));
+    }
+
+    for (writer, path, lines) in [
+        (&mut csv_writer, &csv_path, &csv_lines),
+        (&mut rtab_writer, &rtab_path, &rtab_lines),
+        (&mut copy_writer, &copy_path, &copy_lines),
+    ] {
+        if let Err(e) = writeln!(writer, "{}", lines.join("\n")) {
+            logger.error(&format!("write_presence_absence: write error {}: {}", path.display(), e));
+            std::process::exit(1);
+        }
+    }
+
+    logger.information(&format!(
+        "write_presence_absence: wrote {} orthogroups x {} genomes to {}",
+        csv_lines.len() - 1, genomes.len(), csv_path.display()
+    ));
+    csv_path
+}
@@ -0,0 +1,477 @@
+use crate::args::QueryArgs;
+use crate::logger::Logger;
+use crate::ortholog_summary;
+use crate::util::{open_bufread, open_bufwrite};
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// A gene and, if it is in the combined GFF of create-repo, its location
+#[derive(Debug, Clone, Serialize)]
+pub struct Member {
+    pub genome: String,
+    pub gene: String,
+    pub contig: Option<String>,
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+    pub strand: Option<String>,
+}
+
+/// One aligned gene pair of a DAGchainer synteny block
+#[derive(Debug, Clone)]
+pub struct SyntenicPair {
+    pub gene1: (String, String), // (genome, gene)
+    pub contig1: String,
+    pub coords1: (u64, u64),
+    pub gene2: (String, String),
+    pub contig2: String,
+    pub coords2: (u64, u64),
+}
+
+/// A synteny block: the gene pairs under one "## alignment" header of the aligncoords file
+#[derive(Debug, Clone)]
+pub struct SyntenyBlock {
+    pub id: usize, // 1-based, in file order
+    pub header: String,
+    pub pairs: Vec<SyntenicPair>,
+}
+
+/// A synteny block containing a queried gene, with the gene aligned to it
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockHit {
+    pub block: usize,
+    pub alignment: String,
+    pub genome1: String,
+    pub contig1: String,
+    pub start1: u64,
+    pub end1: u64,
+    pub genome2: String,
+    pub contig2: String,
+    pub start2: u64,
+    pub end2: u64,
+    pub pairs: usize,
+    pub counterpart: Member,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GeneResult {
+    pub gene: Member,
+    pub orthogroup: Option<String>,
+    pub members: Vec<Member>,
+    pub synteny_blocks: Vec<BlockHit>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrthogroupResult {
+    pub orthogroup: String,
+    pub genomes: BTreeMap<String, Vec<Member>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RegionGene {
+    pub gene: Member,
+    pub orthogroup: Option<String>,
+    pub synteny_blocks: Vec<BlockHit>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RegionResult {
+    pub genome: String,
+    pub contig: String,
+    pub start: u64,
+    pub end: u64,
+    pub genes: Vec<RegionGene>,
+}
+
+/// Orthogroups, gene coordinates and synteny blocks of one output directory
+pub struct QueryData {
+    pub clusters: BTreeMap<String, Vec<(String, String)>>,
+    pub gene_to_cluster: HashMap<(String, String), String>,
+    pub locations: HashMap<(String, String), Member>,
+    pub blocks: Vec<SyntenyBlock>,
+    pub gene_to_blocks: HashMap<(String, String), Vec<(usize, usize)>>, // (block index, pair index)
+}
+
+/// Most recently modified file in `dir` whose name starts with `prefix` and ends with `suffix`
+fn latest_file(dir: &Path, prefix: &str, suffix: &str) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.file_name().map(|n| n.to_string_lossy().starts_with(prefix) && n.to_string_lossy().ends_with(suffix)).unwrap_or(false))
+        .max_by_key(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+}
+
+/// Gene locations from the combined GFF written by create-repo (column 9 is genome|gene)
+fn read_locations(gff_path: &Path, logger: &Logger) -> HashMap<(String, String), Member> {
+
+    let reader = open_bufread(gff_path, logger, "read_locations");
+    let mut locations: HashMap<(String, String), Member> = HashMap::new();
+    for line_res in reader.lines() {
+        let line = line_res.unwrap_or_else(|e| {
+            logger.error(&format!("read_locations: read error in {}: {}", gff_path.display(), e));
+            std::process::exit(1);
+        });
+        let cols: Vec<&str> = line.trim_end().split('\t').collect();
+        if line.starts_with('#') || cols.len() < 9 {
+            continue;
+        }
+        let Some((genome, gene)) = cols[8].split_once('|') else {
+            continue;
+        };
+        locations.insert(
+            (genome.to_string(), gene.to_string()),
+            Member {
+                genome: genome.to_string(),
+                gene: gene.to_string(),
+                contig: Some(cols[0].to_string()),
+                start: cols[3].parse().ok(),
+                end: cols[4].parse().ok(),
+                strand: Some(cols[6].to_string()),
+            },
+        );
+    }
+    locations
+}
+
+/// Synteny blocks from the combined DAGchainer aligncoords file:
+/// "## alignment ..." headers, each followed by rows of
+/// genome1 contig1 gene1 end5 end3 pos MATCHES genome2 contig2 gene2 end5 end3 pos evalue score
+fn read_synteny_blocks(aligncoords_path: &Path, logger: &Logger) -> Vec<SyntenyBlock> {
+
+    let reader = open_bufread(aligncoords_path, logger, "read_synteny_blocks");
+    let mut blocks: Vec<SyntenyBlock> = Vec::new();
+
+    // gene IDs may be given as genome|gene
+    let gene_key = |genome: &str, gene: &str| {
+        let gene = gene.strip_prefix(&format!("{}|", genome)).unwrap_or(gene);
+        (genome.to_string(), gene.to_string())
+    };
+    let coords = |a: &str, b: &str| {
+        let (a, b) = (a.parse::<u64>().unwrap_or(0), b.parse::<u64>().unwrap_or(0));
+        (a.min(b), a.max(b))
+    };
+
+    for line_res in reader.lines() {
+        let line = line_res.unwrap_or_else(|e| {
+            logger.error(&format!("read_synteny_blocks: read error in {}: {}", aligncoords_path.display(), e));
+            std::process::exit(1);
+        });
+        let trimmed = line.trim();
+        if let Some(header) = trimmed.strip_prefix("## alignment") {
+            blocks.push(SyntenyBlock { id: blocks.len() + 1, header: header.trim().trim_end_matches(':').to_string(), pairs: Vec::new() });
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let cols: Vec<&str> = trimmed.split('\t').collect();
+        let Some(mi) = cols.iter().position(|c| *c == "MATCHES") else {
+            continue;
+        };
+        if mi < 5 || cols.len() <= mi + 5 {
+            continue;
+        }
+        let Some(block) = blocks.last_mut() else {
+            logger.warning(&format!("read_synteny_blocks: gene pair before any alignment header, skipped: {}", trimmed));
+            continue;
+        };
+        block.pairs.push(SyntenicPair {
+            gene1: gene_key(cols[0], cols[2]),
+            contig1: cols[1].to_string(),
+            coords1: coords(cols[3], cols[4]),
+            gene2: gene_key(cols[mi + 1], cols[mi + 3]),
+            contig2: cols[mi + 2].to_string(),
+            coords2: coords(cols[mi + 4], cols[mi + 5]),
+        });
+    }
+    blocks
+}
+
+impl QueryData {
+    /// Load the ortholog summary (step 4), combined GFF (step 1) and aligncoords (step 6) of an output directory
+    pub fn load(output_dir: &Path, alignment_type: &str, method: Option<&str>, logger: &Logger) -> Self {
+
+        let summary_dir = output_dir.join("synima_step4-ortholog-summary");
+        let clusters_path = match method {
+            Some(m) => summary_dir.join(format!("GENE_CLUSTERS_SUMMARIES.{}.{}.clusters_and_uniques", alignment_type, m)),
+            None => latest_file(&summary_dir, &format!("GENE_CLUSTERS_SUMMARIES.{}.", alignment_type), ".clusters_and_uniques")
+                .unwrap_or_else(|| {
+                    logger.error(&format!("QueryData::load: no {} clusters_and_uniques file in {}, run ortholog-summary first", alignment_type, summary_dir.display()));
+                    std::process::exit(1);
+                }),
+        };
+        logger.information(&format!("QueryData::load: orthogroups from {}", clusters_path.display()));
+        let clusters = ortholog_summary::read_cluster_members(&clusters_path, logger);
+        let gene_to_cluster: HashMap<(String, String), String> = clusters
+            .iter()
+            .flat_map(|(cluster, genes)| genes.iter().map(move |g| (g.clone(), cluster.clone())))
+            .collect();
+
+        let locations = match latest_file(&output_dir.join("synima_step1_create-repo"), "", ".all.gff") {
+            Some(gff_path) => {
+                logger.information(&format!("QueryData::load: gene coordinates from {}", gff_path.display()));
+                read_locations(&gff_path, logger)
+            }
+            None => {
+                logger.warning("QueryData::load: no combined GFF from create-repo, genes are reported without coordinates");
+                HashMap::new()
+            }
+        };
+
+        let blocks = match latest_file(&output_dir.join("synima_step6-dagchainer"), "", ".dagchainer.aligncoords") {
+            Some(aligncoords_path) => {
+                logger.information(&format!("QueryData::load: synteny blocks from {}", aligncoords_path.display()));
+                read_synteny_blocks(&aligncoords_path, logger)
+            }
+            None => {
+                logger.warning("QueryData::load: no aligncoords from dagchainer, synteny blocks are not reported");
+                Vec::new()
+            }
+        };
+        let mut gene_to_blocks: HashMap<(String, String), Vec<(usize, usize)>> = HashMap::new();
+        for (b, block) in blocks.iter().enumerate() {
+            for (p, pair) in block.pairs.iter().enumerate() {
+                gene_to_blocks.entry(pair.gene1.clone()).or_default().push((b, p));
+                gene_to_blocks.entry(pair.gene2.clone()).or_default().push((b, p));
+            }
+        }
+
+        QueryData { clusters, gene_to_cluster, locations, blocks, gene_to_blocks }
+    }
+
+    fn member(&self, key: &(String, String)) -> Member {
+        self.locations.get(key).cloned().unwrap_or_else(|| Member {
+            genome: key.0.clone(),
+            gene: key.1.clone(),
+            contig: None,
+            start: None,
+            end: None,
+            strand: None,
+        })
+    }
+
+    /// Synteny blocks containing a gene, oriented so that genome1 is the gene's side
+    fn block_hits(&self, key: &(String, String)) -> Vec<BlockHit> {
+        let mut hits: Vec<BlockHit> = Vec::new();
+        for &(b, p) in self.gene_to_blocks.get(key).map(|v| v.as_slice()).unwrap_or(&[]) {
+            let block = &self.blocks[b];
+            let pair = &block.pairs[p];
+            let query_is_first = pair.gene1 == *key;
+            let span = |first: bool| {
+                let coords: Vec<(u64, u64)> = block.pairs.iter().map(|q| if first { q.coords1 } else { q.coords2 }).collect();
+                (coords.iter().map(|c| c.0).min().unwrap_or(0), coords.iter().map(|c| c.1).max().unwrap_or(0))
+            };
+            let (side1, side2) = if query_is_first { (true, false) } else { (false, true) };
+            let pick = |first: bool| if first { (&pair.gene1, &pair.contig1) } else { (&pair.gene2, &pair.contig2) };
+            let ((g1, c1), (g2, c2)) = (pick(side1), pick(side2));
+            let ((s1, e1), (s2, e2)) = (span(side1), span(side2));
+            hits.push(BlockHit {
+                block: block.id,
+                alignment: block.header.clone(),
+                genome1: g1.0.clone(),
+                contig1: c1.clone(),
+                start1: s1,
+                end1: e1,
+                genome2: g2.0.clone(),
+                contig2: c2.clone(),
+                start2: s2,
+                end2: e2,
+                pairs: block.pairs.len(),
+                counterpart: self.member(g2),
+            });
+        }
+        hits
+    }
+
+    /// Genes matching an ID, given as gene or genome|gene
+    pub fn find_genes(&self, query: &str) -> Vec<(String, String)> {
+        let mut keys: Vec<(String, String)> = self
+            .gene_to_cluster
+            .keys()
+            .chain(self.locations.keys())
+            .filter(|(genome, gene)| gene == query || format!("{}|{}", genome, gene) == query)
+            .cloned()
+            .collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    pub fn gene(&self, key: &(String, String)) -> GeneResult {
+        let orthogroup = self.gene_to_cluster.get(key).cloned();
+        let members = orthogroup
+            .as_ref()
+            .map(|c| self.clusters[c].iter().map(|m| self.member(m)).collect())
+            .unwrap_or_default();
+        GeneResult { gene: self.member(key), orthogroup, members, synteny_blocks: self.block_hits(key) }
+    }
+
+    pub fn orthogroup(&self, cluster: &str) -> Option<OrthogroupResult> {
+        let genes = self.clusters.get(cluster)?;
+        let mut genomes: BTreeMap<String, Vec<Member>> = BTreeMap::new();
+        for key in genes {
+            genomes.entry(key.0.clone()).or_default().push(self.member(key));
+        }
+        Some(OrthogroupResult { orthogroup: cluster.to_string(), genomes })
+    }
+
+    /// Genes overlapping a region, in order along the contig
+    pub fn region(&self, genome: &str, contig: &str, start: u64, end: u64) -> RegionResult {
+        let mut genes: Vec<&Member> = self
+            .locations
+            .values()
+            .filter(|m| m.genome == genome && m.contig.as_deref() == Some(contig))
+            .filter(|m| m.start.unwrap_or(0) <= end && m.end.unwrap_or(0) >= start)
+            .collect();
+        genes.sort_by_key(|m| (m.start, m.end, m.gene.clone()));
+        let genes = genes
+            .into_iter()
+            .map(|m| {
+                let key = (m.genome.clone(), m.gene.clone());
+                RegionGene { gene: m.clone(), orthogroup: self.gene_to_cluster.get(&key).cloned(), synteny_blocks: self.block_hits(&key) }
+            })
+            .collect();
+        RegionResult { genome: genome.to_string(), contig: contig.to_string(), start, end, genes }
+    }
+}
+
+/// Parse genome:contig:start-end (the contig may itself contain ':')
+pub fn parse_region(region: &str, logger: &Logger) -> (String, String, u64, u64) {
+    let parsed = region.split_once(':').and_then(|(genome, rest)| {
+        let (contig, range) = rest.rsplit_once(':')?;
+        let (start, end) = range.replace(',', "").split_once('-').map(|(s, e)| (s.trim().parse::<u64>(), e.trim().parse::<u64>()))?;
+        Some((genome.to_string(), contig.to_string(), start.ok()?, end.ok()?))
+    });
+    match parsed {
+        Some((genome, contig, start, end)) if !genome.is_empty() && !contig.is_empty() && start <= end => (genome, contig, start, end),
+        _ => {
+            logger.error(&format!("parse_region: region '{}' is not in the format genome:contig:start-end", region));
+            std::process::exit(1);
+        }
+    }
+}
+
+fn opt<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(|v| v.to_string()).unwrap_or_else(|| ".".to_string())
+}
+
+fn member_cols(m: &Member) -> String {
+    format!("{}\t{}\t{}\t{}\t{}\t{}", m.genome, m.gene, opt(&m.contig), opt(&m.start), opt(&m.end), opt(&m.strand))
+}
+
+const MEMBER_HEADER: &str = "genome\tgene\tcontig\tstart\tend\tstrand";
+const BLOCK_HEADER: &str = "block\tgenome1\tcontig1\tstart1\tend1\tgenome2\tcontig2\tstart2\tend2\tpairs\tcounterpart_gene\tcounterpart_start\tcounterpart_end";
+
+fn block_cols(h: &BlockHit) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        h.block, h.genome1, h.contig1, h.start1, h.end1, h.genome2, h.contig2, h.start2, h.end2, h.pairs,
+        h.counterpart.gene, opt(&h.counterpart.start), opt(&h.counterpart.end)
+    )
+}
+
+/// TSV output: each row starts with its record type (gene, member, block), and a
+/// '#' header line precedes the first row of each type
+fn gene_tsv(results: &[GeneResult]) -> Vec<String> {
+    let mut lines = vec![
+        format!("#gene\t{}\torthogroup", MEMBER_HEADER),
+        format!("#member\tquery_gene\torthogroup\t{}", MEMBER_HEADER),
+        format!("#block\tquery_gene\t{}", BLOCK_HEADER),
+    ];
+    for r in results {
+        let query = format!("{}|{}", r.gene.genome, r.gene.gene);
+        lines.push(format!("gene\t{}\t{}", member_cols(&r.gene), opt(&r.orthogroup)));
+        for m in &r.members {
+            lines.push(format!("member\t{}\t{}\t{}", query, opt(&r.orthogroup), member_cols(m)));
+        }
+        for h in &r.synteny_blocks {
+            lines.push(format!("block\t{}\t{}", query, block_cols(h)));
+        }
+    }
+    lines
+}
+
+fn orthogroup_tsv(result: &OrthogroupResult) -> Vec<String> {
+    let mut lines = vec![format!("#member\torthogroup\t{}", MEMBER_HEADER)];
+    for members in result.genomes.values() {
+        for m in members {
+            lines.push(format!("member\t{}\t{}", result.orthogroup, member_cols(m)));
+        }
+    }
+    lines
+}
+
+fn region_tsv(result: &RegionResult) -> Vec<String> {
+    let mut lines = vec![
+        format!("#region\t{}:{}:{}-{}", result.genome, result.contig, result.start, result.end),
+        format!("#gene\t{}\torthogroup", MEMBER_HEADER),
+        format!("#block\tquery_gene\t{}", BLOCK_HEADER),
+    ];
+    for g in &result.genes {
+        lines.push(format!("gene\t{}\t{}", member_cols(&g.gene), opt(&g.orthogroup)));
+        for h in &g.synteny_blocks {
+            lines.push(format!("block\t{}|{}\t{}", g.gene.genome, g.gene.gene, block_cols(h)));
+        }
+    }
+    lines
+}
+
+fn to_json<T: Serialize>(value: &T, logger: &Logger) -> Vec<String> {
+    match serde_json::to_string_pretty(value) {
+        Ok(text) => vec![text],
+        Err(e) => {
+            logger.error(&format!("run_query: failed to serialise results: {}", e));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Synima query: look up a gene, orthogroup or region in the results of an earlier run
+pub fn run_query(query: &QueryArgs, logger: &Logger) {
+
+    let output_dir = Path::new(&query.output_dir);
+    if !output_dir.is_dir() {
+        logger.error(&format!("run_query: output directory {} not found", output_dir.display()));
+        std::process::exit(1);
+    }
+    let data = QueryData::load(output_dir, &query.alignment_type, query.method.as_deref(), logger);
+    let json = query.format == "json";
+
+    let lines = if let Some(gene) = &query.gene {
+        let keys = data.find_genes(gene);
+        if keys.is_empty() {
+            logger.error(&format!("run_query: gene {} not found in the orthogroups or gene coordinates", gene));
+            std::process::exit(1);
+        }
+        let results: Vec<GeneResult> = keys.iter().map(|k| data.gene(k)).collect();
+        if json { to_json(&results, logger) } else { gene_tsv(&results) }
+    } else if let Some(cluster) = &query.orthogroup {
+        let Some(result) = data.orthogroup(cluster) else {
+            logger.error(&format!("run_query: orthogroup {} not found", cluster));
+            std::process::exit(1);
+        };
+        if json { to_json(&result, logger) } else { orthogroup_tsv(&result) }
+    } else if let Some(region) = &query.region {
+        let (genome, contig, start, end) = parse_region(region, logger);
+        let result = data.region(&genome, &contig, start, end);
+        logger.information(&format!("run_query: {} genes in {}", result.genes.len(), region));
+        if json { to_json(&result, logger) } else { region_tsv(&result) }
+    } else {
+        logger.error("run_query: one of --gene, --orthogroup or --region is required");
+        std::process::exit(1);
+    };
+
+    let text = format!("{}\n", lines.join("\n"));
+    let result = match &query.out {
+        Some(path) => open_bufwrite(Path::new(path), logger, "run_query").write_all(text.as_bytes()),
+        None => std::io::stdout().write_all(text.as_bytes()),
+    };
+    if let Err(e) = result {
+        logger.error(&format!("run_query: write error: {}", e));
+        std::process::exit(1);
+    }
+}
@@ -671,7 +671,12 @@ pub fn match_or_extract_genes_from_gff(
         {
             // Step 5a: extract directly from GFF + genome FASTA
             let Some(contigs) = all_genome_sequences.get(genome) else {
-                logger.error(&format!("process_alignment_sequences_per_genome: No genome FASTA found for '{}'", genome));
+                if has_sequences {
+                    // proteome-only mode: nothing to extract from
+                    logger.error(&format!("match_or_extract_genes_from_gff: {} FASTA for '{}' could not be matched to its GFF and no genome FASTA is given to extract sequences from", alignment_type, genome));
+                } else {
+                    logger.error(&format!("process_alignment_sequences_per_genome: No genome FASTA found for '{}'", genome));
+                }
                 std::process::exit(1);
             };
 
@@ -797,7 +802,8 @@ fn check_for_unmatched_peptide_ids(
     };
 
     // Prepare lookup sets that were already matched in extract_features
-    let matched_ids: HashSet<_> = matched_fastas.iter().map(|f| f.id.clone()).collect();
+    // (matched FASTA IDs are already genome|ID, the full list still has the raw IDs)
+    let matched_ids: HashSet<_> = matched_fastas.iter().map(|f| f.id.split_once('|').map(|(_, id)| id).unwrap_or(&f.id).to_string()).collect();
     let all_fasta_ids: HashMap<_, _> = full_fasta_list.iter().map(|f| (f.id.clone(), f)).collect();
 
     let allowed_types = ["gene", "mRNA"];
@@ -0,0 +1,127 @@
+use crate::logger::Logger;
+use crate::read_gff::{self, GffFeature};
+use crate::util::open_bufread;
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+
+/// Read a simple gene table into the same feature model used for GFF files.
+///
+/// Supported layouts (whitespace or tab-separated):
+///   - "genetable": <gene_id> <contig> <start> <end> <strand>  (1-based, inclusive)
+///   - "bed":       <contig> <start> <end> <name> [score] [strand] (0-based start, half-open)
+///
+/// Every row becomes a single "gene" feature with `ID=<gene_id>`, and an equivalent
+/// GFF3 line is kept in `original_line` so ID matching against the pep/cds FASTA
+/// works exactly as it does for GFF input. Lines starting with '#', 'track' or
+/// 'browser' are skipped, as is a header row whose coordinates are not numeric.
+pub fn save_gene_table_features(path: &Path, table_type: &str, logger: &Logger) -> Vec<GffFeature> {
+
+    logger.information(&format!("save_gene_table_features: {} ({})", path.display(), table_type));
+
+    let reader = open_bufread(path, logger, "save_gene_table_features");
+    let mut features: Vec<GffFeature> = Vec::new();
+
+    for (index, line_result) in reader.lines().enumerate() {
+        let line = match line_result {
+            Ok(l) => l,
+            Err(e) => {
+                logger.error(&format!("save_gene_table_features: read error in {}: {}", path.display(), e));
+                std::process::exit(1);
+            }
+        };
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("track") || trimmed.starts_with("browser") {
+            continue;
+        }
+
+        let cols: Vec<&str> = trimmed.split_whitespace().collect();
+
+        // gene_id, contig, start (1-based), end, strand
+        let (gene_id, contig, start, end, strand) = match table_type {
+            "bed" => {
+                if cols.len() < 4 {
+                    logger.error(&format!("save_gene_table_features: Invalid BED line {} in {} (expected at least 4 columns): {}", index + 1, path.display(), trimmed));
+                    std::process::exit(1);
+                }
+                let strand = cols.get(5).copied().unwrap_or(".");
+                match (cols[1].parse::<usize>(), cols[2].parse::<usize>()) {
+                    (Ok(s), Ok(e)) => (cols[3], cols[0], s + 1, e, strand),
+                    _ if features.is_empty() => continue, // header
+                    _ => {
+                        logger.error(&format!("save_gene_table_features: Invalid coordinates at line {} in {}: {}", index + 1, path.display(), trimmed));
+                        std::process::exit(1);
+                    }
+                }
+            }
+            _ => {
+                if cols.len() < 5 {
+                    logger.error(&format!("save_gene_table_features: Invalid gene table line {} in {} (expected gene_id, contig, start, end, strand): {}", index + 1, path.display(), trimmed));
+                    std::process::exit(1);
+                }
+                match (cols[2].parse::<usize>(), cols[3].parse::<usize>()) {
+                    (Ok(s), Ok(e)) => (cols[0], cols[1], s, e, cols[4]),
+                    _ if features.is_empty() => continue, // header
+                    _ => {
+                        logger.error(&format!("save_gene_table_features: Invalid coordinates at line {} in {}: {}", index + 1, path.display(), trimmed));
+                        std::process::exit(1);
+                    }
+                }
+            }
+        };
+
+        if start == 0 || end < start {
+            logger.error(&format!("save_gene_table_features: Invalid coordinates {}..{} for gene {} in {}", start, end, gene_id, path.display()));
+            std::process::exit(1);
+        }
+
+        let strand = match strand {
+            "+" | "-" => strand.chars().next().unwrap(),
+            _ => '.',
+        };
+
+        let original_line = format!("{}\t{}\tgene\t{}\t{}\t.\t{}\t.\tID={}", contig, table_type, start, end, strand, gene_id);
+
+        features.push(GffFeature {
+            seqid: contig.to_string(),
+            feature_type: "gene".to_string(),
+            start,
+            end,
+            strand,
+            attributes: read_gff::parse_gff_attributes(&format!("ID={}", gene_id)),
+            original_line,
+        });
+    }
+
+    if features.is_empty() {
+        logger.error(&format!("save_gene_table_features: No genes found in {}", path.display()));
+        std::process::exit(1);
+    }
+
+    logger.information(&format!("save_gene_table_features: {} genes read from {}", features.len(), path.display()));
+
+    features
+}
+
+/// Contig lengths for a gene table (no sequence-region pragmas, so the
+/// maximum gene end on each contig is used). Returns contig order and lengths.
+pub fn gene_table_contig_lengths(path: &Path, table_type: &str, logger: &Logger) -> (Vec<String>, HashMap<String, u64>) {
+    let features = save_gene_table_features(path, table_type, logger);
+
+    let mut order = Vec::<String>::new();
+    let mut lengths = HashMap::<String, u64>::new();
+
+    for f in &features {
+        let max_end = lengths.entry(f.seqid.clone()).or_insert_with(|| {
+            order.push(f.seqid.clone());
+            0
+        });
+        if f.end as u64 > *max_end {
+            *max_end = f.end as u64;
+        }
+    }
+
+    (order, lengths)
+}
@@ -1,12 +1,11 @@
 use crate::logger::Logger;
-use crate::{read_fasta, read_repo};
+use crate::{read_fasta, read_gene_table, read_repo};
 use crate::read_fasta::Fasta;
 use crate::util::open_bufread;
 
 use std::collections::{HashMap, HashSet};
-use std::io::{BufRead};
+use std::io::BufRead;
 use std::path::Path;
-//use std::io::BufReader;
 
 use read_repo::{RepoEntry};
 
@@ -113,10 +112,14 @@ pub fn save_all_features(repo_entries: &[RepoEntry], logger: &Logger,) -> HashMa
             continue;
         }
 
-        if let Some(gff_file) = entry.files.get("gff") {
+        if let Some((annotation_type, gff_file)) = read_repo::get_annotation_file(entry) {
             let gff_path = Path::new(&gff_file.path);
 
-            let features = save_features(gff_path, logger);
+            let features = if annotation_type == "gff" {
+                save_features(gff_path, logger)
+            } else {
+                read_gene_table::save_gene_table_features(gff_path, annotation_type, logger)
+            };
 
             // Count features by type
             let mut counts: HashMap<String, usize> = HashMap::new();
@@ -414,7 +417,7 @@ fn find_matching_parts(
 
 /// Parses a GFF3 file and groups lines by feature type (e.g., "gene", "mRNA")
 /// Returns a HashMap where keys are feature types and values are vectors of full lines.
-fn save_features(gff_path: &Path, logger: &Logger) -> Vec<GffFeature> {
+pub fn save_features(gff_path: &Path, logger: &Logger) -> Vec<GffFeature> {
 
     logger.information(&format!("read_gff_by_feature: {}", gff_path.display()));
 
@@ -511,3 +514,66 @@ pub fn filter_and_rewrite_gff_lines(
         .collect()
 }
 
+/// Contig lengths for genomes without a genome FASTA (proteome-only mode).
+/// Uses `##sequence-region <seqid> <start> <end>` pragmas where present,
+/// otherwise the maximum feature end seen on each contig.
+/// Returns the contig order (first appearance) and contig -> length.
+pub fn gff_contig_lengths(path: &Path, logger: &Logger) -> (Vec<String>, HashMap<String, u64>) {
+    let reader = open_bufread(path, logger, "gff_contig_lengths");
+
+    let mut order = Vec::<String>::new();
+    let mut region_lengths = HashMap::<String, u64>::new();
+    let mut max_feature_end = HashMap::<String, u64>::new();
+
+    for line_res in reader.lines() {
+        let line = match line_res {
+            Ok(l) => l,
+            Err(e) => {
+                logger.error(&format!("gff_contig_lengths: read error in {}: {}", path.display(), e));
+                std::process::exit(1);
+            }
+        };
+
+        if let Some(rest) = line.strip_prefix("##sequence-region") {
+            let parts: Vec<&str> = rest.split_whitespace().collect();
+            if parts.len() >= 3 {
+                let end = parts[2].parse::<u64>().unwrap_or(0);
+                if !region_lengths.contains_key(parts[0]) && !max_feature_end.contains_key(parts[0]) {
+                    order.push(parts[0].to_string());
+                }
+                region_lengths.insert(parts[0].to_string(), end);
+            }
+            continue;
+        }
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 9 {
+            continue;
+        }
+
+        let seqid = fields[0];
+        let end = fields[4].parse::<u64>().unwrap_or(0);
+
+        if !region_lengths.contains_key(seqid) && !max_feature_end.contains_key(seqid) {
+            order.push(seqid.to_string());
+        }
+        let max_end = max_feature_end.entry(seqid.to_string()).or_insert(0);
+        if end > *max_end {
+            *max_end = end;
+        }
+    }
+
+    let lengths = order
+        .iter()
+        .map(|id| {
+            let len = region_lengths.get(id).or_else(|| max_feature_end.get(id)).copied().unwrap_or(0);
+            (id.clone(), len)
+        })
+        .collect();
+
+    (order, lengths)
+}
@@ -21,8 +21,12 @@ pub struct RepoEntry {
     pub name: String,
     pub base_dir: Option<String>, // from 'dir' row, if provided
     pub files: HashMap<String, RepoFile>,
+    pub groups: Vec<String>,      // from 'group' rows (comma separated labels), if provided
 }
 
+/// Repo spec types that give gene coordinates (one is required per genome)
+pub const ANNOTATION_TYPES: [&str; 3] = ["gff", "genetable", "bed"];
+
 pub struct GeneStruct {
     pub genome: String,
     pub gene_id: String,
@@ -34,11 +38,13 @@ pub struct GeneStruct {
 /// <name>    <type>    <location>
 /// where:
 /// - `<name>` is the genome identifier (e.g., CNB2)
-/// - `<type>` is one of: genome, cds, pep, gff, dir
-/// - `<location>` is either a full path or a filename relative to a prior 'dir' entry
+/// - `<type>` is one of: genome, cds, pep, gff, genetable, bed, dir, group
+/// - `<location>` is either a full path or a filename relative to a prior 'dir' entry,
+///   or for 'group' one or more comma separated group labels (e.g., VGII,clinical)
 ///
 /// Returns a vector of `RepoEntry` structs grouped by genome name.
 /// If a `dir` is provided, all other file paths are checked or completed relative to it.
+/// `genome` may be omitted for genomes that provide a pep/cds FASTA (proteome-only mode).
 pub fn read_repo_spec(file: &str, alignment_type: &str, logger: &Logger) -> Vec<RepoEntry> {
     logger.information(&format!("read_repo_spec: Reading repo spec file: {}", file));
 
@@ -75,6 +81,7 @@ pub fn read_repo_spec(file: &str, alignment_type: &str, logger: &Logger) -> Vec<
             name: name.clone(),
             base_dir: None,
             files: HashMap::new(),
+            groups: Vec::new(),
         });
 
         // Handle "dir" row
@@ -83,6 +90,16 @@ pub fn read_repo_spec(file: &str, alignment_type: &str, logger: &Logger) -> Vec<
             continue;
         }
 
+        // Handle "group" row (labels, not a file)
+        if file_type == "group" {
+            for label in location.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+                if !entry.groups.iter().any(|g| g == label) {
+                    entry.groups.push(label.to_string());
+                }
+            }
+            continue;
+        }
+
         // Build the full path using spec_dir and optional base_dir
         let full_path = if Path::new(&location).is_absolute() {
             location.clone()
@@ -143,13 +160,25 @@ pub fn read_repo_spec(file: &str, alignment_type: &str, logger: &Logger) -> Vec<
 
     logger.information(&format!("read_repo_spec: Parsed {} genome entries", repo_entries.len()));
 
-    // Optional: warn if common file types missing
-    let required_types = vec!["genome", "gff"]; // Always required
-
+    // Coordinates are always required. The genome FASTA can be omitted when the
+    // pep/cds FASTA for this alignment type is given (proteome-only mode).
     for entry in &repo_entries {
-        for &req in &required_types {
-            if !entry.files.contains_key(req) {
-                logger.error(&format!("read_repo_spec: Entry '{}' is missing expected file type '{}'", entry.name, req));
+        let Some((annotation_type, _)) = get_annotation_file(entry) else {
+            logger.error(&format!("read_repo_spec: Entry '{}' is missing expected file type 'gff' (or 'genetable'/'bed')", entry.name));
+            std::process::exit(1);
+        };
+
+        // gene tables hold no exon structure, so sequences cannot be extracted from them
+        if annotation_type != "gff" && !entry.files.contains_key(alignment_type) {
+            logger.error(&format!("read_repo_spec: Entry '{}' uses a '{}' annotation, which requires a '{}' FASTA", entry.name, annotation_type, alignment_type));
+            std::process::exit(1);
+        }
+
+        if !entry.files.contains_key("genome") {
+            if entry.files.contains_key(alignment_type) {
+                logger.information(&format!("read_repo_spec: Entry '{}' has no genome FASTA. Using proteome-only mode (contig lengths taken from the annotation)", entry.name));
+            } else {
+                logger.error(&format!("read_repo_spec: Entry '{}' is missing expected file type 'genome' (required unless a '{}' FASTA is given)", entry.name, alignment_type));
                 std::process::exit(1);
             }
         }
@@ -157,6 +186,14 @@ pub fn read_repo_spec(file: &str, alignment_type: &str, logger: &Logger) -> Vec<
     repo_entries
 }
 
+/// Return the annotation given for a genome in the repo spec (gff preferred),
+/// as (type, file).
+pub fn get_annotation_file(entry: &RepoEntry) -> Option<(&'static str, &RepoFile)> {
+    ANNOTATION_TYPES
+        .iter()
+        .find_map(|t| entry.files.get(*t).map(|f| (*t, f)))
+}
+
 pub fn update_repo_with_parsed_files(repo: &mut Vec<RepoEntry>, main_output_dir: &Path, logger: &Logger) {
 
     logger.information(&format!("update_repo_with_parsed_files: {}", main_output_dir.display()));
@@ -241,6 +278,7 @@ pub fn update_repo_with_parsed_files(repo: &mut Vec<RepoEntry>, main_output_dir:
             name: "synima_all".to_string(),
             base_dir: Some(main_output_dir.to_string_lossy().to_string()),
             files: synima_all_files,
+            groups: Vec::new(),
         });
     }
 }
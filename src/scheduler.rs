@@ -0,0 +1,80 @@
+use crate::logger::Logger;
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How --threads is split between concurrent jobs and threads given to each job
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadPlan {
+    pub concurrent_jobs: usize,
+    pub threads_per_job: usize,
+}
+
+/// Split `total_threads` between `n_jobs` jobs.
+///
+/// With at least as many jobs as threads every job gets one thread. With fewer
+/// jobs than threads, all jobs run at once and share the spare threads, up to
+/// `max_threads_per_job` (1 for single threaded tools).
+pub fn plan_threads(total_threads: usize, n_jobs: usize, max_threads_per_job: usize) -> ThreadPlan {
+    let total_threads = total_threads.max(1);
+    let n_jobs = n_jobs.max(1);
+
+    if n_jobs >= total_threads {
+        return ThreadPlan { concurrent_jobs: total_threads, threads_per_job: 1 };
+    }
+
+    ThreadPlan {
+        concurrent_jobs: n_jobs,
+        threads_per_job: (total_threads / n_jobs).clamp(1, max_threads_per_job.max(1)),
+    }
+}
+
+/// Run `job_fn` over `jobs` on `concurrent_jobs` worker threads, most costly jobs
+/// first, so the largest jobs do not end up running alone at the end.
+pub fn run_largest_first<T, F>(jobs: &[T], costs: &[u64], concurrent_jobs: usize, job_fn: F)
+where
+    T: Sync,
+    F: Fn(&T) + Sync,
+{
+    let mut order: Vec<usize> = (0..jobs.len()).collect();
+    order.sort_by(|&a, &b| costs[b].cmp(&costs[a]).then(a.cmp(&b)));
+
+    let next = AtomicUsize::new(0);
+    let workers = concurrent_jobs.clamp(1, jobs.len().max(1));
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&job_idx) = order.get(i) else { break };
+                job_fn(&jobs[job_idx]);
+            });
+        }
+    });
+}
+
+/// Log the plan for a set of jobs
+pub fn log_plan(context: &str, n_jobs: usize, plan: &ThreadPlan, logger: &Logger) {
+    logger.information(&format!(
+        "{}: {} jobs, largest first, {} concurrent job(s) x {} thread(s) each",
+        context, n_jobs, plan.concurrent_jobs, plan.threads_per_job));
+}
+
+/// Size of a file in bytes (0 if missing), used for cost estimates
+pub fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Search cost: query size x database size
+pub fn search_cost(q_fasta: &Path, s_fasta: &Path) -> u64 {
+    file_size(q_fasta).max(1).saturating_mul(file_size(s_fasta).max(1))
+}
+
+/// MSA cost for a cluster FASTA: roughly N sequences x total residues
+pub fn msa_cost(fasta: &Path) -> u64 {
+    let n_seqs = fs::read_to_string(fasta)
+        .map(|s| s.lines().filter(|l| l.starts_with('>')).count() as u64)
+        .unwrap_or(1);
+    n_seqs.max(1).saturating_mul(file_size(fasta).max(1))
+}
@@ -0,0 +1,276 @@
+use crate::hits::SearchHits;
+use crate::logger::Logger;
+use crate::util::open_file_read;
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Extensions of {q}_vs_{s} search output, uncompressed first
+const PAIR_EXTENSIONS: [&str; 3] = [".out", ".out.gz", ".out.zst"];
+
+/// Compression of search output files (--compress_search)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchCompression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl SearchCompression {
+    pub fn from_arg(value: &str) -> Self {
+        match value {
+            "gzip" => SearchCompression::Gzip,
+            "zstd" => SearchCompression::Zstd,
+            _ => SearchCompression::None,
+        }
+    }
+
+    /// File extension, including the leading ".out"
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SearchCompression::None => PAIR_EXTENSIONS[0],
+            SearchCompression::Gzip => PAIR_EXTENSIONS[1],
+            SearchCompression::Zstd => PAIR_EXTENSIONS[2],
+        }
+    }
+
+    fn from_path(path: &Path) -> Self {
+        let name = path.file_name().and_then(|f| f.to_str()).unwrap_or("");
+        if name.ends_with(".gz") {
+            SearchCompression::Gzip
+        } else if name.ends_with(".zst") {
+            SearchCompression::Zstd
+        } else {
+            SearchCompression::None
+        }
+    }
+}
+
+/// Path of the {q}_vs_{s} search output in `dir` for the given compression
+pub fn pair_file_path(dir: &Path, q: &str, s: &str, compression: SearchCompression) -> PathBuf {
+    dir.join(format!("{}_vs_{}{}", q, s, compression.extension()))
+}
+
+/// Split a search output file name ({q}_vs_{s}.out[.gz|.zst]) into (query, subject)
+pub fn parse_pair_file_name(file_name: &str) -> Option<(&str, &str)> {
+    let base = PAIR_EXTENSIONS.iter().rev().find_map(|ext| file_name.strip_suffix(ext))?;
+    let (q, s) = base.split_once("_vs_")?;
+    if q.is_empty() || s.is_empty() || s.contains("_vs_") {
+        return None;
+    }
+    Some((q, s))
+}
+
+/// The {q}_vs_{s} search output in `dir` in any compression, if present
+pub fn find_pair_file(dir: &Path, q: &str, s: &str) -> Option<PathBuf> {
+    PAIR_EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{}_vs_{}{}", q, s, ext)))
+        .find(|p| p.is_file())
+}
+
+/// Remove the {q}_vs_{s} search output in every compression (before writing a new one)
+pub fn remove_pair_files(dir: &Path, q: &str, s: &str) {
+    for ext in PAIR_EXTENSIONS {
+        let _ = fs::remove_file(dir.join(format!("{}_vs_{}{}", q, s, ext)));
+    }
+}
+
+/// List the {q}_vs_{s} search output files in a directory, as (query, subject, path)
+pub fn list_pair_files(dir: &Path, logger: &Logger) -> Vec<(String, String, PathBuf)> {
+    let read_dir = fs::read_dir(dir).unwrap_or_else(|e| {
+        logger.error(&format!("list_pair_files: Failed to read directory {}: {}", dir.display(), e));
+        std::process::exit(1);
+    });
+
+    let mut pairs: Vec<(String, String, PathBuf)> = Vec::new();
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let Some(file_name) = path.file_name().and_then(|f| f.to_str()) else { continue };
+        let Some((q, s)) = parse_pair_file_name(file_name) else { continue };
+        pairs.push((q.to_string(), s.to_string(), path.clone()));
+    }
+    pairs.sort();
+
+    // the same pair in two compressions (e.g. from runs with different --compress_search)
+    pairs.dedup_by(|later, first| {
+        let duplicate = later.0 == first.0 && later.1 == first.1;
+        if duplicate {
+            logger.warning(&format!("list_pair_files: {} vs {} found more than once, using {}", first.0, first.1, first.2.display()));
+        }
+        duplicate
+    });
+    pairs
+}
+
+/// Open search output for reading, decompressing .gz and .zst files
+pub fn open_search_reader(path: &Path, logger: &Logger, context: &str) -> Box<dyn BufRead> {
+    let file = open_file_read(path, logger, context);
+    match SearchCompression::from_path(path) {
+        SearchCompression::None => Box::new(BufReader::new(file)),
+        SearchCompression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(file))),
+        SearchCompression::Zstd => {
+            let decoder = zstd::stream::read::Decoder::new(file).unwrap_or_else(|e| {
+                logger.error(&format!("{}: failed to open zstd stream {}: {}", context, path.display(), e));
+                std::process::exit(1);
+            });
+            Box::new(BufReader::new(decoder))
+        }
+    }
+}
+
+/// Writer for search output in the given compression.
+/// `finish` must be called to complete the compressed stream.
+pub enum SearchWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::stream::write::Encoder<'static, BufWriter<File>>),
+}
+
+impl SearchWriter {
+    pub fn with_compression(path: &Path, compression: SearchCompression) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match compression {
+            SearchCompression::None => SearchWriter::Plain(file),
+            SearchCompression::Gzip => SearchWriter::Gzip(GzEncoder::new(file, flate2::Compression::default())),
+            SearchCompression::Zstd => SearchWriter::Zstd(zstd::stream::write::Encoder::new(file, 0)?),
+        })
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            SearchWriter::Plain(mut w) => w.flush(),
+            SearchWriter::Gzip(w) => w.finish()?.flush(),
+            SearchWriter::Zstd(w) => w.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for SearchWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            SearchWriter::Plain(w) => w.write(buf),
+            SearchWriter::Gzip(w) => w.write(buf),
+            SearchWriter::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            SearchWriter::Plain(w) => w.flush(),
+            SearchWriter::Gzip(w) => w.flush(),
+            SearchWriter::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+/// Search output of one query genome against every subject genome, written to `.part` files
+/// and renamed to {q}_vs_{s}.out[.gz|.zst] by `finish`. Only one query genome is held open at a time,
+/// so the number of open files grows with the number of genomes, not genome pairs.
+pub struct QueryPairWriters {
+    pub query: String,
+    writers: HashMap<String, (PathBuf, PathBuf, SearchWriter)>,
+}
+
+impl QueryPairWriters {
+    pub fn open(dir: &Path, query: &str, subjects: &[String], compression: SearchCompression, logger: &Logger, context: &str) -> Self {
+        let mut writers = HashMap::new();
+        for s in subjects {
+            remove_pair_files(dir, query, s);
+            let path = pair_file_path(dir, query, s, compression);
+            let part_path = PathBuf::from(format!("{}.part", path.display()));
+            let writer = SearchWriter::with_compression(&part_path, compression).unwrap_or_else(|e| {
+                logger.error(&format!("{}: failed to open for writing {}: {}", context, part_path.display(), e));
+                std::process::exit(1);
+            });
+            writers.insert(s.clone(), (path, part_path, writer));
+        }
+        QueryPairWriters { query: query.to_string(), writers }
+    }
+
+    /// Write a hit line to the {query}_vs_{subject} output; false if `subject` is not a known genome
+    pub fn write_line(&mut self, subject: &str, line: &str, logger: &Logger, context: &str) -> bool {
+        let Some((path, _, writer)) = self.writers.get_mut(subject) else {
+            return false;
+        };
+        if let Err(e) = writeln!(writer, "{}", line) {
+            logger.error(&format!("{}: write error for {}: {}", context, path.display(), e));
+            std::process::exit(1);
+        }
+        true
+    }
+
+    /// Complete every output of this query genome and move it into place
+    pub fn finish(self, logger: &Logger, context: &str) {
+        for (path, part_path, writer) in self.writers.into_values() {
+            let result = writer.finish().and_then(|_| fs::rename(&part_path, &path));
+            if let Err(e) = result {
+                let _ = fs::remove_file(&part_path);
+                logger.error(&format!("{}: failed to write {}: {}", context, path.display(), e));
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Move finished (uncompressed) aligner output from `tmp_path` to `out_path`,
+/// compressing it on the way when `out_path` ends in .gz or .zst
+pub fn store_search_output(tmp_path: &Path, out_path: &Path) -> Result<(), String> {
+    let compression = SearchCompression::from_path(out_path);
+    if compression == SearchCompression::None {
+        return fs::rename(tmp_path, out_path).map_err(|e| format!("rename {} -> {}: {}", tmp_path.display(), out_path.display(), e));
+    }
+
+    // compress to a partial file first, so out_path is never left incomplete
+    let part_path = PathBuf::from(format!("{}.part", out_path.display()));
+    let result = (|| -> io::Result<()> {
+        let mut reader = File::open(tmp_path)?;
+        let mut writer = SearchWriter::with_compression(&part_path, compression)?;
+        io::copy(&mut reader, &mut writer)?;
+        writer.finish()?;
+        fs::rename(&part_path, out_path)
+    })();
+
+    match result {
+        Ok(()) => {
+            let _ = fs::remove_file(tmp_path);
+            Ok(())
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&part_path);
+            Err(format!("compress {} -> {}: {}", tmp_path.display(), out_path.display(), e))
+        }
+    }
+}
+
+/// Stream every hit line of the {q}_vs_{s} search output that passes the hit filters, in pair order.
+/// Used by the orthology methods in place of a concatenated all_vs_all.out copy.
+pub fn for_each_hit_line<F: FnMut(&str)>(hits: &SearchHits, logger: &Logger, context: &str, mut f: F) {
+    let pairs = list_pair_files(&hits.dir, logger);
+    if pairs.is_empty() {
+        logger.error(&format!("{}: no search output ({{q}}_vs_{{s}}.out) found in {}", context, hits.dir.display()));
+        std::process::exit(1);
+    }
+
+    for (q, s, path) in pairs {
+        logger.information(&format!("{}: Including search result: {} vs {}", context, q, s));
+        let reader = open_search_reader(&path, logger, context);
+        for line in reader.lines() {
+            let line = line.unwrap_or_else(|e| {
+                logger.error(&format!("{}: Failed to read from {}: {}", context, path.display(), e));
+                std::process::exit(1);
+            });
+            if line.is_empty() || line.starts_with('#') || !hits.keeps(&line) {
+                continue;
+            }
+            f(&line);
+        }
+    }
+}
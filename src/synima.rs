@@ -1,6 +1,9 @@
 use crate::logger::Logger;
 use crate::RepoEntry;
 use crate::read_fasta;
+use crate::read_gff;
+use crate::read_gene_table;
+use crate::read_repo;
 
 use regex::Regex;
 use anyhow::{Result, Context};
@@ -12,7 +15,7 @@ use std::path::{Path, PathBuf};
 //use std::io::{BufRead, BufReader};
 //use std::io::{BufRead, BufReader};
 //use std::collections::HashSet;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 //use anyhow::anyhow;
 //use anyhow::bail;
 
@@ -27,7 +30,9 @@ struct SummaryRow {
     genome: String,
     core_1to1: u32,
     core_multi: u32,
+    soft_core: u32,
     aux: u32,
+    cloud: u32,
     unique: u32,
 }
 
@@ -36,9 +41,59 @@ struct SummaryItem {
     alignment: String,
     method: String,
     table: Vec<SummaryRow>,
+    thresholds: HashMap<String, f64>, // core, soft_core, cloud (% of genomes)
     pdf_path: Option<String>,
     png_path: Option<String>,
     rscript: Option<String>,
+    pangenome: Option<PangenomeData>,
+    upset: Option<UpsetData>,
+}
+
+#[derive(Serialize)]
+struct PangenomeRow {
+    genomes: usize,
+    pan_mean: f64,
+    pan_min: f64,
+    pan_max: f64,
+    core_mean: f64,
+    core_min: f64,
+    core_max: f64,
+    new_mean: f64,
+}
+
+#[derive(Serialize)]
+struct PangenomeData {
+    curves: Vec<PangenomeRow>,
+    heaps: HashMap<String, String>, // alpha, openness, iterations etc.
+}
+
+#[derive(Serialize)]
+struct UpsetRow {
+    sets: Vec<usize>, // indices into UpsetSets.sets
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct UpsetSets {
+    sets: Vec<String>,
+    set_sizes: Vec<usize>, // orthogroups in each set
+    rows: Vec<UpsetRow>,
+    total_rows: usize,
+}
+
+#[derive(Serialize)]
+struct GroupInfo {
+    name: String,
+    source: String,
+    genomes: Vec<String>,
+    specific: usize,
+}
+
+#[derive(Serialize)]
+struct UpsetData {
+    genomes: UpsetSets,
+    groups: Option<UpsetSets>,
+    group_info: Vec<GroupInfo>,
 }
 
 #[derive(Serialize)]
@@ -53,7 +108,15 @@ pub struct OrthoParams {
     pub aligner: String,
     pub max_target_seqs: usize,
     pub diamond_sensitivity: String,
+    pub mmseqs_sensitivity: f32,
     pub evalue: String,
+    pub rbh_tie_tolerance: f64,
+    pub rbh_min_coverage: f64,
+    pub omcl_evalue_cutoff: f64,
+    pub inflation: f64,
+    pub mcl_graph: String,
+    pub synteny_max_gap: usize,
+    pub synteny_min_anchors: usize,
     pub dagchainer_chains: usize,
     pub genetic_code: usize
 }
@@ -186,29 +249,59 @@ pub fn inject_json_into_html(path: &Path, id: &str, json: &str) -> Result<()> {
 
 // Ortholog functions below
 
-/// Parse a `.summary` file
-fn parse_summary_file(path: &Path) -> Result<Vec<SummaryRow>> {
+/// Parse a `.summary` file into per-genome class counts, the classification thresholds
+/// and the number of distinct single-copy core orthogroups
+fn parse_summary_file(path: &Path) -> Result<(Vec<SummaryRow>, HashMap<String, f64>, usize)> {
     let text = fs::read_to_string(path)?;
     let mut rows = Vec::new();
+    let mut sco_clusters: HashSet<&str> = HashSet::new();
+
+    // Summaries written before soft-core/cloud classes had 4 classes, with core at 100%
+    let mut thresholds: HashMap<String, f64> = HashMap::from([
+        ("core".to_string(), 100.0),
+        ("soft_core".to_string(), 100.0),
+        ("cloud".to_string(), 0.0),
+    ]);
 
     for line in text.lines() {
         let line = line.trim();
+        if let Some(rest) = line.strip_prefix("#thresholds") {
+            for (key, value) in rest.split_whitespace().filter_map(|kv| kv.split_once('=')) {
+                if let Ok(v) = value.parse::<f64>() {
+                    thresholds.insert(key.to_string(), v);
+                }
+            }
+            continue;
+        }
         if line.is_empty() || line.starts_with('#') { continue; }
 
-        // genome core aux unique
+        // genome class cluster_id (classification listing)
         let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() < 5 { continue; }
-
-        rows.push(SummaryRow {
-            genome:      parts[0].to_string(),
-            core_1to1:   parts[1].parse().unwrap_or(0),
-            core_multi:  parts[2].parse().unwrap_or(0),
-            aux:         parts[3].parse().unwrap_or(0),
-            unique:      parts[4].parse().unwrap_or(0),
-        });
+        if parts.len() == 3 {
+            if parts[1] == "core_1to1" {
+                sco_clusters.insert(parts[2]);
+            }
+            continue;
+        }
+
+        // genome core_1to1 core_multi (soft_core) aux (cloud) unique
+        let counts: Vec<u32> = parts.iter().skip(1).map(|p| p.parse().unwrap_or(0)).collect();
+        let row = match counts.len() {
+            6 => SummaryRow {
+                genome: parts[0].to_string(),
+                core_1to1: counts[0], core_multi: counts[1], soft_core: counts[2], aux: counts[3], cloud: counts[4], unique: counts[5],
+            },
+            4 => SummaryRow {
+                genome: parts[0].to_string(),
+                core_1to1: counts[0], core_multi: counts[1], soft_core: 0, aux: counts[2], cloud: 0, unique: counts[3],
+            },
+            _ => continue,
+        };
+        rows.push(row);
     }
 
-    Ok(rows)
+    let n_sco = sco_clusters.len();
+    Ok((rows, thresholds, n_sco))
 }
 
 /// Extract alignment + method from a filename like:
@@ -264,6 +357,103 @@ fn find_associated_files(dir: &Path, alignment: &str, method: &str) -> (Option<S
     (pdf_path, png_path, rscript_contents)
 }
 
+/// Parse the pan/core-genome curves and Heaps' law fit written next to a summary, if present
+fn parse_pangenome_files(dir: &Path, alignment: &str, method: &str) -> Option<PangenomeData> {
+    let prefix = format!("GENE_CLUSTERS_SUMMARIES.{}.{}.cluster_dist_per_genome", alignment, method);
+    let curves_text = fs::read_to_string(dir.join(format!("{}.pangenome_curves.tsv", prefix))).ok()?;
+    let heaps_text = fs::read_to_string(dir.join(format!("{}.pangenome_heaps.tsv", prefix))).ok()?;
+
+    let mut curves = Vec::new();
+    for line in curves_text.lines() {
+        if line.trim().is_empty() || line.starts_with('#') { continue; }
+
+        // genomes pan_mean pan_sd pan_min pan_max core_mean core_sd core_min core_max new_mean new_sd
+        let v: Vec<f64> = line.split('\t').map(|x| x.parse().unwrap_or(0.0)).collect();
+        if v.len() < 10 { continue; }
+
+        curves.push(PangenomeRow {
+            genomes: v[0] as usize,
+            pan_mean: v[1],
+            pan_min: v[3],
+            pan_max: v[4],
+            core_mean: v[5],
+            core_min: v[7],
+            core_max: v[8],
+            new_mean: v[9],
+        });
+    }
+
+    let heaps = heaps_text
+        .lines()
+        .filter_map(|l| l.split_once('\t'))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+    Some(PangenomeData { curves, heaps })
+}
+
+/// Intersections kept for the UpSet panel (largest first)
+const MAX_UPSET_ROWS: usize = 500;
+
+/// Parse an `*.upset_genomes.tsv`/`*.upset_groups.tsv` file (set flags, degree, orthogroups)
+fn parse_upset_file(path: &Path) -> Option<UpsetSets> {
+    let text = fs::read_to_string(path).ok()?;
+    let mut lines = text.lines();
+    let header = lines.next()?.trim_start_matches('#');
+    let cols: Vec<&str> = header.split('\t').collect();
+    if cols.len() < 3 { return None; }
+    let sets: Vec<String> = cols[..cols.len() - 2].iter().map(|s| s.to_string()).collect();
+
+    let mut rows = Vec::new();
+    for line in lines {
+        if line.trim().is_empty() { continue; }
+        let v: Vec<&str> = line.split('\t').collect();
+        if v.len() != sets.len() + 2 { continue; }
+        rows.push(UpsetRow {
+            sets: (0..sets.len()).filter(|&i| v[i] == "1").collect(),
+            count: v[sets.len() + 1].parse().unwrap_or(0),
+        });
+    }
+
+    let mut set_sizes = vec![0usize; sets.len()];
+    for row in &rows {
+        for &i in &row.sets {
+            set_sizes[i] += row.count;
+        }
+    }
+
+    let total_rows = rows.len();
+    rows.truncate(MAX_UPSET_ROWS);
+    Some(UpsetSets { sets, set_sizes, rows, total_rows })
+}
+
+/// Parse the orthogroup intersections and genome groups written by ortholog-summary
+fn parse_upset_files(dir: &Path, alignment: &str, method: &str) -> Option<UpsetData> {
+    let prefix = format!("GENE_CLUSTERS_SUMMARIES.{}.{}", alignment, method);
+    let genomes = parse_upset_file(&dir.join(format!("{}.upset_genomes.tsv", prefix)))?;
+    let groups = parse_upset_file(&dir.join(format!("{}.upset_groups.tsv", prefix)));
+
+    // group source genomes specific_orthogroups
+    let group_info = fs::read_to_string(dir.join(format!("{}.groups.tsv", prefix)))
+        .map(|text| {
+            text.lines()
+                .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+                .filter_map(|l| {
+                    let v: Vec<&str> = l.split('\t').collect();
+                    (v.len() >= 4).then(|| GroupInfo {
+                        name: v[0].to_string(),
+                        source: v[1].to_string(),
+                        genomes: v[2].split(',').map(|g| g.to_string()).collect(),
+                        specific: v[3].parse().unwrap_or(0),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(UpsetData { genomes, groups, group_info })
+}
+
 /// Main function to assemble summaries into JSON and inject into HTML
 pub fn process_ortholog_summaries(
     gene_clusters_out_dir: &Path,
@@ -272,6 +462,9 @@ pub fn process_ortholog_summaries(
 
     let mut summaries: Vec<SummaryItem> = Vec::new();
 
+    // Number of single-copy core orthogroups (of the first summary, as used for the tree)
+    let mut global_sco = 0usize;
+
     for entry in fs::read_dir(gene_clusters_out_dir)
         .with_context(|| format!("Cannot read directory {:?}", gene_clusters_out_dir))?
     {
@@ -289,35 +482,33 @@ pub fn process_ortholog_summaries(
         };
 
         // Parse table
-        let table = parse_summary_file(&path).with_context(|| format!("Failed parsing {:?}", path))?;
+        let (table, thresholds, n_sco) = parse_summary_file(&path).with_context(|| format!("Failed parsing {:?}", path))?;
+        if summaries.is_empty() {
+            global_sco = n_sco;
+        }
 
         // Find PDF + R script
         let (pdf_path, png_path, rscript) = find_associated_files(gene_clusters_out_dir, &alignment, &method);
 
+        // Pan/core-genome curves
+        let pangenome = parse_pangenome_files(gene_clusters_out_dir, &alignment, &method);
+
+        // Orthogroup intersections and genome groups
+        let upset = parse_upset_files(gene_clusters_out_dir, &alignment, &method);
+
         summaries.push(SummaryItem {
             alignment,
             method,
             table,
+            thresholds,
             pdf_path,
             png_path,
             rscript,
+            pangenome,
+            upset,
         });
     }
 
-    // Compute global single-copy ortholog count across all summaries
-    let mut global_sco = 0usize;
-
-    for summary in &summaries {
-        for row in &summary.table {
-            // row.core_1to1 is the SCO count for this genome
-            // We only want to count *unique SCO groups*, not sum per-genome
-            // So track only once from one genome (e.g., the first)
-            global_sco = row.core_1to1 as usize;
-            break;
-        }
-        break;
-    }
-
     // Serialize to JSON
     let json = serde_json::to_string(&OrthologSummary { params, summaries, single_copy_orthologs: global_sco })?;
 
@@ -387,24 +578,35 @@ pub fn build_synteny_config(repo_entries: &[RepoEntry], genome_order: &[String],
             continue;
         }
 
-        // Genome FASTA
-        let genome_fasta_path = if let Some(genome_file) = entry.files.get("genome") {
-            PathBuf::from(&genome_file.path)
-        } else if let Some(genome_file) = entry.files.get("genome_parsed") {
-            PathBuf::from(&genome_file.path)
-        } else {
-            logger.warning(&format!("build_synteny_config: no genome FASTA for genome {}, skipping", genome));
-            continue;
-        };
+        // Genome FASTA (or GFF coordinates in proteome-only mode)
+        let (total_len, contig_map, fasta_order) = if let Some(genome_file) = entry.files.get("genome").or_else(|| entry.files.get("genome_parsed")) {
+            let genome_fasta_path = PathBuf::from(&genome_file.path);
+
+            // Total genome length
+            let total_len = read_fasta::fasta_to_total_seq_length(&genome_fasta_path)?;
+
+            // Contig -> length map
+            let contig_map = read_fasta::fasta_id_to_seq_length_hash(&genome_fasta_path)?;
+
+            // Order array
+            let fasta_order = read_fasta::fasta_id_to_order_array(&genome_fasta_path)?;
 
-        // Total genome length
-        let total_len = read_fasta::fasta_to_total_seq_length(&genome_fasta_path)?;
+            (total_len, contig_map, fasta_order)
+        } else if let Some((annotation_type, gff_file)) = read_repo::get_annotation_file(entry) {
+            logger.information(&format!("build_synteny_config: no genome FASTA for genome {}, using contig lengths from {}", genome, gff_file.path));
 
-        // Contig -> length map
-        let contig_map = read_fasta::fasta_id_to_seq_length_hash(&genome_fasta_path)?;
+            let (gff_order, contig_map) = if annotation_type == "gff" {
+                read_gff::gff_contig_lengths(Path::new(&gff_file.path), logger)
+            } else {
+                read_gene_table::gene_table_contig_lengths(Path::new(&gff_file.path), annotation_type, logger)
+            };
+            let total_len = contig_map.values().sum();
 
-        // Order array
-        let fasta_order = read_fasta::fasta_id_to_order_array(&genome_fasta_path)?;
+            (total_len, contig_map, gff_order)
+        } else {
+            logger.warning(&format!("build_synteny_config: no genome FASTA or GFF for genome {}, skipping", genome));
+            continue;
+        };
 
         // Convert contigs to struct list
         let contigs = contig_map.into_iter()
@@ -2,30 +2,30 @@ use crate::Logger;
 use crate::dagchainer::ClusterToGenes;
 use crate::util::{LogResultExt,mkdir,open_bufwrite};
 use crate::read_fasta;
+use crate::msa::{self, MultipleAligner};
+use crate::parse_dna_and_peptide;
 use crate::Args;
+use crate::scheduler;
 
 use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::Write;
 use std::collections::{HashSet,HashMap};
 use std::process::Command;
 use std::process::Stdio;
 
-use rayon::prelude::*;
-
-const MAX_MUSCLE_AA: usize = 20000;  // Muscle5 safe limit
-const MAX_MUSCLE_NT: usize = 60_000;   // 60k nt limit for nucleotide alignments
-
 // cluster_to_genes: HashMap<String, Vec<ClusterMember>>
 // ClusterMember { genome: String, trans_id: String }
+// Single-copy orthogroups in at least `min_genomes` genomes (all genomes unless --core_threshold is below 100)
 pub fn write_malign_files(
     cluster_to_genes: &ClusterToGenes,
     alignment_type: &str,
     pep_by_id: &HashMap<String, String>,
     malign_outdir: &Path,
     genomes_parsed: &HashSet<String>,
+    min_genomes: usize,
     logger: &Logger) {
 
     // Ensure directory exists
@@ -35,8 +35,9 @@ pub fn write_malign_files(
     let mut genomes: Vec<String> = genomes_parsed.iter().cloned().collect();
     genomes.sort();
     let n_genomes = genomes.len();
+    let min_genomes = min_genomes.clamp(2.min(n_genomes), n_genomes);
 
-    logger.information(&format!("write_cluster_pep_files: considering {} genomes for 1:1 ortholog clusters", n_genomes));
+    logger.information(&format!("write_cluster_pep_files: considering {} genomes for 1:1 ortholog clusters (present in at least {})", n_genomes, min_genomes));
 
     let mut cluster_ids: Vec<String> = cluster_to_genes.keys().cloned().collect();
     cluster_ids.sort();
@@ -57,36 +58,22 @@ pub fn write_malign_files(
 
         let members = &cluster_to_genes[&cluster_id];
 
-        // 2) Quick length check: must have exactly one member per genome
-        if members.len() != n_genomes {
+        // 2) Quick length check: must have at most one member per genome
+        if members.len() < min_genomes || members.len() > n_genomes {
             skipped_not_1to1 += 1;
+            remove_stale_alignment(malign_outdir, &cluster_id, alignment_type);
             continue;
         }
 
-        // 3) Count per genome and ensure exactly one per genome, and no extra genomes
+        // 3) Count per genome and ensure one gene in each genome present, in enough genomes
         let mut per_genome: HashMap<&str, usize> = HashMap::new();
         for m in members {
             *per_genome.entry(m.genome.as_str()).or_insert(0) += 1;
         }
 
-        if per_genome.len() != n_genomes {
-            skipped_not_1to1 += 1;
-            continue;
-        }
-
-        let mut is_1to1 = true;
-        for g in &genomes {
-            match per_genome.get(g.as_str()) {
-                Some(1) => {}
-                _ => {
-                    is_1to1 = false;
-                    break;
-                }
-            }
-        }
-
-        if !is_1to1 {
+        if per_genome.len() < min_genomes || per_genome.values().any(|&c| c != 1) || per_genome.keys().any(|g| !genomes_parsed.contains(*g)) {
             skipped_not_1to1 += 1;
+            remove_stale_alignment(malign_outdir, &cluster_id, alignment_type);
             continue;
         }
 
@@ -130,131 +117,284 @@ pub fn write_malign_files(
     logger.information(&format!("write_cluster_pep_files: clusters examined: {}, 1:1 orthologs: {}, unique clusters skipped: {}, skipped non-1:1: {}", total, written, skipped_uniq, skipped_not_1to1));
 }
 
-fn cluster_is_too_large(path: &Path, is_pep: bool, logger: &Logger) -> bool {
+/// Remove the FASTA and alignment of a cluster written by an earlier run with another --core_threshold
+fn remove_stale_alignment(malign_outdir: &Path, cluster_id: &str, alignment_type: &str) {
+    let fasta = malign_outdir.join(format!("{}.{}", cluster_id, alignment_type));
+    for derived in ["mfa", "pep", "pep.mfa", "codon.mfa"] {
+        let _ = fs::remove_file(fasta.with_extension(format!("{}.{}", alignment_type, derived)));
+    }
+    let _ = fs::remove_file(fasta);
+}
+
+fn cluster_is_too_large(path: &Path, msa: &dyn MultipleAligner, is_pep: bool, logger: &Logger) -> bool {
+
+    let Some(max_allowed) = msa.max_length(is_pep) else {
+        return false;
+    };
 
     // Load sequences from the cluster file
     let seqs = read_fasta::read_fasta(&path, &logger);
 
     let max_len = seqs.iter().map(|f| f.seq.len()).max().unwrap_or(0);
 
-    if is_pep {
-        if max_len > MAX_MUSCLE_AA {
-            logger.warning(&format!("Cluster {} has peptide length {} > {} aa: skipping MUSCLE", path.display(), max_len, MAX_MUSCLE_AA));
-            return true;
-        }
-    } else {
-        if max_len > MAX_MUSCLE_NT {
-            logger.warning(&format!("Cluster {} has nucleotide length {} > {} nt: skipping MUSCLE", path.display(), max_len, MAX_MUSCLE_NT));
-            return true;
-        }
+    if max_len > max_allowed {
+        // Aligners without a length limit for this sequence type
+        let alphabet = if is_pep { "pep" } else { "cds" };
+        let alternatives: Vec<&str> = msa::MSA_NAMES.iter().copied()
+            .filter(|name| msa::get_msa(name).map(|m| m.max_length(is_pep).is_none() && m.supported_alphabets().contains(&alphabet)).unwrap_or(false))
+            .collect();
+        logger.warning(&format!("Cluster {} has {} length {} > {}: skipping {} (--msa {} has no length limit)",
+            path.display(), alphabet, max_len, max_allowed, msa.display_name(), alternatives.join("/")));
+        return true;
     }
 
     false
 }
 
-pub fn run_muscle_on_clusters(
+/// Remove alignments made by another --msa aligner, so they are redone with this one
+fn remove_alignments_of_other_msa(malign_dir: &Path, msa: &dyn MultipleAligner, sequence_ext: &str, logger: &Logger) {
+    let marker = malign_dir.join(format!("msa_aligner.{}.txt", sequence_ext));
+    let mfa_suffix = format!(".{}.mfa", sequence_ext);
+    let previous = fs::read_to_string(&marker).map(|s| s.trim().to_string()).unwrap_or_else(|_| "muscle".to_string());
+
+    if previous != msa.name() {
+        logger.information(&format!("run_msa_on_clusters: removing *{} alignments made by {} in {}", mfa_suffix, previous, malign_dir.display()));
+        if let Ok(rd) = fs::read_dir(malign_dir) {
+            for path in rd.filter_map(|e| e.ok().map(|e| e.path())) {
+                if path.file_name().and_then(|n| n.to_str()).map(|n| n.ends_with(&mfa_suffix)).unwrap_or(false) {
+                    let _ = fs::remove_file(path);
+                }
+            }
+        }
+    }
+
+    fs::write(&marker, format!("{}\n", msa.name())).log_or_exit(logger, |e| {
+        format!("run_msa_on_clusters: failed to write {}: {}", marker.display(), e)
+    });
+}
+
+/// Run the --msa aligner on every `*.<sequence_ext>` cluster file in `malign_dir`, writing `*.<sequence_ext>.mfa`.
+/// `sequence_ext` is the alignment type, or "pep" for the translated CDS of codon alignments.
+pub fn run_msa_on_clusters(
     malign_dir: &Path,
-    muscle_path: &Path,
+    msa: &dyn MultipleAligner,
+    msa_path: &Path,
+    sequence_ext: &str,
     args: &Args,
     logger: &Logger) {
 
-    logger.information(&format!("run_muscle_on_clusters: running MUSCLE on peptide clusters in {}", malign_dir.display()));
+    let msa_name = msa.display_name();
+    logger.information(&format!("run_msa_on_clusters: running {} on .{} clusters in {}", msa_name, sequence_ext, malign_dir.display()));
 
-    let alignment_type = args.alignment_type.as_str();
     let total_threads = args.threads.max(1);
 
     // Make sure directory exists
-    mkdir(malign_dir, logger, "run_muscle_on_clusters");
+    mkdir(malign_dir, logger, "run_msa_on_clusters");
+    remove_alignments_of_other_msa(malign_dir, msa, sequence_ext, logger);
 
-    // Collect all *.pep files in MALIGN_DIR
+    // Collect all *.pep (or *.cds) files in MALIGN_DIR
     let read_dir = fs::read_dir(malign_dir).log_or_exit(logger, |e| {
-        format!("run_muscle_on_clusters: failed to read MALIGN_DIR {}: {}", malign_dir.display(), e)
+        format!("run_msa_on_clusters: failed to read MALIGN_DIR {}: {}", malign_dir.display(), e)
     });
 
     let mut cds_or_pep_files: Vec<PathBuf> = Vec::new();
 
     for entry_res in read_dir {
         let entry = entry_res.log_or_exit(logger, |e| {
-            format!("run_muscle_on_clusters: failed to read entry in {}: {}", malign_dir.display(), e)
+            format!("run_msa_on_clusters: failed to read entry in {}: {}", malign_dir.display(), e)
         });
 
         let path = entry.path();
-        if path.extension() == Some(OsStr::new(alignment_type)) {
+        if path.extension() == Some(OsStr::new(sequence_ext)) {
             cds_or_pep_files.push(path);
         }
     }
 
     if cds_or_pep_files.is_empty() {
-        logger.warning(&format!("run_muscle_on_clusters: no .{} files found for MUSCLE, skipping alignment step", alignment_type));
+        logger.warning(&format!("run_msa_on_clusters: no .{} files found for {}, skipping alignment step", sequence_ext, msa_name));
         return;
     }
 
     cds_or_pep_files.sort();
 
-    logger.information(&format!("run_muscle_on_clusters: found {} .{} files, running MUSCLE with {} threads", cds_or_pep_files.len(), alignment_type, total_threads));
+    logger.information(&format!("run_msa_on_clusters: found {} .{} files, running {} with {} threads", cds_or_pep_files.len(), sequence_ext, msa_name, total_threads));
 
-    // Build a dedicated rayon pool so we do not rely on the global one
-    let pool = rayon::ThreadPoolBuilder::new().num_threads(total_threads).build().log_or_exit(logger, |e| {format!("run_muscle_on_clusters: failed to build rayon thread pool for MUSCLE: {}", e) });
+    // Split threads between concurrent aligner runs and aligner threads, largest clusters first
+    let costs: Vec<u64> = cds_or_pep_files.iter().map(|p| scheduler::msa_cost(p)).collect();
+    let plan = scheduler::plan_threads(total_threads, cds_or_pep_files.len(), total_threads);
+    scheduler::log_plan("run_msa_on_clusters", cds_or_pep_files.len(), &plan, logger);
 
-    pool.install(|| {
-        cds_or_pep_files.par_iter().for_each(|cds_or_pep_path| {
+    scheduler::run_largest_first(&cds_or_pep_files, &costs, plan.concurrent_jobs, |cds_or_pep_path| {
+        // Determine if this is peptide or nucleotide
+        let is_pep = sequence_ext == "pep";
 
-            // Determine if this is peptide or nucleotide
-            let is_pep = alignment_type == "pep";
+        // Skip the aligner if any sequence exceeds its allowable size
+        if cluster_is_too_large(cds_or_pep_path, msa, is_pep, logger) {
+            return;  // do not crash, just skip the alignment
+        }
 
-            // Skip MUSCLE if any sequence exceeds allowable size
-            if cluster_is_too_large(cds_or_pep_path, is_pep, logger) {
-                logger.warning(&format!("run_muscle_on_clusters: Skipping MUSCLE: {} is too large for alignment", cds_or_pep_path.display()));
-                return;  // do not crash, just skip MUSCLE
-            }
+        // Output is "<pep>.mfa", same as Perl: $opt_s.mfa
+        let ext = format!("{}.mfa", sequence_ext);
+        let mfa_path = cds_or_pep_path.with_extension(ext);
+
+        // Skip if output already exists and is non empty
+        let already_done = mfa_path.metadata().map(|m| m.len() > 0).unwrap_or(false);
 
-            // Output is "<pep>.mfa", same as Perl: $opt_s.mfa
-            let ext = format!("{}.mfa", alignment_type);
-            let mfa_path = cds_or_pep_path.with_extension(ext);
+        if already_done {
+            logger.information(&format!("run_msa_on_clusters: alignment already exists, skipping {}: {}", msa_name, mfa_path.display()));
+            return;
+        }
 
-            // Skip if output already exists and is non empty
-            let already_done = mfa_path.metadata().map(|m| m.len() > 0).unwrap_or(false);
+        // Align to a temporary file, so a failed or killed run never leaves a partial .mfa
+        let tmp_path = PathBuf::from(format!("{}.tmp", mfa_path.display()));
+        let status = msa.align_command(msa_path, cds_or_pep_path, &tmp_path, is_pep, plan.threads_per_job)
+            .and_then(|mut cmd| cmd.status().map_err(|e| e.to_string()));
 
-            if already_done {
-                logger.information(&format!("run_muscle_on_clusters: alignment already exists, skipping MUSCLE: {}", mfa_path.display()));
-                return;
+        match status {
+            Ok(st) if st.success() => {
+                fs::rename(&tmp_path, &mfa_path).log_or_exit(logger, |e| {
+                    format!("run_msa_on_clusters: failed to rename {} to {}: {}", tmp_path.display(), mfa_path.display(), e)
+                });
+            }
+            Ok(st) => {
+                let _ = fs::remove_file(&tmp_path);
+                logger.error(&format!("run_msa_on_clusters: {} failed for {} with status {}", msa_name, cds_or_pep_path.display(), st));
+                std::process::exit(1);
             }
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path);
+                logger.error(&format!("run_msa_on_clusters: failed to start {} for {}: {}", msa_name, cds_or_pep_path.display(), e));
+                std::process::exit(1);
+            }
+        }
+    });
 
-            //logger.information(&format!("run_muscle_on_clusters: MUSCLE aligning {} -> {}", pep_path.display(), mfa_path.display()));
+    logger.information(&format!("run_msa_on_clusters: {} alignments complete", msa_name));
+}
 
-            let status = Command::new(muscle_path)
-                .arg("-align").arg(cds_or_pep_path)
-                .arg("-output").arg(&mfa_path)
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                // Keep MUSCLE single threaded and let rayon control concurrency
-                .arg("-threads").arg("1")
-                .status();
+/// Translate each single-copy CDS cluster (`*.cds`) to `*.cds.pep` for codon alignment.
+/// Terminal stop codons are dropped and internal stops become X, so the aligner sees plain residues
+/// and every residue still corresponds to one codon of the CDS.
+pub fn write_codon_peptides(
+    malign_dir: &Path,
+    genetic_code: usize,
+    logger: &Logger) {
 
-            match status {
-                Ok(st) if st.success() => {
-                    //logger.information(&format!("run_muscle_on_clusters: MUSCLE finished for {}", pep_path.display()));
-                }
-                Ok(st) => {
-                    logger.error(&format!("run_muscle_on_clusters: MUSCLE failed for {} with status {}", cds_or_pep_path.display(), st));
-                    std::process::exit(1);
-                }
-                Err(e) => {
-                    logger.error(&format!("run_muscle_on_clusters: failed to start MUSCLE for {}: {}", cds_or_pep_path.display(), e));
-                    std::process::exit(1);
-                }
+    let read_dir = fs::read_dir(malign_dir).log_or_exit(logger, |e| {
+        format!("write_codon_peptides: failed to read MALIGN_DIR {}: {}", malign_dir.display(), e)
+    });
+
+    let mut cds_files: Vec<PathBuf> = read_dir
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension() == Some(OsStr::new("cds")))
+        .collect();
+    cds_files.sort();
+
+    let mut written = 0usize;
+    for cds_path in &cds_files {
+        let pep_path = cds_path.with_extension("cds.pep");
+        let already_exists = pep_path.metadata().map(|m| m.len() > 0).unwrap_or(false);
+        if already_exists {
+            continue;
+        }
+
+        let mut writer = open_bufwrite(&pep_path, logger, "write_codon_peptides");
+        for rec in read_fasta::read_fasta(cds_path, logger) {
+            let mut pep = parse_dna_and_peptide::translate_dna_to_peptide(&rec.seq, &rec.id, genetic_code, logger);
+            if pep.ends_with('*') {
+                pep.pop();
             }
-        });
+            let pep = pep.replace('*', "X");
+            writeln!(writer, ">{}", rec.id).unwrap();
+            writeln!(writer, "{}", pep).unwrap();
+        }
+        written += 1;
+    }
+
+    logger.information(&format!("write_codon_peptides: translated {} CDS clusters ({} already translated)", written, cds_files.len() - written));
+}
+
+/// Codon alignment of one CDS from its aligned protein: "---" for each gap and the next codon
+/// for each residue, or "NNN" once the CDS runs out. Returns the alignment and the number of
+/// missing codons.
+pub fn thread_codons(aligned_pep: &str, cds: &str) -> (String, usize) {
+    let mut codons = cds.as_bytes().chunks_exact(3);
+    let mut codon_seq = String::with_capacity(aligned_pep.len() * 3);
+    let mut missing = 0usize;
+    for residue in aligned_pep.chars() {
+        if residue == '-' || residue == '.' {
+            codon_seq.push_str("---");
+            continue;
+        }
+        match codons.next() {
+            Some(codon) => codon_seq.push_str(std::str::from_utf8(codon).unwrap_or("NNN")),
+            None => {
+                codon_seq.push_str("NNN");
+                missing += 1;
+            }
+        }
+    }
+    (codon_seq, missing)
+}
+
+/// Thread each CDS cluster through its protein alignment (`*.cds.pep.mfa`), pal2nal-style, writing
+/// the codon alignment `*.cds.codon.mfa`. A gap in the protein alignment becomes "---" and each
+/// residue takes the next codon of the CDS; trailing partial codons and the stop codon are dropped.
+pub fn thread_cds_through_alignments(
+    malign_dir: &Path,
+    logger: &Logger) {
+
+    let read_dir = fs::read_dir(malign_dir).log_or_exit(logger, |e| {
+        format!("thread_cds_through_alignments: failed to read MALIGN_DIR {}: {}", malign_dir.display(), e)
     });
 
-    logger.information("run_muscle_on_clusters: MUSCLE alignments complete");
+    let mut pep_alignments: Vec<PathBuf> = read_dir
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.file_name().and_then(|n| n.to_str()).map(|n| n.ends_with(".cds.pep.mfa")).unwrap_or(false))
+        .collect();
+    pep_alignments.sort();
+
+    if pep_alignments.is_empty() {
+        logger.warning(&format!("thread_cds_through_alignments: no .cds.pep.mfa alignments found in {}", malign_dir.display()));
+        return;
+    }
+
+    for pep_mfa in &pep_alignments {
+        // X.cds.pep.mfa -> X.cds and X.cds.codon.mfa
+        let cds_path = pep_mfa.with_extension("").with_extension("");
+        let codon_path = cds_path.with_extension("cds.codon.mfa");
+
+        let cds_by_id: HashMap<String, String> = read_fasta::read_fasta(&cds_path, logger)
+            .into_iter()
+            .map(|rec| (rec.id, rec.seq.to_ascii_uppercase()))
+            .collect();
+
+        let mut writer = open_bufwrite(&codon_path, logger, "thread_cds_through_alignments");
+        for rec in read_fasta::read_fasta(pep_mfa, logger) {
+            let cds = cds_by_id.get(&rec.id).unwrap_or_else(|| {
+                logger.error(&format!("thread_cds_through_alignments: no CDS for {} in {}", rec.id, cds_path.display()));
+                std::process::exit(1);
+            });
+
+            let (codon_seq, missing) = thread_codons(&rec.seq, cds);
+            if missing > 0 {
+                logger.warning(&format!("thread_cds_through_alignments: CDS of {} is {} codons shorter than its protein in {}", rec.id, missing, pep_mfa.display()));
+            }
+
+            writeln!(writer, ">{}", rec.id).unwrap();
+            writeln!(writer, "{}", codon_seq).unwrap();
+        }
+    }
+
+    logger.information(&format!("thread_cds_through_alignments: wrote {} codon alignments", pep_alignments.len()));
 }
 
-/// Concatenate all MUSCLE alignments (*.pep.mfa) in `malign_dir`
+/// Concatenate all multiple alignments (*.pep.mfa) in `malign_dir`
 /// into a single alignment per genome and write as FASTA.
 ///
 /// `genomes_parsed` should be the set of genomes that appear in the
 /// 1:1 core orthogroups (from `save_gene_ids_from_ortholog_file`).
+/// Genomes missing from an alignment are filled with gaps.
 pub fn concatenate_alignments_and_write(
     malign_dir: &Path,
     genomes_parsed: &HashSet<String>,
@@ -367,17 +507,10 @@ pub fn concatenate_alignments_and_write(
             }
         }
 
-        // QC: every genome must be present in this alignment
+        // Append to concatenated sequences (gaps for genomes missing from orthogroups below 100% core)
+        let gaps = "-".repeat(length_of_seq.unwrap_or(0));
         for g in &genomes {
-            if !this_alignment.contains_key(g) {
-                logger.error(&format!("concatenate_alignments_and_write: alignment {} missing genome {}", aln.display(), g));
-                std::process::exit(1);
-            }
-        }
-
-        // Append to concatenated sequences
-        for g in &genomes {
-            let seg = this_alignment.get(g).expect("checked above");
+            let seg = this_alignment.get(g).unwrap_or(&gaps);
             let current = concat_alignment.get_mut(g).expect("pre-initialised for all genomes");
             current.push_str(seg);
         }
@@ -474,6 +607,53 @@ pub fn run_fasttree_on_alignment(
     logger.information(&format!("run_fasttree_on_alignment: wrote tree to {}",tree_path.display()));
 }
 
+/// Tree built by FastTree from the concatenated single-copy core alignment
+pub fn supermatrix_tree_path(tree_out_dir: &Path, alignment_type: &str, method_label: &str) -> PathBuf {
+    tree_out_dir.join(format!("SC_core_concat.{}.{}.mfa.tree", alignment_type, method_label))
+}
+
+/// Rooted species tree imported from a full OrthoFinder run
+pub fn species_tree_path(tree_out_dir: &Path, alignment_type: &str, method_label: &str) -> PathBuf {
+    tree_out_dir.join(format!("SpeciesTree_rooted.{}.{}.species.tree", alignment_type, method_label))
+}
+
+/// The tree used for the report: the imported species tree if present, otherwise the supermatrix tree
+pub fn tree_path_for_method(tree_out_dir: &Path, alignment_type: &str, method_label: &str) -> PathBuf {
+    let species_tree = species_tree_path(tree_out_dir, alignment_type, method_label);
+    if species_tree.is_file() {
+        species_tree
+    } else {
+        supermatrix_tree_path(tree_out_dir, alignment_type, method_label)
+    }
+}
+
+/// Copy OrthoFinder's rooted species tree (SpeciesTree_rooted.txt) into the tree output,
+/// warning about genomes missing from the tree
+pub fn import_species_tree(species_tree: &Path, tree_path: &Path, genomes: &HashSet<String>, logger: &Logger) {
+
+    let newick = fs::read_to_string(species_tree).log_or_exit(logger, |e| {
+        format!("import_species_tree: failed to read {}: {}", species_tree.display(), e)
+    });
+    let newick = newick.trim();
+    if !newick.starts_with('(') || !newick.ends_with(';') {
+        logger.error(&format!("import_species_tree: {} is not a Newick tree", species_tree.display()));
+        std::process::exit(1);
+    }
+
+    let leaves: HashSet<String> = extract_leaf_order_from_newick(newick).into_iter().collect();
+    let mut missing: Vec<&String> = genomes.iter().filter(|g| !leaves.contains(*g)).collect();
+    missing.sort();
+    if !missing.is_empty() {
+        logger.warning(&format!("import_species_tree: genomes missing from the species tree: {}", missing.iter().map(|g| g.as_str()).collect::<Vec<_>>().join(", ")));
+    }
+
+    fs::write(tree_path, format!("{}\n", newick)).log_or_exit(logger, |e| {
+        format!("import_species_tree: failed to write tree {}: {}", tree_path.display(), e)
+    });
+
+    logger.information(&format!("import_species_tree: wrote OrthoFinder species tree to {}", tree_path.display()));
+}
+
 pub fn extract_leaf_order_from_newick(newick: &str) -> Vec<String> {
     let mut leaves = Vec::new();
     let mut token = String::new();
@@ -502,4 +682,88 @@ pub fn extract_leaf_order_from_newick(newick: &str) -> Vec<String> {
     }
 
     leaves
-}
\ No newline at end of file
+}
+
+/// Leaf sets of the internal nodes of a Newick tree (internal labels and branch lengths are ignored)
+pub fn newick_clades(newick: &str) -> Vec<BTreeSet<String>> {
+    let mut clades: Vec<BTreeSet<String>> = Vec::new();
+    let mut open: Vec<BTreeSet<String>> = Vec::new();
+    let mut token = String::new();
+    let mut after_close = false; // a label straight after ')' names an internal node
+    let mut in_length = false;
+
+    for c in newick.chars() {
+        match c {
+            '(' => {
+                open.push(BTreeSet::new());
+                token.clear();
+                after_close = false;
+                in_length = false;
+            }
+            ',' | ')' | ';' => {
+                let leaf = token.trim().trim_matches('\'');
+                if !after_close && !leaf.is_empty() {
+                    if let Some(top) = open.last_mut() {
+                        top.insert(leaf.to_string());
+                    }
+                }
+                token.clear();
+                in_length = false;
+                after_close = false;
+                if c == ')' {
+                    if let Some(clade) = open.pop() {
+                        if let Some(parent) = open.last_mut() {
+                            parent.extend(clade.iter().cloned());
+                        }
+                        clades.push(clade);
+                    }
+                    after_close = true;
+                }
+            }
+            ':' => in_length = true,
+            _ if !in_length => token.push(c),
+            _ => {}
+        }
+    }
+    clades
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaps_become_gap_codons() {
+        // M-K. with stop codon TAA left over
+        assert_eq!(thread_codons("M-K.", "ATGAAATAA"), ("ATG---AAA---".to_string(), 0));
+    }
+
+    #[test]
+    fn leading_and_trailing_gaps() {
+        assert_eq!(thread_codons("--MK-", "ATGAAA"), ("------ATGAAA---".to_string(), 0));
+    }
+
+    #[test]
+    fn partial_codon_dropped() {
+        assert_eq!(thread_codons("MK", "ATGAAAGC"), ("ATGAAA".to_string(), 0));
+    }
+
+    #[test]
+    fn short_cds_padded_with_n() {
+        // the CDS has 2 codons (and a partial one) for 3 residues
+        assert_eq!(thread_codons("M-KL", "ATGAAAG"), ("ATG---AAANNN".to_string(), 1));
+        assert_eq!(thread_codons("MKL", ""), ("NNNNNNNNN".to_string(), 3));
+    }
+
+    #[test]
+    fn threads_alignment_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("OG1.cds"), ">A|a1\natgaaatga\n>B|b1\nATGCCCAAATAA\n").unwrap();
+        fs::write(dir.path().join("OG1.cds.pep.mfa"), ">A|a1\nM-K\n>B|b1\nMPK\n").unwrap();
+
+        thread_cds_through_alignments(dir.path(), &Logger);
+
+        let codon = fs::read_to_string(dir.path().join("OG1.cds.codon.mfa")).unwrap();
+        assert_eq!(codon, ">A|a1\nATG---AAA\n>B|b1\nATGCCCAAA\n");
+    }
+}
@@ -115,11 +115,13 @@ where
     }
 }
 
-pub fn get_version(tool: &str, args: &[&str]) -> Option<String> {
+pub fn get_version<T: AsRef<std::ffi::OsStr>>(tool: T, args: &[&str]) -> Option<String> {
     match std::process::Command::new(tool)
         .args(args)
         .output()
     {
+        // a tool that fails to run (e.g. missing shared libraries) has no version to report
+        Ok(out) if !out.status.success() => None,
         Ok(out) => {
             let text = String::from_utf8_lossy(&out.stdout).to_string();
             if text.trim().is_empty() {
@@ -152,8 +154,8 @@ pub fn get_orthology_tool_version(method: &str) -> String {
         }
 
         "orthomcl" => {
-            // Synima always uses OrthoMCL v1.4
-            "1.4".into()
+            // Synima implements the OrthoMCL v1.4 algorithm natively
+            "1.4 (Synima2 native)".into()
         }
 
         "rbh" => {
@@ -161,27 +163,23 @@ pub fn get_orthology_tool_version(method: &str) -> String {
             "Part of Synima2 pipeline".into()
         }
 
-        other => format!("Unknown method '{}'", other),
-    }
-}
+        "mcl" => {
+            // Synima built-in Markov clustering
+            "Part of Synima2 pipeline".into()
+        }
 
-pub fn clean_blast_version(raw: &str) -> String {
-    let mut s = raw.trim().to_string();
+        "synteny" => {
+            // Synima built-in synteny-aware orthology
+            "Part of Synima2 pipeline".into()
+        }
 
-    // strip leading "blastp:", "blastn:", etc
-    if let Some(idx) = s.find(':') {
-        let after = s[(idx + 1)..].trim().to_string();
-        if after.chars().next().map(|c| c.is_ascii_digit()).unwrap_or(false) {
-            s = after;
+        "imported" => {
+            // Orthogroups computed outside Synima (see the provenance in the methods table)
+            "N/A (imported)".into()
         }
-    }
 
-    // remove everything after "Package:"
-    if let Some(idx) = s.to_lowercase().find("package") {
-        s = s[..idx].trim().to_string();
+        other => format!("Unknown method '{}'", other),
     }
-
-    s
 }
 
 pub fn clean_muscle_version(raw: &str) -> String {
@@ -203,27 +201,3 @@ pub fn clean_muscle_version(raw: &str) -> String {
 
     s
 }
-
-pub fn clean_diamond_version(raw: &str) -> String {
-    let s = raw.trim();
-
-    // Try: "diamond version 2.1.6"
-    if let Some(rest) = s.strip_prefix("diamond version ") {
-        return rest.trim().to_string();
-    }
-
-    // Try: "diamond v2.1.6"
-    if let Some(rest) = s.strip_prefix("diamond ") {
-        return rest.trim().to_string();
-    }
-
-    // Try: split on whitespace and look for something that starts with digits
-    for token in s.split_whitespace() {
-        if token.chars().next().map(|c| c.is_ascii_digit()).unwrap_or(false) {
-            return token.to_string();
-        }
-    }
-
-    // Fallback: return cleaned string
-    s.to_string()
-}
\ No newline at end of file
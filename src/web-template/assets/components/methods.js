@@ -34,18 +34,22 @@ SYNIMA.showMethods = function () {
   const orthologyToolEntry  = findCategory("Orthology tool")[0];
   const alignerEntry        = findCategory("Aligner")[0];
   const alignParams         = findCategory("Aligner parameters");
-  const muscleEntry         = findCategory("Multiple aligner")[0];
+  const orthologyParams     = findCategory("Orthology parameters");
+  const msaEntry            = findCategory("Multiple aligner")[0];
+  const codonEntry          = findCategory("Multiple alignment parameters").find(p => p.name === "codon_alignment");
   const fasttreeEntry       = findCategory("Tree builder")[0];
   const dagEntry            = findCategory("Synteny chaining")[0];
   const dagParams           = findCategory("Synteny chaining parameters")[0];
   const seqTypeEntry        = findCategory("Sequence type")[0];
+  const classParams         = findCategory("Orthogroup classification");
 
   const synima_version      = synima ? synima.version : "-";
   const orthology_tool      = orthologyToolEntry ? orthologyToolEntry.name : "-";
   const orthology_version   = orthologyToolEntry ? orthologyToolEntry.version : "-";
   const aligner             = alignerEntry ? alignerEntry.name : "-";
   const aligner_version     = alignerEntry ? alignerEntry.version : "-";
-  const muscle_version      = muscleEntry ? muscleEntry.version : "-";
+  const msa_name            = msaEntry ? msaEntry.name : "MUSCLE";
+  const msa_version         = msaEntry ? msaEntry.version : "-";
   const fasttree_version    = fasttreeEntry ? fasttreeEntry.version : "-";
   const dagchainer_version  = dagEntry ? dagEntry.version : "-";
   const dagchainer_chains   = dagParams ? dagParams.version : "-";
@@ -63,21 +67,71 @@ SYNIMA.showMethods = function () {
   // Extract aligner parameters
   let max_target_seqs = "-";
   let evalue          = "-";
-  let diamond_extra   = "";
+  let aligner_extra   = "";
 
   alignParams.forEach(p => {
     if (p.name === "max_target_seqs") max_target_seqs = p.version;
-    if (p.name === "evalue")          evalue = p.version;
-    if (p.name === "diamond_sensitivity")
-      diamond_extra = `, diamond_sensitivity=${p.version}`;
+    else if (p.name === "evalue")     evalue = p.version;
+    else aligner_extra += `, ${p.name}=${p.version}`; // e.g. diamond_sensitivity, mmseqs_sensitivity
   });
 
+  // Orthology parameters (e.g. rbh_tie_tolerance, rbh_min_coverage)
+  const orthology_mcl = orthology_tool.toLowerCase() === "synteny"
+    ? ", splitting homology clusters from Markov clustering [[MCL]] into positional orthologs by collinearity of the hits along gene order"
+    : orthology_tool.toLowerCase() !== "mcl" && orthologyParams.some(p => p.name === "inflation")
+      ? " with Markov clustering [[MCL]]"
+      : "";
+  const orthology_extra = orthologyParams.length
+    ? `${orthology_mcl} (${orthologyParams.map(p => `${p.name}=${p.version}`).join(", ")})`
+    : "";
+
+  // Tree: OrthoFinder's rooted species tree (full run) or the FastTree supermatrix tree
+  const species_tree = fasttreeEntry && fasttreeEntry.name.toLowerCase().startsWith("orthofinder");
+
+  // Orthogroup classes (% of genomes); summaries from older versions have core at 100%
+  const classThreshold = (name, fallback) => {
+    const p = classParams.find(x => x.name === name);
+    return p ? Number(p.version) : fallback;
+  };
+  const core_threshold      = classThreshold("core_threshold", 100);
+  const soft_core_threshold = classThreshold("soft_core_threshold", core_threshold);
+  const cloud_threshold     = classThreshold("cloud_threshold", 0);
+
+  const class_desc = core_threshold >= 100 && soft_core_threshold >= core_threshold && cloud_threshold <= 0
+    ? `Orthogroups assigned by Synima were classified into core, accessory, and unique categories.`
+    : `Orthogroups assigned by Synima were classified as core (present in at least ${core_threshold}% of genomes)${
+        soft_core_threshold < core_threshold ? `, soft-core (at least ${soft_core_threshold}%)` : ""
+      }, accessory (shell${cloud_threshold > 0 ? `, at least ${cloud_threshold}%` : ""})${
+        cloud_threshold > 0 ? `, cloud (fewer than ${cloud_threshold}% of genomes)` : ""
+      } or unique (a single genome).`;
+  const sco_desc = core_threshold >= 100
+    ? "single-copy core orthologs were identified"
+    : `single-copy orthologs present in at least ${core_threshold}% of genomes were identified (missing genomes were treated as gaps)`;
+
   // Number of single-copy orthologs
   const num_single_copy =
     orthData && orthData.single_copy_orthologs !== undefined
       ? orthData.single_copy_orthologs
       : 0;
 
+  // Codon alignments: CDS threaded through the protein alignments (MUSCLE, MAFFT or FAMSA)
+  const msa_desc = codonEntry
+    ? `Each orthogroup of single-copy orthologs was translated (${codonEntry.version}) and the proteins aligned
+  separately using ${msa_name} v${msa_version} [[${msa_name}]] with default settings; the coding sequences were then threaded
+  through the protein alignments to give codon alignments.`
+    : `Each orthogroup of single-copy orthologs was aligned separately using ${msa_name} v${msa_version} [[${msa_name}]]
+  with default settings.`;
+
+  const tree_desc = species_tree
+    ? `${class_desc}
+  The rooted species tree was inferred by OrthoFinder v${fasttree_version} [[OrthoFinder]] from the gene trees
+  of all orthogroups (STAG and STRIDE), and gene duplication events were identified by reconciling each
+  gene tree with the species tree.`
+    : `${class_desc}
+  ${num_single_copy} ${sco_desc} and used to construct a phylogenetic tree.
+  ${msa_desc} All alignments were concatenated into a single FASTA, and an
+  'approximately maximum-likelihood' tree was inferred using FastTree v${fasttree_version} [[FastTree]].`;
+
   // --------------------------------------------------------------------
   // Citation engine (Option A: first-appearance ordering)
   // --------------------------------------------------------------------
@@ -116,8 +170,10 @@ SYNIMA.showMethods = function () {
 
   function citationKeyForAligner(aligner) {
     const a = aligner.toLowerCase();
-    if (a === "blastplus" || a === "legacy") return "BLAST+";
+    if (a === "blastplus") return "BLAST+";
+    if (a === "blastlegacy" || a === "legacy") return "BLAST";
     if (a === "diamond") return "DIAMOND";
+    if (a === "mmseqs" || a === "mmseqs2") return "MMseqs2";
     return aligner;
   }
 
@@ -160,21 +216,29 @@ SYNIMA.showMethods = function () {
   // Build Description with placeholder tags
   // --------------------------------------------------------------------
 
+  // Imported all-vs-all results were computed outside Synima
+  const search_desc = aligner === "imported"
+    ? `precomputed outside Synima and imported from ${aligner_version.replace(/^external search results \((.*)\)$/, "$1")}.`
+    : `computed with
+  ${aligner} v${aligner_version} [[${citationKeyForAligner(aligner)}]]
+  using the parameters max_target_seqs=${max_target_seqs}, evalue=${evalue}${aligner_extra}.`;
+
+  // Imported orthogroups were computed outside Synima, version reads "<tool> orthogroups (<path>)"
+  const imported_match = orthology_version.match(/^(.*) orthogroups \((.*)\)$/);
+  const orthology_desc = orthology_tool.toLowerCase() === "imported" && imported_match
+    ? `Orthogroups were computed outside Synima${imported_match[1] === "external" ? "" : ` with ${imported_match[1]} [[${imported_match[1]}]]`}
+  and imported from ${imported_match[2]}, with gene IDs mapped to the ${sequence_type_human} sequences parsed by Synima.`
+    : `Orthologs were inferred using ${orthology_tool} v${orthology_version} [[${orthology_tool}]]${orthology_extra}
+  based on an all-vs-all comparison of ${sequence_type_human} sequences ${search_desc}`;
+
   let desc = `
 <p>
   Ortholog prediction and synteny analysis were performed using Synima v${synima_version} [[Synima]].
-  Orthologs were inferred using ${orthology_tool} v${orthology_version} [[${orthology_tool}]]
-  based on an all-vs-all comparison of ${sequence_type_human} sequences computed with
-  ${aligner} v${aligner_version} [[${citationKeyForAligner(aligner)}]]
-  using the parameters max_target_seqs=${max_target_seqs}, evalue=${evalue}${diamond_extra}.
+  ${orthology_desc}
 </p>
 
 <p>
-  Orthogroups assigned by Synima were classified into core, accessory, and unique categories.
-  ${num_single_copy} single-copy core orthologs were identified and used to construct a phylogenetic tree.
-  Each orthogroup of single-copy orthologs was aligned separately using MUSCLE v${muscle_version} [[MUSCLE]]
-  with default settings. All alignments were concatenated into a single FASTA, and an
-  'approximately maximum-likelihood' tree was inferred using FastTree v${fasttree_version} [[FastTree]].
+  ${tree_desc}
 </p>
 
 <p>
@@ -9,10 +9,33 @@ const BREWER_SET2_8 = [
 const ORTHO_CATS = [
   { key: "core_1to1",  label: "Core (1:1)",     color: BREWER_SET2_8[0] },
   { key: "core_multi", label: "Core (multi)",   color: BREWER_SET2_8[1] },
+  { key: "soft_core",  label: "Soft-core",      color: BREWER_SET2_8[4] },
   { key: "aux",        label: "Aux",           color: BREWER_SET2_8[2] },
+  { key: "cloud",      label: "Cloud",         color: BREWER_SET2_8[5] },
   { key: "unique",     label: "Unique",        color: BREWER_SET2_8[3] },
 ];
 
+// Classes the summary's thresholds can produce (soft-core and cloud only when enabled)
+function orthoCatsFor(summary) {
+  const t = summary.thresholds || {};
+  const core = t.core !== undefined ? t.core : 100;
+  const softCore = t.soft_core !== undefined ? t.soft_core : core;
+  const cloud = t.cloud !== undefined ? t.cloud : 0;
+  return ORTHO_CATS.filter(c =>
+    (c.key !== "soft_core" || softCore < core) &&
+    (c.key !== "cloud" || cloud > 0)
+  );
+}
+
+function orthoThresholdText(summary) {
+  const t = summary.thresholds || {};
+  if (t.core === undefined || (t.core >= 100 && t.soft_core >= t.core && t.cloud <= 0)) return "";
+  let text = `Core: present in at least ${t.core}% of genomes`;
+  if (t.soft_core < t.core) text += `; soft-core: at least ${t.soft_core}%`;
+  text += t.cloud > 0 ? `; aux (shell): at least ${t.cloud}%; cloud: fewer than ${t.cloud}%` : "; aux: more than one genome";
+  return text + "; unique: a single genome.";
+}
+
 function ensureOrthoTooltip() {
   let tip = document.getElementById("ortho-tooltip");
   if (tip) return tip;
@@ -125,7 +148,8 @@ function renderOrthologStackedChart(summary, containerId) {
   const heightNeeded = (axisY + 10 + TICK_FONT + 6 + AXIS_TITLE_FONT + 18) + Math.round(FONT * 1.2);
 
   // Find max total for scaling
-  const totals = rows.map(r => ORTHO_CATS.reduce((s, c) => s + (Number(r[c.key]) || 0), 0));
+  const cats = orthoCatsFor(summary);
+  const totals = rows.map(r => cats.reduce((s, c) => s + (Number(r[c.key]) || 0), 0));
   const maxTotal = Math.max(...totals, 1);
 
   // Axis unit choice: hundreds or thousands
@@ -173,7 +197,7 @@ function renderOrthologStackedChart(summary, containerId) {
     svg.appendChild(t);
 
     let x0 = 0;
-    ORTHO_CATS.forEach(cat => {
+    cats.forEach(cat => {
       const v = Number(r[cat.key]) || 0;
       const w = (v / axisMaxGenes) * innerW;
 
@@ -256,7 +280,7 @@ function renderOrthologStackedChart(summary, containerId) {
   const legend = document.createElementNS(ns, "g");
   const legendX = margin.left + innerW + 24;
   const legendY = margin.top;
-  const legendItems = [...ORTHO_CATS].reverse();
+  const legendItems = [...cats].reverse();
 
   const swatch = Math.round(FONT * 0.9);
   const legendGap = Math.round(FONT * 1.15);
@@ -290,6 +314,252 @@ function renderOrthologStackedChart(summary, containerId) {
   container.appendChild(svg);
 }
 
+// ----------------------------
+// Pan/core-genome accumulation curves (mean with min-max band over random genome orders)
+// ----------------------------
+const PANGENOME_SERIES = [
+  { key: "pan",  label: "Pan-genome",  color: BREWER_SET2_8[1] },
+  { key: "core", label: "Core genome", color: BREWER_SET2_8[0] },
+];
+
+function renderPangenomeChart(pangenome, containerId) {
+  const container = document.getElementById(containerId);
+  if (!container) return;
+
+  const rows = (pangenome && Array.isArray(pangenome.curves)) ? pangenome.curves : [];
+  if (!rows.length) {
+    container.innerHTML = "<p>No pan-genome curves available.</p>";
+    return;
+  }
+
+  const FONT = 26;
+  const TICK_FONT = Math.round(FONT * 0.9);
+  const margin = { top: 24, right: 340, bottom: 120, left: 160 };
+  const innerW = 900;
+  const innerH = 520;
+  const width = margin.left + innerW + margin.right;
+  const height = margin.top + innerH + margin.bottom;
+
+  const maxN = Math.max(...rows.map(r => r.genomes), 1);
+  const maxY = Math.max(...rows.map(r => r.pan_max), 1);
+  const stepY = niceStep(maxY / 5);
+  const axisMaxY = Math.ceil(maxY / stepY) * stepY;
+  const stepX = Math.max(1, niceStep(maxN / 10));
+
+  const xPos = n => margin.left + (maxN > 1 ? ((n - 1) / (maxN - 1)) * innerW : innerW / 2);
+  const yPos = v => margin.top + innerH - (v / axisMaxY) * innerH;
+
+  const ns = "http://www.w3.org/2000/svg";
+  const svg = document.createElementNS(ns, "svg");
+  svg.setAttribute("viewBox", `0 0 ${width} ${height}`);
+  svg.setAttribute("width", "100%");
+  svg.setAttribute("height", String(height));
+  svg.style.display = "block";
+
+  function el(tag, attrs, text) {
+    const e = document.createElementNS(ns, tag);
+    Object.entries(attrs).forEach(([k, v]) => e.setAttribute(k, String(v)));
+    if (text !== undefined) e.textContent = text;
+    svg.appendChild(e);
+    return e;
+  }
+
+  // axes, ticks and grid
+  el("line", { x1: margin.left, x2: margin.left + innerW, y1: margin.top + innerH, y2: margin.top + innerH, stroke: "#111", "stroke-width": 2 });
+  el("line", { x1: margin.left, x2: margin.left, y1: margin.top, y2: margin.top + innerH, stroke: "#111", "stroke-width": 2 });
+  for (let v = 0; v <= axisMaxY + 1e-9; v += stepY) {
+    const y = yPos(v);
+    el("line", { x1: margin.left, x2: margin.left + innerW, y1: y, y2: y, stroke: "#e5e5e5", "stroke-width": 1 });
+    el("text", { x: margin.left - 12, y: y + TICK_FONT * 0.35, "text-anchor": "end", "font-family": "sans-serif", "font-size": TICK_FONT, fill: "#111" }, String(v));
+  }
+  for (let n = 1; n <= maxN; n += stepX) {
+    const x = xPos(n);
+    el("line", { x1: x, x2: x, y1: margin.top + innerH, y2: margin.top + innerH + 10, stroke: "#111", "stroke-width": 2 });
+    el("text", { x: x, y: margin.top + innerH + 10 + TICK_FONT + 6, "text-anchor": "middle", "font-family": "sans-serif", "font-size": TICK_FONT, fill: "#111" }, String(n));
+  }
+  el("text", { x: margin.left + innerW / 2, y: height - 24, "text-anchor": "middle", "font-family": "sans-serif", "font-size": FONT, fill: "#111" }, "Number of genomes");
+  el("text", { x: 36, y: margin.top + innerH / 2, "text-anchor": "middle", "font-family": "sans-serif", "font-size": FONT, fill: "#111",
+               transform: `rotate(-90 36 ${margin.top + innerH / 2})` }, "Number of orthogroups");
+
+  // min-max band and mean line per series
+  PANGENOME_SERIES.forEach(series => {
+    const upper = rows.map(r => `${xPos(r.genomes)},${yPos(r[series.key + "_max"])}`);
+    const lower = rows.slice().reverse().map(r => `${xPos(r.genomes)},${yPos(r[series.key + "_min"])}`);
+    el("polygon", { points: upper.concat(lower).join(" "), fill: series.color, "fill-opacity": 0.25, stroke: "none" });
+    el("polyline", { points: rows.map(r => `${xPos(r.genomes)},${yPos(r[series.key + "_mean"])}`).join(" "),
+                     fill: "none", stroke: series.color, "stroke-width": 4 });
+
+    rows.forEach(r => {
+      const dot = el("circle", { cx: xPos(r.genomes), cy: yPos(r[series.key + "_mean"]), r: 6, fill: series.color });
+      dot.addEventListener("mousemove", (evt) => {
+        showOrthoTip(
+          evt,
+          `<div style="font-weight:600; margin-bottom:2px;">${series.label}</div>
+           <div>${r.genomes} genomes: <span style="font-weight:600;">${r[series.key + "_mean"].toFixed(1)}</span>
+           (${r[series.key + "_min"]}-${r[series.key + "_max"]})</div>`
+        );
+      });
+      dot.addEventListener("mouseleave", hideOrthoTip);
+    });
+  });
+
+  // legend
+  PANGENOME_SERIES.forEach((series, i) => {
+    const yy = margin.top + i * Math.round(FONT * 1.15);
+    const lx = margin.left + innerW + 24;
+    el("rect", { x: lx, y: yy, width: Math.round(FONT * 0.9), height: Math.round(FONT * 0.9), fill: series.color, stroke: "#999", "stroke-width": 1 });
+    el("text", { x: lx + Math.round(FONT * 0.9) + 14, y: yy + FONT * 0.75, "font-family": "sans-serif", "font-size": FONT, fill: "#111" }, series.label);
+  });
+
+  container.innerHTML = "";
+  container.appendChild(svg);
+}
+
+function pangenomeDescription(pangenome) {
+  const h = (pangenome && pangenome.heaps) || {};
+  const alpha = h.heaps_alpha && h.heaps_alpha !== "NA" ? Number(h.heaps_alpha).toFixed(3) : null;
+  const openness = h.openness === "open"
+    ? "open (new orthogroups keep being found as genomes are added)"
+    : h.openness === "closed" ? "closed (few new orthogroups are expected from further genomes)" : null;
+
+  let text = `Pan- and core-genome size over ${h.iterations || "?"} random orders of the ${h.genomes || "?"} genomes (seed ${h.seed || "?"}).`;
+  if (alpha && openness) {
+    text += ` Heaps' law fit to the new orthogroups per added genome gives &alpha; = ${alpha}
+      (R&sup2; = ${Number(h.heaps_r_squared).toFixed(3)}): the pan-genome is ${openness}.`;
+  } else {
+    text += " Too few genomes to fit Heaps' law.";
+  }
+  return text;
+}
+
+// ----------------------------
+// Orthogroup intersections (UpSet): set sizes, intersection sizes and membership matrix
+// ----------------------------
+function renderUpsetChart(sets, containerId, maxRows, highlight) {
+  const container = document.getElementById(containerId);
+  if (!container) return;
+
+  const rows = (sets && Array.isArray(sets.rows)) ? sets.rows.slice(0, maxRows) : [];
+  if (!rows.length) {
+    container.innerHTML = "<p>No orthogroup intersections available.</p>";
+    return;
+  }
+
+  const FONT = 26;
+  const TICK_FONT = Math.round(FONT * 0.9);
+  const names = sets.sets;
+  const dot = 26;                                    // column width / row height of the matrix
+  const labelW = Math.max(...names.map(n => n.length), 4) * FONT * 0.6 + 24;
+  const setBarW = 320;
+  const barH = 420;
+  const margin = { top: 30, right: 40, bottom: 40, left: 20 };
+
+  const matrixX = margin.left + setBarW + labelW;
+  const matrixY = margin.top + barH + 20;
+  const width = matrixX + rows.length * dot + margin.right;
+  const height = matrixY + names.length * dot + margin.bottom + TICK_FONT * 2;
+
+  const maxCount = Math.max(...rows.map(r => r.count), 1);
+  const stepY = niceStep(maxCount / 5);
+  const axisMaxY = Math.ceil(maxCount / stepY) * stepY;
+  const maxSet = Math.max(...sets.set_sizes, 1);
+
+  const ns = "http://www.w3.org/2000/svg";
+  const svg = document.createElementNS(ns, "svg");
+  svg.setAttribute("viewBox", `0 0 ${width} ${height}`);
+  svg.setAttribute("width", String(width));
+  svg.setAttribute("height", String(height));
+  svg.style.display = "block";
+
+  function el(tag, attrs, text) {
+    const e = document.createElementNS(ns, tag);
+    Object.entries(attrs).forEach(([k, v]) => e.setAttribute(k, String(v)));
+    if (text !== undefined) e.textContent = text;
+    svg.appendChild(e);
+    return e;
+  }
+
+  const active = BREWER_SET2_8[1];
+  const muted = "#555";
+  const isHighlighted = r => highlight === null || r.sets.includes(highlight);
+
+  // alternate row stripes behind the matrix
+  names.forEach((_, k) => {
+    if (k % 2 === 0) {
+      el("rect", { x: margin.left + setBarW, y: matrixY + k * dot, width: width - margin.right - margin.left - setBarW, height: dot, fill: "#f5f5f5" });
+    }
+  });
+
+  // intersection size axis
+  el("line", { x1: matrixX - 6, x2: matrixX - 6, y1: margin.top, y2: margin.top + barH, stroke: "#111", "stroke-width": 2 });
+  for (let v = 0; v <= axisMaxY + 1e-9; v += stepY) {
+    const y = margin.top + barH - (v / axisMaxY) * barH;
+    el("line", { x1: matrixX - 6, x2: width - margin.right, y1: y, y2: y, stroke: "#e5e5e5", "stroke-width": 1 });
+    el("text", { x: matrixX - 14, y: y + TICK_FONT * 0.35, "text-anchor": "end", "font-family": "sans-serif", "font-size": TICK_FONT, fill: "#111" }, String(v));
+  }
+
+  // intersection bars and membership matrix columns
+  rows.forEach((r, c) => {
+    const cx = matrixX + c * dot + dot / 2;
+    const h = (r.count / axisMaxY) * barH;
+    const colour = isHighlighted(r) ? active : "#cccccc";
+    const bar = el("rect", { x: cx - dot * 0.35, y: margin.top + barH - h, width: dot * 0.7, height: h, fill: colour });
+
+    const members = r.sets.map(k => names[k]);
+    bar.addEventListener("mousemove", (evt) => {
+      showOrthoTip(
+        evt,
+        `<div style="font-weight:600; margin-bottom:2px;">${r.count} orthogroups</div>
+         <div>${members.length === names.length ? "In all sets" : `Only in: ${members.join(", ")}`}</div>`
+      );
+    });
+    bar.addEventListener("mouseleave", hideOrthoTip);
+
+    const ys = r.sets.map(k => matrixY + k * dot + dot / 2);
+    names.forEach((_, k) => {
+      el("circle", { cx: cx, cy: matrixY + k * dot + dot / 2, r: dot * 0.3, fill: r.sets.includes(k) ? (isHighlighted(r) ? muted : "#999") : "#e5e5e5" });
+    });
+    if (ys.length > 1) {
+      el("line", { x1: cx, x2: cx, y1: Math.min(...ys), y2: Math.max(...ys), stroke: isHighlighted(r) ? muted : "#999", "stroke-width": 4 });
+    }
+  });
+
+  // set names (click to highlight) and set size bars
+  names.forEach((name, k) => {
+    const y = matrixY + k * dot;
+    const label = el("text", { x: matrixX - 12, y: y + dot * 0.78, "text-anchor": "end", "font-family": "sans-serif",
+                               "font-size": Math.min(FONT, dot * 0.9), fill: highlight === k ? active : "#111", cursor: "pointer" }, name);
+    label.addEventListener("click", () => renderUpsetChart(sets, containerId, maxRows, highlight === k ? null : k));
+
+    const w = (sets.set_sizes[k] / maxSet) * (setBarW - 20);
+    const bar = el("rect", { x: margin.left + setBarW - w, y: y + dot * 0.15, width: w, height: dot * 0.7, fill: BREWER_SET2_8[0] });
+    bar.addEventListener("mousemove", (evt) => {
+      showOrthoTip(evt, `<div style="font-weight:600;">${name}</div><div>${sets.set_sizes[k]} orthogroups</div>`);
+    });
+    bar.addEventListener("mouseleave", hideOrthoTip);
+  });
+  el("text", { x: margin.left + setBarW - 10, y: height - margin.bottom + TICK_FONT * 0.5, "text-anchor": "end", "font-family": "sans-serif", "font-size": TICK_FONT, fill: "#111" }, "Set size");
+  el("text", { x: matrixX, y: margin.top - 8, "font-family": "sans-serif", "font-size": TICK_FONT, fill: "#111" }, "Intersection size");
+
+  container.innerHTML = "";
+  container.appendChild(svg);
+}
+
+function upsetGroupTable(upset) {
+  if (!upset.group_info || !upset.group_info.length) return "";
+  const source = s => s === "clade" ? "Tree clade" : "Repo spec";
+  return `
+    <table class="ortho-table">
+      <thead><tr><th>Group</th><th>Defined by</th><th>Genomes</th><th>Group-specific orthogroups</th></tr></thead>
+      <tbody>
+        ${upset.group_info.map(g => `
+          <tr><td>${g.name}</td><td>${source(g.source)}</td><td>${g.genomes.join(", ")}</td><td>${g.specific}</td></tr>
+        `).join("")}
+      </tbody>
+    </table>
+  `;
+}
+
 function exportSvgElement(svgEl, filename) {
   const clone = svgEl.cloneNode(true);
   inlineSvgComputedStyles(clone);
@@ -363,6 +633,7 @@ function formatOrthoTool(method) {
     if (method === "orthomcl") return "OrthoMCL";
     if (method === "rbh") return "Reciprocal Best Hits (RBH)";
     if (method === "orthofinder") return "OrthoFinder";
+    if (method === "synteny") return "Synteny-aware orthology";
     return method;
 }
 
@@ -405,12 +676,13 @@ SYNIMA.showOrthologs = function () {
     <tr><th>Orthology tool</th><td>${orthoTool}</td></tr>
   `;
 
-  // Shared parameters (apply to BLAST and DIAMOND)
-  if (params.max_target_seqs !== undefined) {
+  // Shared parameters (apply to BLAST and DIAMOND, not to imported search results)
+  const searchRun = params.aligner !== "imported";
+  if (searchRun && params.max_target_seqs !== undefined) {
     paramRows += `<tr><th>Max target seqs</th><td>${params.max_target_seqs}</td></tr>`;
   }
 
-  if (params.evalue !== undefined) {
+  if (searchRun && params.evalue !== undefined) {
     paramRows += `<tr><th>E-value</th><td>${params.evalue}</td></tr>`;
   }
 
@@ -419,6 +691,36 @@ SYNIMA.showOrthologs = function () {
     paramRows += `<tr><th>Diamond sensitivity</th><td>${params.diamond_sensitivity}</td></tr>`;
   }
 
+  // MMseqs2-specific settings
+  if (params.aligner === "mmseqs" && params.mmseqs_sensitivity !== undefined) {
+    paramRows += `<tr><th>MMseqs2 sensitivity</th><td>${params.mmseqs_sensitivity}</td></tr>`;
+  }
+
+  // Strict reciprocal best hit settings
+  if (data.summaries[0].method === "rbh" && params.rbh_tie_tolerance !== undefined) {
+    paramRows += `<tr><th>RBH tie tolerance</th><td>${params.rbh_tie_tolerance}</td></tr>`;
+    paramRows += `<tr><th>RBH min coverage</th><td>${params.rbh_min_coverage}</td></tr>`;
+  }
+
+  // OrthoMCL settings
+  if (data.summaries[0].method === "orthomcl" && params.inflation !== undefined) {
+    paramRows += `<tr><th>OrthoMCL e-value cutoff</th><td>${params.omcl_evalue_cutoff}</td></tr>`;
+    paramRows += `<tr><th>MCL inflation</th><td>${params.inflation}</td></tr>`;
+  }
+
+  // Markov clustering settings
+  if (data.summaries[0].method === "mcl" && params.inflation !== undefined) {
+    paramRows += `<tr><th>MCL graph</th><td>${params.mcl_graph}</td></tr>`;
+    paramRows += `<tr><th>MCL inflation</th><td>${params.inflation}</td></tr>`;
+  }
+
+  // Synteny-aware orthology settings
+  if (data.summaries[0].method === "synteny" && params.synteny_max_gap !== undefined) {
+    paramRows += `<tr><th>MCL inflation</th><td>${params.inflation}</td></tr>`;
+    paramRows += `<tr><th>Synteny max gap (genes)</th><td>${params.synteny_max_gap}</td></tr>`;
+    paramRows += `<tr><th>Synteny min anchors</th><td>${params.synteny_min_anchors}</td></tr>`;
+  }
+
   // BLAST-specific things (none yet, but easily added later)
 
   // Always relevant for translation of coding sequences
@@ -452,10 +754,7 @@ SYNIMA.showOrthologs = function () {
       <thead>
       <tr>
         <th>Genome</th>
-        <th>Core (1:1)</th>
-        <th>Core (multi)</th>
-        <th>Aux</th>
-        <th>Unique</th>
+        ${orthoCatsFor(summary).map(c => `<th>${c.label}</th>`).join("")}
       </tr>
       </thead>
       <tbody>
@@ -465,15 +764,17 @@ SYNIMA.showOrthologs = function () {
           html += `
             <tr>
               <td>${row.genome}</td>
-              <td>${row.core_1to1}</td>
-              <td>${row.core_multi}</td>
-              <td>${row.aux}</td>
-              <td>${row.unique}</td>
+              ${orthoCatsFor(summary).map(c => `<td>${row[c.key]}</td>`).join("")}
             </tr>
           `;
     });
     html += `</tbody></table>`;
 
+    const thresholdText = orthoThresholdText(summary);
+    if (thresholdText) {
+      html += `<p>${thresholdText}</p>`;
+    }
+
     // ----------------------------
     // Ortholog stacked barchart & Download buttons
     // ----------------------------
@@ -510,11 +811,107 @@ SYNIMA.showOrthologs = function () {
       </div>
     `;
 
-    chartJobs.push({ summary, chartId, menuId, i });
-
       //  <h2>Plot</h2>
 
 
+    // ----------------------------
+    // Pan/core-genome curves & Download buttons
+    // ----------------------------
+    const panId = `pan-chart-${i}`;
+    const panMenuId = `pan-dl-${i}`;
+    if (summary.pangenome) {
+      html += `
+        <div style="display:flex; align-items:flex-end; justify-content:space-between; margin-bottom:6px;">
+          <h2 style="margin:0;">Pan-genome accumulation</h2>
+
+          <div style="position:relative; display:inline-block;">
+            <button id="${panMenuId}-btn" style="padding:2px 6px; margin:0;">Download ▾</button>
+
+            <div id="${panMenuId}-dd" class="hidden"
+              style="position:absolute; right:0; top:100%; margin-top:2px; background:white; color:black;
+                     border:1px solid #ccc; border-radius:4px; box-shadow:0 2px 4px rgba(0,0,0,0.2);
+                     z-index:1000; width:120px;">
+              <button id="${panMenuId}-svg"
+                style="display:block; width:100%; text-align:left; padding:4px 8px; border:none; background:none; cursor:pointer;"
+                onmouseover="this.style.background='#e5e5e5'" onmouseout="this.style.background='none'">
+                SVG
+              </button>
+              <button id="${panMenuId}-png"
+                style="display:block; width:100%; text-align:left; padding:4px 8px; border:none; background:none; cursor:pointer;"
+                onmouseover="this.style.background='#e5e5e5'" onmouseout="this.style.background='none'">
+                PNG
+              </button>
+            </div>
+          </div>
+        </div>
+
+        <p>${pangenomeDescription(summary.pangenome)}</p>
+
+        <div class="tree-view" style="--synima-tree-bg:#ffffff;">
+          <div id="${panId}" style="width:100%; overflow-x:auto;"></div>
+        </div>
+      `;
+    }
+
+    // ----------------------------
+    // Orthogroup intersections (UpSet) & Download buttons
+    // ----------------------------
+    const upsetId = `upset-chart-${i}`;
+    const upsetMenuId = `upset-dl-${i}`;
+    if (summary.upset) {
+      const hasGroups = !!summary.upset.groups;
+      html += `
+        <div style="display:flex; align-items:flex-end; justify-content:space-between; margin-bottom:6px;">
+          <h2 style="margin:0;">Orthogroup intersections</h2>
+
+          <div style="display:flex; gap:8px; align-items:center;">
+            ${hasGroups ? `
+            <select id="${upsetId}-sets" style="padding:2px 6px;">
+              <option value="genomes">Genomes</option>
+              <option value="groups">Groups</option>
+            </select>` : ""}
+            <select id="${upsetId}-rows" style="padding:2px 6px;">
+              <option value="20">Top 20</option>
+              <option value="40" selected>Top 40</option>
+              <option value="100">Top 100</option>
+              <option value="100000">All</option>
+            </select>
+
+            <div style="position:relative; display:inline-block;">
+              <button id="${upsetMenuId}-btn" style="padding:2px 6px; margin:0;">Download ▾</button>
+
+              <div id="${upsetMenuId}-dd" class="hidden"
+                style="position:absolute; right:0; top:100%; margin-top:2px; background:white; color:black;
+                       border:1px solid #ccc; border-radius:4px; box-shadow:0 2px 4px rgba(0,0,0,0.2);
+                       z-index:1000; width:120px;">
+                <button id="${upsetMenuId}-svg"
+                  style="display:block; width:100%; text-align:left; padding:4px 8px; border:none; background:none; cursor:pointer;"
+                  onmouseover="this.style.background='#e5e5e5'" onmouseout="this.style.background='none'">
+                  SVG
+                </button>
+                <button id="${upsetMenuId}-png"
+                  style="display:block; width:100%; text-align:left; padding:4px 8px; border:none; background:none; cursor:pointer;"
+                  onmouseover="this.style.background='#e5e5e5'" onmouseout="this.style.background='none'">
+                  PNG
+                </button>
+              </div>
+            </div>
+          </div>
+        </div>
+
+        <p>Orthogroups (including unique genes) shared by exactly each combination of ${hasGroups ? "genomes or groups (a group shares an orthogroup if any of its genomes has it)" : "genomes"}.
+        Click a name to highlight the intersections that include it.</p>
+
+        <div class="tree-view" style="--synima-tree-bg:#ffffff;">
+          <div id="${upsetId}" style="width:100%; overflow-x:auto;"></div>
+        </div>
+
+        ${upsetGroupTable(summary.upset)}
+      `;
+    }
+
+    chartJobs.push({ summary, chartId, menuId, panId, panMenuId, upsetId, upsetMenuId, i });
+
     // ----------------------------
     // Rscript 
     // ----------------------------
@@ -550,6 +947,51 @@ SYNIMA.showOrthologs = function () {
       SYNIMA.exportOrthologChartPng(job.chartId, `synima_orthologs_${job.i}.png`);
     });
   }
+
+  if (job.summary.upset) {
+    const setsSelect = document.getElementById(`${job.upsetId}-sets`);
+    const rowsSelect = document.getElementById(`${job.upsetId}-rows`);
+    const drawUpset = () => {
+      const mode = setsSelect ? setsSelect.value : "genomes";
+      const sets = mode === "groups" ? job.summary.upset.groups : job.summary.upset.genomes;
+      renderUpsetChart(sets, job.upsetId, Number(rowsSelect ? rowsSelect.value : 40), null);
+    };
+    drawUpset();
+    if (setsSelect) setsSelect.addEventListener("change", drawUpset);
+    if (rowsSelect) rowsSelect.addEventListener("change", drawUpset);
+
+    wireDropdown(`${job.upsetMenuId}-btn`, `${job.upsetMenuId}-dd`);
+    const upsetSvgBtn = document.getElementById(`${job.upsetMenuId}-svg`);
+    const upsetPngBtn = document.getElementById(`${job.upsetMenuId}-png`);
+    if (upsetSvgBtn) {
+      upsetSvgBtn.addEventListener("click", () => {
+        SYNIMA.exportOrthologChartSvg(job.upsetId, `synima_intersections_${job.i}.svg`);
+      });
+    }
+    if (upsetPngBtn) {
+      upsetPngBtn.addEventListener("click", () => {
+        SYNIMA.exportOrthologChartPng(job.upsetId, `synima_intersections_${job.i}.png`);
+      });
+    }
+  }
+
+  if (job.summary.pangenome) {
+    renderPangenomeChart(job.summary.pangenome, job.panId);
+    wireDropdown(`${job.panMenuId}-btn`, `${job.panMenuId}-dd`);
+
+    const panSvgBtn = document.getElementById(`${job.panMenuId}-svg`);
+    const panPngBtn = document.getElementById(`${job.panMenuId}-png`);
+    if (panSvgBtn) {
+      panSvgBtn.addEventListener("click", () => {
+        SYNIMA.exportOrthologChartSvg(job.panId, `synima_pangenome_${job.i}.svg`);
+      });
+    }
+    if (panPngBtn) {
+      panPngBtn.addEventListener("click", () => {
+        SYNIMA.exportOrthologChartPng(job.panId, `synima_pangenome_${job.i}.png`);
+      });
+    }
+  }
 });
 
 
@@ -1153,6 +1153,9 @@ SYNIMA.showTree = function () {
     return;
   }
 
+    // Rooted species tree imported from a full OrthoFinder run
+    const speciesTree = treeItem.file_name.startsWith("SpeciesTree_rooted.");
+
     // ----------------------------
     // Header / Download
     // ----------------------------
@@ -1164,8 +1167,8 @@ SYNIMA.showTree = function () {
         <table class="param-table">
           <tr><th>Sequence type</th><td>${seqType}</td></tr>
           <tr><th>Orthology tool</th><td>${orthoTool}</td></tr>
-          <tr><th>Multiple alignment</th><td>MUSCLE v5</td></tr>
-          <tr><th>Tree builder</th><td>FastTree</td></tr>
+          <tr><th>Multiple alignment</th><td>${speciesTree ? "-" : "MUSCLE v5"}</td></tr>
+          <tr><th>Tree builder</th><td>${speciesTree ? "OrthoFinder species tree (STAG/STRIDE)" : "FastTree"}</td></tr>
           <tr><th>Tree file</th><td>${treeItem.file_name}</td></tr>
         </table>
       </div>